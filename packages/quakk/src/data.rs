@@ -1,44 +1,390 @@
-use std::{any::Any, fmt::Debug};
+//! [`Data`] is the value flowing through the edges of the graph
+//!
+//! `Data` is reference counted, cloning it is cheap and shares the underlying value, which is only
+//! copied when mutated through [`Data::make_mut`] while shared. Scalars (`bool`, `i64`, `f32` and
+//! `f64`) are held inline instead, creating or cloning them never allocates.
+//!
+//! Any type implementing [`Any`] and [`Debug`] can be carried, but a few built-in types have
+//! first-class support, with non-consuming accessors and well defined conversions :
+//!
+//! | type         | accessor            | converts from                     |
+//! |--------------|---------------------|-----------------------------------|
+//! | `bool`       | [`Data::as_bool`]   | numbers ([`Data::to_bool`])       |
+//! | `i64`        | [`Data::as_i64`]    | floats, bool ([`Data::to_i64`])   |
+//! | `f32`        | [`Data::as_f32`]    | numbers, bool ([`Data::to_f32`])  |
+//! | `f64`        | [`Data::as_f64`]    | numbers, bool ([`Data::to_f64`])  |
+//! | `String`     | [`Data::as_text`]   | numbers, bool ([`Data::to_text`]) |
+//! | [`DataList`] | [`Data::as_list`]   |                                   |
+//! | [`DataMap`]  | [`Data::as_map`]    |                                   |
+use std::{any::Any, collections::BTreeMap, fmt::Debug, sync::Arc};
 
 use anyhow::anyhow;
 
-pub trait DataType: Any + Debug {}
+/// A list of values
+pub type DataList = Vec<Data>;
 
-impl<T> DataType for T where T: Any + Debug {}
+/// A map of values, indexed by name
+pub type DataMap = BTreeMap<String, Data>;
 
+pub trait DataType: Any + Debug + Send + Sync {
+    /// The name of the concrete type, used in error messages
+    fn type_name(&self) -> &'static str;
+}
+
+impl<T> DataType for T
+where
+    T: Any + Debug + Send + Sync,
+{
+    fn type_name(&self) -> &'static str {
+        std::any::type_name::<T>()
+    }
+}
+
+#[derive(Clone)]
 pub struct Data {
-    inner: Box<dyn DataType>,
+    inner: Inner,
+}
+
+/// The value of a [`Data`], scalars being held inline
+#[derive(Clone)]
+enum Inner {
+    Bool(bool),
+    I64(i64),
+    F32(f32),
+    F64(f64),
+    Shared(Arc<dyn DataType>),
 }
 
 impl Data {
     pub fn new(value: impl DataType) -> Self {
-        Data {
-            inner: Box::new(value),
+        let any = &value as &dyn Any;
+
+        let inner = if let Some(value) = any.downcast_ref::<f32>() {
+            Inner::F32(*value)
+        } else if let Some(value) = any.downcast_ref::<f64>() {
+            Inner::F64(*value)
+        } else if let Some(value) = any.downcast_ref::<i64>() {
+            Inner::I64(*value)
+        } else if let Some(value) = any.downcast_ref::<bool>() {
+            Inner::Bool(*value)
+        } else {
+            Inner::Shared(Arc::new(value))
+        };
+
+        Data { inner }
+    }
+
+    /// The contained value
+    fn value(&self) -> &dyn DataType {
+        match &self.inner {
+            Inner::Bool(value) => value,
+            Inner::I64(value) => value,
+            Inner::F32(value) => value,
+            Inner::F64(value) => value,
+            Inner::Shared(value) => &**value,
         }
     }
 
     pub fn into_f32(self) -> Result<f32, anyhow::Error> {
-        self.downcast::<f32>().ok_or(anyhow!("not an f32"))
+        let found = self.type_name();
+        self.downcast::<f32>().ok_or_else(|| mismatch("f32", found))
     }
 
     pub fn into_string(self) -> Result<String, anyhow::Error> {
-        self.downcast::<String>().ok_or(anyhow!("not an f32"))
+        let found = self.type_name();
+        self.downcast::<String>()
+            .ok_or_else(|| mismatch("text", found))
     }
 
-    pub fn downcast<T: DataType>(self) -> Option<T> {
-        (self.inner as Box<dyn Any>)
-            .downcast::<T>()
-            .ok()
-            .map(|data| *data)
+    /// Take the contained value out, it is only cloned if the value is shared
+    pub fn downcast<T: DataType + Clone>(self) -> Option<T> {
+        match self.inner {
+            Inner::Shared(value) => (value as Arc<dyn Any + Send + Sync>)
+                .downcast::<T>()
+                .ok()
+                .map(Arc::unwrap_or_clone),
+            _ => self.downcast_ref::<T>().cloned(),
+        }
     }
 
     pub fn downcast_ref<T: DataType>(&self) -> Option<&T> {
-        ((&*self.inner) as &dyn Any).downcast_ref::<T>()
+        (self.value() as &dyn Any).downcast_ref::<T>()
+    }
+
+    /// Get a mutable reference to the contained value, cloning it first if it is shared with
+    /// other `Data` (copy-on-write)
+    ///
+    /// Return `None` if the contained value is not of type `T`
+    /// ```
+    /// # use quakk::Data;
+    /// let mut data = Data::new(1.0f32);
+    /// let shared = data.clone();
+    ///
+    /// *data.make_mut::<f32>().unwrap() += 1.0;
+    /// assert_eq!(data.as_f32().unwrap(), 2.0);
+    /// assert_eq!(shared.as_f32().unwrap(), 1.0);
+    /// ```
+    pub fn make_mut<T: DataType + Clone>(&mut self) -> Option<&mut T> {
+        let value: &mut dyn Any = match &mut self.inner {
+            Inner::Bool(value) => value,
+            Inner::I64(value) => value,
+            Inner::F32(value) => value,
+            Inner::F64(value) => value,
+            Inner::Shared(shared) => {
+                if Arc::get_mut(shared).is_none() {
+                    let value = (&**shared as &dyn Any).downcast_ref::<T>()?.clone();
+                    *shared = Arc::new(value);
+                }
+
+                Arc::get_mut(shared)?
+            }
+        };
+
+        value.downcast_mut::<T>()
+    }
+
+    /// Do both `Data` share the same underlying value, scalars being held inline are never shared
+    pub fn ptr_eq(&self, other: &Data) -> bool {
+        match (&self.inner, &other.inner) {
+            (Inner::Shared(value), Inner::Shared(other)) => Arc::ptr_eq(value, other),
+            _ => false,
+        }
+    }
+
+    /// Is the contained value of type `T`
+    pub fn is<T: DataType>(&self) -> bool {
+        self.downcast_ref::<T>().is_some()
+    }
+
+    /// The name of the contained type, built-in types have short names (`bool`, `i64`, `f32`,
+    /// `f64`, `text`, `list` and `map`)
+    pub fn type_name(&self) -> &'static str {
+        if self.is::<bool>() {
+            "bool"
+        } else if self.is::<i64>() {
+            "i64"
+        } else if self.is::<f32>() {
+            "f32"
+        } else if self.is::<f64>() {
+            "f64"
+        } else if self.is::<String>() {
+            "text"
+        } else if self.is::<DataList>() {
+            "list"
+        } else if self.is::<DataMap>() {
+            "map"
+        } else {
+            self.value().type_name()
+        }
     }
 }
 
+/// # Accessors
+///
+/// Accessors do not consume the value, and only succeed if the contained value is exactly of
+/// the requested type
+impl Data {
+    pub fn as_bool(&self) -> anyhow::Result<bool> {
+        self.strict::<bool>("bool").copied()
+    }
+
+    pub fn as_i64(&self) -> anyhow::Result<i64> {
+        self.strict::<i64>("i64").copied()
+    }
+
+    pub fn as_f32(&self) -> anyhow::Result<f32> {
+        self.strict::<f32>("f32").copied()
+    }
+
+    pub fn as_f64(&self) -> anyhow::Result<f64> {
+        self.strict::<f64>("f64").copied()
+    }
+
+    pub fn as_text(&self) -> anyhow::Result<&str> {
+        self.strict::<String>("text").map(String::as_str)
+    }
+
+    pub fn as_list(&self) -> anyhow::Result<&[Data]> {
+        self.strict::<DataList>("list").map(Vec::as_slice)
+    }
+
+    pub fn as_map(&self) -> anyhow::Result<&DataMap> {
+        self.strict::<DataMap>("map")
+    }
+
+    fn strict<T: DataType>(&self, expected: &str) -> anyhow::Result<&T> {
+        self.downcast_ref::<T>()
+            .ok_or_else(|| mismatch(expected, self.type_name()))
+    }
+}
+
+/// # Conversions
+///
+/// Conversions do not consume the value, and follow these rules :
+/// - numbers convert to each others, floats are truncated toward zero when converted to `i64`,
+///   non finite floats cannot be converted to `i64`
+/// - `bool` converts to `1` or `0`, and numbers convert to `true` when they are neither zero nor NaN
+/// - numbers and `bool` convert to text using their usual representation
+/// - text is never implicitly parsed as a number
+impl Data {
+    pub fn to_f64(&self) -> anyhow::Result<f64> {
+        if let Some(value) = self.downcast_ref::<f64>() {
+            Ok(*value)
+        } else if let Some(value) = self.downcast_ref::<f32>() {
+            Ok(*value as f64)
+        } else if let Some(value) = self.downcast_ref::<i64>() {
+            Ok(*value as f64)
+        } else if let Some(value) = self.downcast_ref::<bool>() {
+            Ok(if *value { 1.0 } else { 0.0 })
+        } else {
+            Err(mismatch("a number", self.type_name()))
+        }
+    }
+
+    pub fn to_f32(&self) -> anyhow::Result<f32> {
+        match self.downcast_ref::<f32>() {
+            Some(value) => Ok(*value),
+            None => self.to_f64().map(|value| value as f32),
+        }
+    }
+
+    pub fn to_i64(&self) -> anyhow::Result<i64> {
+        if let Some(value) = self.downcast_ref::<i64>() {
+            return Ok(*value);
+        }
+
+        let value = self.to_f64()?;
+        if value.is_finite() {
+            Ok(value.trunc() as i64)
+        } else {
+            Err(anyhow!(
+                "cannot convert the non finite {} `{value}` to i64",
+                self.type_name()
+            ))
+        }
+    }
+
+    pub fn to_bool(&self) -> anyhow::Result<bool> {
+        match self.downcast_ref::<bool>() {
+            Some(value) => Ok(*value),
+            None => self
+                .to_f64()
+                .map(|value| value != 0.0 && !value.is_nan())
+                .map_err(|_| mismatch("bool", self.type_name())),
+        }
+    }
+
+    pub fn to_text(&self) -> anyhow::Result<String> {
+        if let Some(value) = self.downcast_ref::<String>() {
+            Ok(value.clone())
+        } else if let Some(value) = self.downcast_ref::<f32>() {
+            Ok(value.to_string())
+        } else if let Some(value) = self.downcast_ref::<f64>() {
+            Ok(value.to_string())
+        } else if let Some(value) = self.downcast_ref::<i64>() {
+            Ok(value.to_string())
+        } else if let Some(value) = self.downcast_ref::<bool>() {
+            Ok(value.to_string())
+        } else {
+            Err(mismatch("text", self.type_name()))
+        }
+    }
+}
+
+fn mismatch(expected: &str, found: &str) -> anyhow::Error {
+    anyhow!("type mismatch, expected {expected} but found {found}")
+}
+
 impl Debug for Data {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "Data: {:?}", self.inner)
+        write!(f, "Data: {:?}", self.value())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clones_share_their_value() {
+        let data = Data::new(vec![Data::new(1i64), Data::new(2i64)]);
+        let shared = data.clone();
+
+        assert!(data.ptr_eq(&shared));
+        assert_eq!(shared.as_list().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn scalars_are_inline() {
+        let mut data = Data::new(1.5f64);
+        let copy = data.clone();
+        assert!(!data.ptr_eq(&copy));
+
+        *data.make_mut::<f64>().unwrap() *= 2.0;
+        assert_eq!(data.as_f64().unwrap(), 3.0);
+        assert_eq!(copy.downcast::<f64>(), Some(1.5));
+        assert_eq!(format!("{data:?}"), "Data: 3.0");
+    }
+
+    #[test]
+    fn make_mut_copies_on_write() {
+        let mut data = Data::new("tick".to_string());
+        let previous = data.clone();
+
+        data.make_mut::<String>().unwrap().push_str(" tock");
+        assert!(!data.ptr_eq(&previous));
+        assert_eq!(data.as_text().unwrap(), "tick tock");
+        assert_eq!(previous.as_text().unwrap(), "tick");
+
+        // The value is not shared anymore, it is mutated in place
+        let before = data.clone();
+        drop(before);
+        let pointer = data.as_text().unwrap().as_ptr();
+        data.make_mut::<String>().unwrap().make_ascii_uppercase();
+        assert_eq!(data.as_text().unwrap().as_ptr(), pointer);
+
+        assert!(data.make_mut::<f32>().is_none());
+        assert_eq!(data.as_text().unwrap(), "TICK TOCK");
+    }
+
+    #[test]
+    fn accessors_are_strict() {
+        let data = Data::new(2.5f32);
+
+        assert_eq!(data.as_f32().unwrap(), 2.5);
+        assert_eq!(
+            data.as_bool().unwrap_err().to_string(),
+            "type mismatch, expected bool but found f32"
+        );
+        assert!(data.as_f64().is_err());
+
+        let list = Data::new(vec![Data::new(true), Data::new("a".to_string())]);
+        assert_eq!(list.type_name(), "list");
+        assert_eq!(list.as_list().unwrap()[1].as_text().unwrap(), "a");
+
+        let map = Data::new(DataMap::from([("bpm".to_string(), Data::new(120i64))]));
+        assert_eq!(map.as_map().unwrap()["bpm"].as_i64().unwrap(), 120);
+    }
+
+    #[test]
+    fn numeric_conversions() {
+        assert_eq!(Data::new(true).to_f32().unwrap(), 1.0);
+        assert_eq!(Data::new(3i64).to_f64().unwrap(), 3.0);
+        assert_eq!(Data::new(-2.7f32).to_i64().unwrap(), -2);
+        assert!(Data::new(f64::NAN).to_i64().is_err());
+        assert!(!Data::new(0.0f32).to_bool().unwrap());
+        assert!(Data::new(-1i64).to_bool().unwrap());
+        assert_eq!(
+            Data::new("1".to_string()).to_f32().unwrap_err().to_string(),
+            "type mismatch, expected a number but found text"
+        );
+    }
+
+    #[test]
+    fn text_conversions() {
+        assert_eq!(Data::new(1.5f32).to_text().unwrap(), "1.5");
+        assert_eq!(Data::new(42i64).to_text().unwrap(), "42");
+        assert_eq!(Data::new(false).to_text().unwrap(), "false");
+        assert!(Data::new(DataList::new()).to_text().is_err());
     }
 }
@@ -8,8 +8,10 @@ use std::{
     sync::{Arc, Mutex},
 };
 
+mod dot;
+
 use crate::{
-    Data, LasyFold, Meta, Node,
+    Data, GraphInputs, LasyFold, Meta, Node,
     id::{InId, InoutId, NodeId, NodeInId, NodeInoutId, NodeOutId, OutId},
     numeric::{ArithmeticsInId, NumericConstantOutId},
 };
@@ -96,11 +98,18 @@ impl Vertex {
     }
 
     pub fn inbound_for(&self, in_id: &dyn InId) -> Option<&NodeOutId> {
-        self.inbound.get(&dyn_clone::clone_box(in_id))
+        self.inbound.get(in_id)
     }
 
     pub fn outbound_for(&self, out_id: &dyn OutId) -> Option<&HashSet<NodeInId>> {
-        self.outbound.get(&dyn_clone::clone_box(out_id))
+        self.outbound.get(out_id)
+    }
+
+    /// Every inbound edge, by input
+    pub(crate) fn inbound(&self) -> impl Iterator<Item = (&dyn InId, &NodeOutId)> {
+        self.inbound
+            .iter()
+            .map(|(in_id, source)| (&**in_id, source))
     }
 }
 
@@ -265,19 +274,37 @@ impl Graph {
 #[derive(Debug, Default)]
 pub struct GraphIn;
 
+/// An output of the [`GraphIn`] node, that is an input of the graph
+///
+/// Besides the historical `Numeric` output, graph inputs are identified by name. A `Named` id
+/// should not use a name already taken by another variant
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum GraphInOutId {
     Numeric,
+    Named(String),
+}
+
+impl GraphInOutId {
+    pub fn named(name: &str) -> Self {
+        Self::Named(name.to_string())
+    }
 }
 
-impl OutId for GraphInOutId {}
+impl OutId for GraphInOutId {
+    fn name(&self) -> String {
+        match self {
+            Self::Numeric => "Numeric".to_string(),
+            Self::Named(name) => name.clone(),
+        }
+    }
+}
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum GraphInInId {
     Numeric,
 }
 
-impl InId for GraphInOutId {}
+impl InId for GraphInInId {}
 
 impl GraphIn {
     pub fn new() -> Self {
@@ -301,13 +328,8 @@ impl Node for GraphIn {
         "GraphIn"
     }
 
-    fn fold(&self, out_id: &dyn OutId, _lasy_fold: LasyFold, meta: Meta) -> anyhow::Result<Data> {
-        dbg!(self.title());
-
-        dbg!(out_id);
-        dbg!(meta);
-
-        Ok(Data::new(f32::default()))
+    fn fold(&self, out_id: &dyn OutId, lasy_fold: LasyFold, meta: Meta) -> anyhow::Result<Data> {
+        lasy_fold.get_graph_in(out_id, meta)
     }
 
     fn node_in_id(&self, in_id: &dyn InId, node_id: NodeId) -> Option<NodeInId> {
@@ -320,24 +342,73 @@ impl Node for GraphIn {
             .downcast_ref::<GraphInOutId>()
             .map(|out_id| NodeOutId::new(node_id, out_id))
     }
+
+    fn in_ids(&self) -> Vec<Box<dyn InId>> {
+        Vec::new()
+    }
+
+    fn out_ids(&self) -> Vec<Box<dyn OutId>> {
+        vec![Box::new(GraphInOutId::Numeric)]
+    }
 }
 
 #[derive(Debug, Default)]
 pub struct GraphOut;
 
+/// An input of the [`GraphOut`] node, see [`GraphOutOutId`]
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum GraphOutInId {
     Numeric,
+    Named(String),
 }
 
-impl InId for GraphOutInId {}
+impl GraphOutInId {
+    pub fn named(name: &str) -> Self {
+        Self::Named(name.to_string())
+    }
+}
 
+impl InId for GraphOutInId {
+    fn name(&self) -> String {
+        match self {
+            Self::Numeric => "Numeric".to_string(),
+            Self::Named(name) => name.clone(),
+        }
+    }
+}
+
+/// An output of the [`GraphOut`] node, that is an output of the graph
+///
+/// Each output forwards the [`GraphOutInId`] of the same name. A `Named` id should not use a
+/// name already taken by another variant
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum GraphOutOutId {
     Numeric,
+    Named(String),
 }
 
-impl OutId for GraphOutOutId {}
+impl GraphOutOutId {
+    pub fn named(name: &str) -> Self {
+        Self::Named(name.to_string())
+    }
+
+    /// Return the output with the given name, see [`OutId::name`]
+    pub fn from_name(name: &str) -> Self {
+        match name {
+            "Numeric" => Self::Numeric,
+            name => Self::named(name),
+        }
+    }
+}
+
+impl OutId for GraphOutOutId {
+    fn name(&self) -> String {
+        match self {
+            Self::Numeric => "Numeric".to_string(),
+            Self::Named(name) => name.clone(),
+        }
+    }
+}
 
 impl GraphOut {
     pub fn new() -> Self {
@@ -355,11 +426,10 @@ impl Node for GraphOut {
     }
 
     fn fold(&self, out_id: &dyn OutId, lasy_fold: LasyFold, meta: Meta) -> anyhow::Result<Data> {
-        dbg!(out_id);
-
         if let Some(out_id) = out_id.as_any().downcast_ref::<GraphOutOutId>() {
             match out_id {
                 GraphOutOutId::Numeric => lasy_fold.get_in(&GraphOutInId::Numeric, meta),
+                GraphOutOutId::Named(name) => lasy_fold.get_in(&GraphOutInId::named(name), meta),
             }
         } else {
             Err(anyhow!("not a valid out_id"))
@@ -376,26 +446,61 @@ impl Node for GraphOut {
     fn node_out_id(&self, out_id: &dyn OutId, node_id: NodeId) -> Option<NodeOutId> {
         None
     }
+
+    fn in_ids(&self) -> Vec<Box<dyn InId>> {
+        vec![Box::new(GraphOutInId::Numeric)]
+    }
+
+    fn out_ids(&self) -> Vec<Box<dyn OutId>> {
+        Vec::new()
+    }
 }
 
+/// A node wrapping a whole [`Graph`]
 #[derive(Debug)]
-struct Subgraph {
+pub struct Subgraph {
     graph: Arc<Mutex<Graph>>,
 }
 
+/// An input of a [`Subgraph`], subgraph inputs are dynamic and identified by name
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
-struct SubgraphInId {
+pub struct SubgraphInId {
     name: String,
 }
 
-impl InId for SubgraphInId {}
+impl SubgraphInId {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+        }
+    }
+}
+
+impl InId for SubgraphInId {
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+}
 
+/// An output of a [`Subgraph`], subgraph outputs are dynamic and identified by name
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
-struct SubgraphOutId {
+pub struct SubgraphOutId {
     name: String,
 }
 
-impl OutId for SubgraphOutId {}
+impl SubgraphOutId {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+        }
+    }
+}
+
+impl OutId for SubgraphOutId {
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+}
 
 impl Subgraph {
     pub fn new() -> Self {
@@ -403,6 +508,11 @@ impl Subgraph {
             graph: Arc::new(Mutex::new(Graph::new())),
         }
     }
+
+    /// Return a reference to the inner [`Graph`]
+    pub fn graph(&self) -> Arc<Mutex<Graph>> {
+        self.graph.clone()
+    }
 }
 
 impl Node for Subgraph {
@@ -410,34 +520,29 @@ impl Node for Subgraph {
         Self::default()
     }
 
-    fn fold(
-        &self,
-        container_out_id: &dyn OutId,
-        lasy_fold: LasyFold,
-        meta: Meta,
-    ) -> anyhow::Result<Data> {
-        // if self.id_for("out") == Some(container_out_id) {
-
-        //     let inner_out_id = {
-        //         self.graph
-        //             .lock()
-        //             .expect("the inner graph has been poisoned, who was it ?!")
-        //             .graph_out_id_for("out")
-        //             .context("out name not found for this graph")
-        //             bail
-        //     };
-
-        //     let lasy_fold = LasyFold::new(out_id.node_id(), self.graph.clone());
+    fn fold(&self, out_id: &dyn OutId, lasy_fold: LasyFold, meta: Meta) -> anyhow::Result<Data> {
+        let out_id = out_id
+            .as_any()
+            .downcast_ref::<SubgraphOutId>()
+            .ok_or(anyhow!("not a valid out_id"))?;
 
-        //     lasy_fold
-        //         .get_input(out_id.inout_id(), self.base_meta)
-        //         .ok_or(anyhow!("prout"))
+        let graph_out_handle = self
+            .graph
+            .lock()
+            .expect("the inner graph has been poisoned, who was it ?!")
+            .graph_out_handle();
 
-        //     lasy_fold.get_input(in_id, meta)
+        let inner_lasy_fold = LasyFold::new(NodeId::GraphOut, self.graph.clone())
+            .with_graph_inputs(GraphInputs::Parent(Rc::new(lasy_fold)));
 
-        // } else {
-        // }
-        Ok(Data::new(f32::default()))
+        graph_out_handle
+            .node()
+            .fold(
+                &GraphOutOutId::from_name(&out_id.name),
+                inner_lasy_fold,
+                meta,
+            )
+            .with_context(|| format!("Could not evaluate the subgraph output `{}`", out_id.name))
     }
 
     fn title(&self) -> &str {
@@ -457,6 +562,58 @@ impl Node for Subgraph {
             .downcast_ref::<SubgraphOutId>()
             .map(|out_id| NodeOutId::new(node_id, out_id))
     }
+
+    // Ports of a subgraph are the ones patched inside its graph
+    fn in_ids(&self) -> Vec<Box<dyn InId>> {
+        let graph = self
+            .graph
+            .lock()
+            .expect("the inner graph has been poisoned, who was it ?!");
+
+        let mut names: Vec<String> = graph
+            .vertex_for_id(NodeId::GraphIn)
+            .map(|vertex| {
+                vertex
+                    .outbound
+                    .iter()
+                    .filter(|(_, node_in_ids)| !node_in_ids.is_empty())
+                    .map(|(out_id, _)| out_id.name())
+                    .collect()
+            })
+            .unwrap_or_default();
+        names.sort();
+
+        names
+            .iter()
+            .map(|name| Box::new(SubgraphInId::new(name)) as Box<dyn InId>)
+            .collect()
+    }
+
+    fn out_ids(&self) -> Vec<Box<dyn OutId>> {
+        let graph = self
+            .graph
+            .lock()
+            .expect("the inner graph has been poisoned, who was it ?!");
+
+        let mut names: Vec<String> = graph
+            .vertex_for_id(NodeId::GraphOut)
+            .map(|vertex| vertex.inbound.keys().map(|in_id| in_id.name()).collect())
+            .unwrap_or_default();
+        names.sort();
+
+        names
+            .iter()
+            .map(|name| Box::new(SubgraphOutId::new(name)) as Box<dyn OutId>)
+            .collect()
+    }
+
+    fn inner_graph(&self) -> Option<Arc<Mutex<Graph>>> {
+        Some(self.graph.clone())
+    }
+
+    fn forwards_inner_graph(&self) -> bool {
+        true
+    }
 }
 
 impl Default for Subgraph {
@@ -470,3 +627,55 @@ impl Default for Graph {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod subgraph_tests {
+    use super::*;
+    use crate::{
+        numeric::{ArithmeticOperation, Arithmetics, ArithmeticsOutId},
+        testing::fold_node,
+    };
+
+    #[test]
+    fn subgraph_forwards_its_ins_and_outs() {
+        let subgraph = Subgraph::new();
+        {
+            let mut graph = subgraph.graph.lock().unwrap();
+            let multiply = graph.insert(Box::new(Arithmetics::new(
+                ArithmeticOperation::Multiplication,
+            )));
+
+            for (name, in_id) in [("a", ArithmeticsInId::Term1), ("b", ArithmeticsInId::Term2)] {
+                let graph_in = graph.graph_in_out_id(&GraphInOutId::named(name)).unwrap();
+                graph
+                    .patch(graph_in, multiply.node_in_id(&in_id).unwrap())
+                    .unwrap();
+            }
+
+            let product = graph
+                .graph_out_in_id(&GraphOutInId::named("product"))
+                .unwrap();
+            graph
+                .patch(
+                    multiply.node_out_id(&ArithmeticsOutId::Out).unwrap(),
+                    product,
+                )
+                .unwrap();
+        }
+
+        let names: Vec<String> = subgraph.in_ids().iter().map(|id| id.name()).collect();
+        assert_eq!(names, vec!["a", "b"]);
+        assert_eq!(subgraph.out_ids()[0].name(), "product");
+
+        let product = fold_node(
+            subgraph,
+            vec![
+                (&SubgraphInId::new("a"), Data::new(3.0f32)),
+                (&SubgraphInId::new("b"), Data::new(4.0f32)),
+            ],
+            &SubgraphOutId::new("product"),
+        )
+        .unwrap();
+        assert_eq!(product.as_f32().unwrap(), 12.0);
+    }
+}
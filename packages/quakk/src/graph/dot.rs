@@ -0,0 +1,261 @@
+//! Export a [`Graph`] as a [Graphviz](https://graphviz.org) DOT document
+//!
+//! Each node is drawn as a record, its inputs on the left, its title and short id in the
+//! middle, and its outputs on the right. Edges are labelled with the name of the ports they
+//! connect, and nodes wrapping an inner graph (like [`Subgraph`](crate::Subgraph)) are drawn as
+//! clusters containing the inner nodes.
+
+use std::fmt::Write;
+
+use crate::id::{InId, NodeId, OutId};
+
+use super::{Graph, Vertex};
+
+/// # Graph export
+impl Graph {
+    /// Render this graph and every nested graph as a DOT document
+    ///
+    /// The output is stable for a given graph, and can be rendered with `dot -Tsvg`
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::new();
+
+        dot.push_str("digraph quakk {\n");
+        dot.push_str("    rankdir=LR;\n");
+        dot.push_str("    node [shape=record];\n");
+        self.write_dot(&mut dot, "n", 1);
+        dot.push_str("}\n");
+
+        dot
+    }
+
+    /// Write every node and edge of this graph, prefixing node identifiers with `prefix` so
+    /// nested graphs do not collide with their parent
+    fn write_dot(&self, dot: &mut String, prefix: &str, depth: usize) {
+        let indent = "    ".repeat(depth);
+
+        let mut vertices: Vec<(&NodeId, &Vertex)> = self.vertices.iter().collect();
+        vertices.sort_by_key(|(node_id, _)| node_key(**node_id));
+
+        for (node_id, vertex) in &vertices {
+            let dot_id = dot_id(prefix, **node_id);
+            let node = vertex.node_handle.node();
+            let in_ids = vertex.in_ports();
+            let out_ids = vertex.out_ports();
+
+            let ins = in_ids
+                .iter()
+                .enumerate()
+                .map(|(index, in_id)| format!("<i{index}> {}", escape_record(&in_id.name())))
+                .collect::<Vec<_>>()
+                .join("|");
+            let outs = out_ids
+                .iter()
+                .enumerate()
+                .map(|(index, out_id)| format!("<o{index}> {}", escape_record(&out_id.name())))
+                .collect::<Vec<_>>()
+                .join("|");
+
+            let short_id = match node_id {
+                NodeId::Node(hash_id) => format!("\\n{}", hash_id.display_short()),
+                _ => String::new(),
+            };
+
+            let title = escape_record(node.title());
+            let record = format!(
+                "{indent}\"{dot_id}\" [label=\"{{{{{ins}}}|{title}{short_id}|{{{outs}}}}}\"];\n"
+            );
+
+            match node.inner_graph() {
+                Some(inner_graph) => {
+                    let inner_graph = inner_graph
+                        .lock()
+                        .expect("the inner graph has been poisoned, who was it ?!");
+
+                    let _ = writeln!(dot, "{indent}subgraph \"cluster_{dot_id}\" {{");
+                    let _ = writeln!(
+                        dot,
+                        "{indent}    label=\"{}{short_id}\";",
+                        escape_label(node.title())
+                    );
+                    dot.push_str("    ");
+                    dot.push_str(&record);
+                    inner_graph.write_dot(dot, &dot_id, depth + 1);
+                    let _ = writeln!(dot, "{indent}}}");
+                }
+                None => dot.push_str(&record),
+            }
+        }
+
+        let mut edges = Vec::new();
+        for (node_id, vertex) in &vertices {
+            let in_ids = vertex.in_ports();
+
+            for (in_id, node_out_id) in &vertex.inbound {
+                let in_index = in_ids.iter().position(|id| id == in_id);
+
+                let out_node_id = node_out_id.node_id();
+                let out_id = node_out_id.out_id_ref();
+                let out_index = self.vertices.get(&out_node_id).and_then(|out_vertex| {
+                    out_vertex.out_ports().iter().position(|id| **id == *out_id)
+                });
+
+                edges.push(format!(
+                    "{indent}\"{}\"{} -> \"{}\"{} [label=\"{} → {}\"];\n",
+                    dot_id(prefix, out_node_id),
+                    out_index
+                        .map(|index| format!(":o{index}"))
+                        .unwrap_or_default(),
+                    dot_id(prefix, **node_id),
+                    in_index
+                        .map(|index| format!(":i{index}"))
+                        .unwrap_or_default(),
+                    escape_label(&out_id.name()),
+                    escape_label(&in_id.name()),
+                ));
+            }
+        }
+
+        edges.sort();
+        edges.into_iter().for_each(|edge| dot.push_str(&edge));
+    }
+}
+
+impl Vertex {
+    /// Every input of this vertex's node : the ones it declares, followed by the patched ones it
+    /// does not declare (e.g. dynamic ports), sorted by name
+    fn in_ports(&self) -> Vec<Box<dyn InId>> {
+        let mut in_ids = self.node_handle.node().in_ids();
+        let mut extra_in_ids: Vec<_> = self
+            .inbound
+            .keys()
+            .filter(|in_id| !in_ids.contains(in_id))
+            .cloned()
+            .collect();
+        extra_in_ids.sort_by_key(|in_id| in_id.name());
+        in_ids.extend(extra_in_ids);
+
+        in_ids
+    }
+
+    /// Every output of this vertex's node, see [`Vertex::in_ports`]
+    fn out_ports(&self) -> Vec<Box<dyn OutId>> {
+        let mut out_ids = self.node_handle.node().out_ids();
+        let mut extra_out_ids: Vec<_> = self
+            .outbound
+            .iter()
+            .filter(|(out_id, node_in_ids)| !node_in_ids.is_empty() && !out_ids.contains(out_id))
+            .map(|(out_id, _)| out_id.clone())
+            .collect();
+        extra_out_ids.sort_by_key(|out_id| out_id.name());
+        out_ids.extend(extra_out_ids);
+
+        out_ids
+    }
+}
+
+/// A sort key giving a stable order to nodes, `GraphIn` first and `GraphOut` last
+fn node_key(node_id: NodeId) -> (u8, String) {
+    match node_id {
+        NodeId::GraphIn => (0, String::new()),
+        NodeId::Node(hash_id) => (1, hash_id.display()),
+        NodeId::GraphOut => (2, String::new()),
+    }
+}
+
+fn dot_id(prefix: &str, node_id: NodeId) -> String {
+    match node_id {
+        NodeId::GraphIn => format!("{prefix}_graph_in"),
+        NodeId::GraphOut => format!("{prefix}_graph_out"),
+        NodeId::Node(hash_id) => format!("{prefix}_{}", hash_id.display()),
+    }
+}
+
+/// Escape a string to be used inside a quoted DOT label
+fn escape_label(input: &str) -> String {
+    input.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// Escape a string to be used as a field of a record label, where braces, pipes and angle
+/// brackets have a special meaning
+fn escape_record(input: &str) -> String {
+    input
+        .chars()
+        .fold(String::with_capacity(input.len()), |mut out, char| {
+            if matches!(char, '\\' | '"' | '{' | '}' | '|' | '<' | '>' | ' ') {
+                out.push('\\');
+            }
+            out.push(char);
+            out
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        Graph, GraphOutInId, Subgraph,
+        numeric::{
+            ArithmeticOperation, Arithmetics, ArithmeticsInId, ArithmeticsOutId, NumericConstant,
+            NumericConstantOutId,
+        },
+    };
+
+    #[test]
+    fn nodes_ports_and_edges_are_exported() {
+        let mut graph = Graph::new();
+
+        let constant = graph.insert(Box::new(NumericConstant::new(2.0)));
+        let add = graph.insert(Box::new(Arithmetics::new(ArithmeticOperation::Addition)));
+
+        graph
+            .patch(
+                constant.node_out_id(&NumericConstantOutId::Out).unwrap(),
+                add.node_in_id(&ArithmeticsInId::Term2).unwrap(),
+            )
+            .unwrap();
+        graph
+            .patch(
+                add.node_out_id(&ArithmeticsOutId::Out).unwrap(),
+                graph.graph_out_in_id(&GraphOutInId::Numeric).unwrap(),
+            )
+            .unwrap();
+
+        let dot = graph.to_dot();
+        let crate::NodeId::Node(add_hash) = add.node_id() else {
+            unreachable!()
+        };
+
+        assert!(dot.starts_with("digraph quakk {"));
+        assert!(dot.contains(&format!("Arithmetics\\n{}", add_hash.display_short())));
+        assert!(dot.contains("{<i0> Term1|<i1> Term2}"));
+        assert!(dot.contains(&format!(
+            "\"n_{}\":o0 -> \"n_graph_out\":i0 [label=\"Out → Numeric\"];",
+            add_hash.display()
+        )));
+        assert!(dot.contains("[label=\"Out → Term2\"]"));
+
+        assert_eq!(dot, graph.to_dot(), "the export should be stable");
+    }
+
+    #[test]
+    fn subgraphs_are_exported_as_clusters() {
+        let mut graph = Graph::new();
+
+        let subgraph = Subgraph::new();
+        subgraph
+            .graph()
+            .lock()
+            .unwrap()
+            .insert(Box::new(NumericConstant::new(1.0)));
+        let subgraph = graph.insert(Box::new(subgraph));
+
+        let dot = graph.to_dot();
+        let crate::NodeId::Node(subgraph_hash) = subgraph.node_id() else {
+            unreachable!()
+        };
+
+        let cluster_id = format!("n_{}", subgraph_hash.display());
+        assert!(dot.contains(&format!("subgraph \"cluster_{cluster_id}\" {{")));
+        assert!(dot.contains(&format!("\"{cluster_id}_graph_in\"")));
+        assert!(dot.contains("Numeric\\ Constant"));
+    }
+}
@@ -51,7 +51,7 @@ pub struct HashId {
 impl HashId {
     /// Get a new random unique id
     /// ```
-    /// # use quakk::HashId;
+    /// # use quakk::id::HashId;
     /// assert_ne!(HashId::new(), HashId::new());
     /// ```
     pub fn new() -> Self {
@@ -62,7 +62,7 @@ impl HashId {
 
     /// Get a new unique id based on a string input
     /// ```
-    /// # use quakk::HashId;
+    /// # use quakk::id::HashId;
     /// assert_eq!(HashId::new_from("test"), HashId::new_from("test"));
     /// assert_ne!(HashId::new_from("test"), HashId::new_from("other"));
     /// ```
@@ -135,12 +135,22 @@ impl Debug for NodeId {
     }
 }
 
-pub trait InId: Any + Debug + DynClone + DynEq + DynHash {}
+pub trait InId: Any + Debug + DynClone + DynEq + DynHash {
+    /// A human readable name for this input, used when displaying the graph
+    fn name(&self) -> String {
+        format!("{self:?}")
+    }
+}
 dyn_clone::clone_trait_object!(InId);
 dyn_eq::eq_trait_object!(InId);
 dyn_hash::hash_trait_object!(InId);
 
-pub trait OutId: Any + Debug + DynClone + DynEq + DynHash {}
+pub trait OutId: Any + Debug + DynClone + DynEq + DynHash {
+    /// A human readable name for this output, used when displaying the graph
+    fn name(&self) -> String {
+        format!("{self:?}")
+    }
+}
 dyn_clone::clone_trait_object!(OutId);
 dyn_eq::eq_trait_object!(OutId);
 dyn_hash::hash_trait_object!(OutId);
@@ -199,6 +209,10 @@ impl NodeInId {
     pub fn in_id(self) -> Box<dyn InId> {
         self.in_id
     }
+
+    pub fn in_id_ref(&self) -> &dyn InId {
+        &*self.in_id
+    }
 }
 
 impl Debug for NodeInId {
@@ -228,6 +242,10 @@ impl NodeOutId {
     pub fn out_id(self) -> Box<dyn OutId> {
         self.out_id
     }
+
+    pub fn out_id_ref(&self) -> &dyn OutId {
+        &*self.out_id
+    }
 }
 
 impl Debug for NodeOutId {
@@ -1,12 +1,33 @@
-use std::sync::{Arc, Mutex};
+use std::{
+    collections::HashMap,
+    rc::Rc,
+    sync::{Arc, Mutex},
+};
 
-use anyhow::Context;
+use anyhow::{Context, anyhow};
 
 use crate::{
-    Data, Graph, Meta,
-    id::{InId, InoutId, NodeId},
+    Data, Graph, Meta, SubgraphInId,
+    id::{InId, InoutId, NodeId, OutId},
+    prepared::Plan,
 };
 
+/// Where the inputs of a [`Graph`], that is the outputs of its [`GraphIn`](crate::GraphIn)
+/// node, get their values from
+#[derive(Debug, Clone, Default)]
+pub enum GraphInputs {
+    /// No input is provided, e.g. for the root graph
+    #[default]
+    None,
+
+    /// The graph is wrapped in a node, like a [`Subgraph`](crate::Subgraph), each graph input
+    /// is the wrapping node's input of the same name
+    Parent(Rc<LasyFold>),
+
+    /// Each graph input is bound to a value, by name
+    Values(Rc<HashMap<String, Data>>),
+}
+
 /// `LasyFold` [folds] the [`Graph`] into a single value.
 ///
 /// It can be viewed a the "evaluator" or "executor" of Quakk, the term "fold" is used to tie back
@@ -34,19 +55,81 @@ use crate::{
 #[derive(Debug, Clone)]
 pub struct LasyFold {
     node_id: NodeId,
-    graph: Arc<Mutex<Graph>>,
+    source: Source,
+    graph_inputs: GraphInputs,
+}
+
+/// Where a [`LasyFold`] finds the inputs of its node
+#[derive(Debug, Clone)]
+enum Source {
+    /// The graph, locked on each read
+    Graph(Arc<Mutex<Graph>>),
+    /// A [`Prepared`](crate::Prepared) output, with the index of the node in its plan
+    Plan(Rc<Plan>, usize),
 }
 
 impl LasyFold {
     /// Create a new `LasyFold`
     pub fn new(node_id: NodeId, graph: Arc<Mutex<Graph>>) -> Self {
-        Self { node_id, graph }
+        Self {
+            node_id,
+            source: Source::Graph(graph),
+            graph_inputs: GraphInputs::None,
+        }
+    }
+
+    /// A `LasyFold` for a node of a prepared output, see [`Prepared`](crate::Prepared)
+    pub(crate) fn planned(plan: Rc<Plan>, index: usize) -> Self {
+        Self {
+            node_id: plan.node_id(index),
+            source: Source::Plan(plan, index),
+            graph_inputs: GraphInputs::None,
+        }
+    }
+
+    /// Set where the inputs of the graph get their values from, see [`GraphInputs`]
+    pub fn with_graph_inputs(mut self, graph_inputs: GraphInputs) -> Self {
+        self.graph_inputs = graph_inputs;
+        self
+    }
+
+    /// Does the node have an inbound edge for the given [`InId`]
+    ///
+    /// Useful for optional inputs, falling back to a default value when left unpatched
+    pub fn is_patched(&self, in_id: &dyn InId) -> bool {
+        let graph = match &self.source {
+            Source::Graph(graph) => graph,
+            Source::Plan(plan, index) => return plan.is_patched(*index, in_id),
+        };
+
+        graph
+            .lock()
+            .expect("the graph has been poisoned, who was it!?")
+            .vertex_for_id(self.node_id)
+            .is_some_and(|vertex| vertex.inbound_for(in_id).is_some())
+    }
+
+    /// Fold the input of the graph corresponding to an output of its `GraphIn` node
+    pub fn get_graph_in(&self, out_id: &dyn OutId, meta: Meta) -> anyhow::Result<Data> {
+        let name = out_id.name();
+
+        match &self.graph_inputs {
+            GraphInputs::None => Err(anyhow!("The graph input `{name}` is not provided")),
+            GraphInputs::Parent(parent) => parent.get_in(&SubgraphInId::new(&name), meta),
+            GraphInputs::Values(values) => values.get(&name).cloned().context(format!(
+                "The graph input `{name}` is not bound to any value"
+            )),
+        }
     }
 
     pub fn get_in(&self, in_id: &dyn InId, meta: Meta) -> anyhow::Result<Data> {
+        let graph = match &self.source {
+            Source::Graph(graph) => graph,
+            Source::Plan(plan, index) => return plan.get_in(*index, in_id, meta),
+        };
+
         let (in_node_handle, in_node_out_id) = {
-            let graph = self
-                .graph
+            let graph = graph
                 .lock()
                 .expect("the graph has been poisoned, who was it!?");
 
@@ -67,11 +150,10 @@ impl LasyFold {
             (in_node_handle, in_node_out_id)
         };
 
-        dbg!(in_node_handle.node().title());
-
         in_node_handle.node().fold(
             &*in_node_out_id.out_id(),
-            LasyFold::new(in_node_handle.node_id(), self.graph.clone()),
+            LasyFold::new(in_node_handle.node_id(), graph.clone())
+                .with_graph_inputs(self.graph_inputs.clone()),
             meta,
         )
     }
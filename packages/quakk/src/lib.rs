@@ -6,7 +6,15 @@ pub use lasy::*;
 
 mod node;
 pub use node::Node;
+pub use node::clock;
+pub use node::expression;
+pub use node::list;
+pub use node::logic;
 pub use node::numeric;
+pub use node::pattern;
+pub use node::random;
+pub use node::sequence;
+pub use node::template;
 pub use node::textual;
 
 mod meta;
@@ -14,8 +22,20 @@ pub use meta::*;
 
 pub mod id;
 
+mod parse;
+pub use parse::ParseError;
+
+mod prepared;
+pub use prepared::Prepared;
+
+mod state;
+pub use state::{NodeState, Progress};
+
 mod data;
-pub use data::Data;
+pub use data::{Data, DataList, DataMap, DataType};
+
+#[cfg(test)]
+mod testing;
 
 use anyhow::{Context, anyhow};
 use std::sync::{Arc, Mutex};
@@ -37,6 +57,9 @@ impl Default for Quakk {
             base_meta: Meta {
                 quality: Quality::Balanced,
                 tick: 0,
+                tick_rate: 60.0,
+                sample_rate: 48_000,
+                block_size: 800,
             },
 
             graph,
@@ -50,6 +73,16 @@ impl Quakk {
     }
 
     pub fn fold_for(&self, graph_out_out_id: GraphOutOutId) -> anyhow::Result<Data> {
+        self.fold_with(graph_out_out_id, self.base_meta)
+    }
+
+    /// Prepare an output of the graph for realtime evaluation, see [`Prepared`]
+    pub fn prepare(&self, graph_out_out_id: GraphOutOutId) -> anyhow::Result<Prepared> {
+        Prepared::new(self.graph.clone(), graph_out_out_id)
+    }
+
+    /// Same as [`Quakk::fold_for`], with a given [`Meta`] instead of [`Quakk::base_meta`]
+    pub fn fold_with(&self, graph_out_out_id: GraphOutOutId, meta: Meta) -> anyhow::Result<Data> {
         let graph_out_out_id: &dyn OutId = &graph_out_out_id;
 
         let graph_out_handle = {
@@ -64,7 +97,7 @@ impl Quakk {
             .fold(
                 graph_out_out_id,
                 LasyFold::new(NodeId::GraphOut, self.graph.clone()),
-                self.base_meta,
+                meta,
             )
             .context("Could not evaluate the graph")
     }
@@ -32,4 +32,37 @@ pub struct Meta {
 
     /// A quality norm used to find a tradeoff between quality and performance
     pub quality: Quality,
+
+    /// How many ticks happen each second
+    pub tick_rate: f64,
+
+    /// How many audio samples happen each second
+    pub sample_rate: u32,
+
+    /// How many audio samples a node computes in a single fold, audio nodes fold one block of
+    /// samples each tick
+    pub block_size: usize,
+}
+
+impl Meta {
+    /// The time elapsed since the first tick, in seconds
+    pub fn time(&self) -> f64 {
+        self.tick as f64 / self.tick_rate
+    }
+
+    /// Set the audio sample rate and block size, the tick rate then being the count of blocks
+    /// per second
+    pub fn with_audio(self, sample_rate: u32, block_size: usize) -> Self {
+        Self {
+            sample_rate,
+            block_size,
+            tick_rate: sample_rate as f64 / block_size as f64,
+            ..self
+        }
+    }
+
+    /// The index of the first sample of the current block, counted since the first tick
+    pub fn first_sample(&self) -> u64 {
+        self.tick * self.block_size as u64
+    }
 }
@@ -1,11 +1,22 @@
-use std::fmt::Debug;
+use std::{
+    fmt::Debug,
+    sync::{Arc, Mutex},
+};
 
 use crate::{
-    Data, LasyFold, Meta, NodeId, OutId,
+    Data, Graph, LasyFold, Meta, NodeId, OutId,
     id::{InId, InoutId, NodeInId, NodeInoutId, NodeOutId},
 };
 
+pub mod clock;
+pub mod expression;
+pub mod list;
+pub mod logic;
 pub mod numeric;
+pub mod pattern;
+pub mod random;
+pub mod sequence;
+pub mod template;
 pub mod textual;
 
 #[derive(Debug)]
@@ -26,6 +37,26 @@ pub trait Node: Debug {
     fn node_in_id(&self, in_id: &dyn InId, node_id: NodeId) -> Option<NodeInId>;
     fn node_out_id(&self, out_id: &dyn OutId, node_id: NodeId) -> Option<NodeOutId>;
 
+    /// Every input this node declares
+    fn in_ids(&self) -> Vec<Box<dyn InId>>;
+
+    /// Every output this node declares
+    fn out_ids(&self) -> Vec<Box<dyn OutId>>;
+
+    /// The [`Graph`] contained by this node, if it wraps one (e.g. a subgraph)
+    fn inner_graph(&self) -> Option<Arc<Mutex<Graph>>> {
+        None
+    }
+
+    /// Does this node only forward its [`inner_graph`](Node::inner_graph), each output folding
+    /// the graph output of the same name and each graph input being the input of the same name,
+    /// like a [`Subgraph`](crate::Subgraph)
+    ///
+    /// Such nodes are inlined in a [`Prepared`](crate::Prepared) graph
+    fn forwards_inner_graph(&self) -> bool {
+        false
+    }
+
     // fn node_inout_id_for(&self, inout_name: &str, node_id: NodeId) -> Option<NodeInoutId> {
     //     self.id_for(inout_name)
     //         .and_then(|inout_id| Some(NodeInoutId::new(node_id, inout_id)))
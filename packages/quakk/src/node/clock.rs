@@ -0,0 +1,480 @@
+//! Tempo, counting beats and bars so that other nodes can follow a tempo rather than ticks
+//!
+//! A beat is a quarter note, a [`Clock`] counts them at a tempo and a [`BeatDivider`] turns a
+//! count of beats into triggers at a musical [`Division`].
+
+use std::str::FromStr;
+
+use anyhow::{Context, anyhow};
+
+use crate::{
+    Data, LasyFold, Meta, Node, NodeState, ParseError, Progress,
+    id::{InId, NodeId, NodeInId, NodeOutId, OutId},
+    random::get_f64_or,
+};
+
+/// A length of note, like `1/4` for a beat or `1/8T` for an eighth of a triplet
+/// ```
+/// # use quakk::clock::Division;
+/// let division: Division = "1/8.".parse().unwrap();
+/// assert_eq!(division.beats(), 0.75);
+///
+/// let error = "1/8x".parse::<Division>().unwrap_err();
+/// assert_eq!(error.to_string(), "expected `T` or `.` at position 3");
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Division {
+    beats: f64,
+}
+
+impl Division {
+    /// `numerator / denominator` of a whole note, which lasts 4 beats
+    pub fn new(numerator: u32, denominator: u32) -> Self {
+        Self {
+            beats: 4.0 * numerator as f64 / denominator as f64,
+        }
+    }
+
+    /// Three divisions in the time of two
+    pub fn triplet(self) -> Self {
+        Self {
+            beats: self.beats * 2.0 / 3.0,
+        }
+    }
+
+    /// The division lengthened by half
+    pub fn dotted(self) -> Self {
+        Self {
+            beats: self.beats * 1.5,
+        }
+    }
+
+    /// The length of the division, in beats
+    pub fn beats(&self) -> f64 {
+        self.beats
+    }
+}
+
+impl Default for Division {
+    fn default() -> Self {
+        Self::new(1, 4)
+    }
+}
+
+impl FromStr for Division {
+    type Err = ParseError;
+
+    /// Parse `n/d`, or `n` for `n/1`, optionally followed by `T` for a triplet or `.` for a
+    /// dotted division
+    fn from_str(source: &str) -> Result<Self, ParseError> {
+        let chars: Vec<char> = source.chars().collect();
+        let mut at = 0;
+
+        let mut integer = |at: &mut usize| -> Result<u32, ParseError> {
+            let start = *at;
+            while chars.get(*at).is_some_and(char::is_ascii_digit) {
+                *at += 1;
+            }
+
+            let digits: String = chars[start..*at].iter().collect();
+            match digits.parse::<u32>() {
+                Ok(0) => Err(ParseError::new(start, "a division cannot be 0")),
+                Ok(integer) => Ok(integer),
+                Err(_) => Err(ParseError::new(start, "expected a number")),
+            }
+        };
+
+        let numerator = integer(&mut at)?;
+        let denominator = if chars.get(at) == Some(&'/') {
+            at += 1;
+            integer(&mut at)?
+        } else {
+            1
+        };
+
+        let division = Self::new(numerator, denominator);
+        let division = match chars.get(at) {
+            None => return Ok(division),
+            Some('T' | 't') => division.triplet(),
+            Some('.') => division.dotted(),
+            Some(_) => return Err(ParseError::new(at, "expected `T` or `.`")),
+        };
+
+        match chars.get(at + 1) {
+            None => Ok(division),
+            Some(char) => Err(ParseError::new(at + 1, format!("unexpected `{char}`"))),
+        }
+    }
+}
+
+/// Whether a count of divisions changed since the last tick, giving a trigger on each new one
+#[derive(Debug, Default)]
+struct Triggers {
+    /// The count of divisions at the last tick
+    last: NodeState<Option<i64>>,
+}
+
+impl Triggers {
+    fn step(&self, meta: Meta, count: i64) -> anyhow::Result<Data> {
+        self.last.step(meta, |last| {
+            let trigger = *last != Some(count);
+            *last = Some(count);
+
+            Ok(Data::new(if trigger { 1.0f32 } else { 0.0 }))
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ClockInId {
+    /// The tempo in beats per minute, `120` when not patched
+    Bpm,
+    /// How many beats a bar lasts, `4` when not patched
+    BeatsPerBar,
+}
+
+impl InId for ClockInId {}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ClockOutId {
+    /// The count of beats since the first tick, with the part of the current beat
+    Beat,
+    /// How far the current beat is, from `0` to `1`
+    BeatPhase,
+    /// The index of the current bar, from `0`
+    Bar,
+    /// How far the current bar is, from `0` to `1`
+    BarPhase,
+    /// `1` on the first tick of each division, `0` otherwise
+    Trigger,
+}
+
+impl OutId for ClockOutId {}
+
+/// Count beats at a tempo, triggering at a [`Division`]
+///
+/// The count is a [`Progress`], moving forward by the ticks elapsed at the current tempo. Patching
+/// `Beat` to the `Beat` input of other nodes, like a [`Pattern`](crate::pattern::Pattern) or a
+/// [`BeatDivider`], keeps them all in time.
+#[derive(Debug, Default)]
+pub struct Clock {
+    division: Division,
+    /// The count of beats since the first tick
+    beat: NodeState<Progress>,
+    triggers: Triggers,
+}
+
+impl Clock {
+    pub fn new(division: Division) -> Self {
+        Self {
+            division,
+            ..Self::default()
+        }
+    }
+
+    pub fn division(&self) -> Division {
+        self.division
+    }
+
+    /// The count of beats at this tick
+    fn beat(&self, lasy_fold: &LasyFold, meta: Meta) -> anyhow::Result<f64> {
+        let beat = self.beat.step(meta, |progress| {
+            let bpm = get_f64_or(lasy_fold, &ClockInId::Bpm, meta, 120.0)?;
+
+            let beats_per_tick = bpm / 60.0 / meta.tick_rate;
+            if !(beats_per_tick.is_finite() && beats_per_tick >= 0.0) {
+                return Err(anyhow!("the tempo should be positive, found {bpm} BPM"));
+            }
+
+            Ok(Data::new(progress.advance(meta.tick, beats_per_tick)))
+        })?;
+
+        beat.as_f64()
+    }
+}
+
+impl Node for Clock {
+    fn initialize() -> Self {
+        Self::default()
+    }
+
+    fn title(&self) -> &str {
+        "Clock"
+    }
+
+    fn fold(&self, out_id: &dyn OutId, lasy_fold: LasyFold, meta: Meta) -> anyhow::Result<Data> {
+        let Some(out_id) = out_id.as_any().downcast_ref::<ClockOutId>() else {
+            return Err(anyhow!("not a valid out_id"));
+        };
+
+        let beat = self.beat(&lasy_fold, meta)?;
+        let bar = || -> anyhow::Result<f64> {
+            let beats_per_bar = get_f64_or(&lasy_fold, &ClockInId::BeatsPerBar, meta, 4.0)?;
+            if beats_per_bar > 0.0 && beats_per_bar.is_finite() {
+                Ok(beat / beats_per_bar)
+            } else {
+                Err(anyhow!(
+                    "a bar should last more than 0 beats, found {beats_per_bar}"
+                ))
+            }
+        };
+
+        let data = match out_id {
+            ClockOutId::Beat => Data::new(beat),
+            ClockOutId::BeatPhase => Data::new(beat.fract() as f32),
+            ClockOutId::Bar => Data::new(bar()?.floor() as i64),
+            ClockOutId::BarPhase => Data::new(bar()?.fract() as f32),
+            ClockOutId::Trigger => self
+                .triggers
+                .step(meta, (beat / self.division.beats()).floor() as i64)?,
+        };
+
+        Ok(data)
+    }
+
+    fn node_in_id(&self, in_id: &dyn InId, node_id: NodeId) -> Option<NodeInId> {
+        in_id
+            .as_any()
+            .downcast_ref::<ClockInId>()
+            .map(|in_id| NodeInId::new(node_id, in_id))
+    }
+
+    fn node_out_id(&self, out_id: &dyn OutId, node_id: NodeId) -> Option<NodeOutId> {
+        out_id
+            .as_any()
+            .downcast_ref::<ClockOutId>()
+            .map(|out_id| NodeOutId::new(node_id, out_id))
+    }
+
+    fn in_ids(&self) -> Vec<Box<dyn InId>> {
+        vec![Box::new(ClockInId::Bpm), Box::new(ClockInId::BeatsPerBar)]
+    }
+
+    fn out_ids(&self) -> Vec<Box<dyn OutId>> {
+        vec![
+            Box::new(ClockOutId::Beat),
+            Box::new(ClockOutId::BeatPhase),
+            Box::new(ClockOutId::Bar),
+            Box::new(ClockOutId::BarPhase),
+            Box::new(ClockOutId::Trigger),
+        ]
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum BeatDividerInId {
+    /// The count of beats, like the `Beat` output of a [`Clock`], following the time at
+    /// 120 BPM when not patched
+    Beat,
+}
+
+impl InId for BeatDividerInId {}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum BeatDividerOutId {
+    /// `1` on the first tick of each division, `0` otherwise
+    Trigger,
+    /// `1` during the first half of each division, `0` otherwise
+    Gate,
+    /// How far the current division is, from `0` to `1`
+    Phase,
+    /// The count of divisions since the beat `0`
+    Count,
+}
+
+impl OutId for BeatDividerOutId {}
+
+/// Divide a count of beats at a [`Division`], like `1/8T` from the beats of a [`Clock`]
+#[derive(Debug, Default)]
+pub struct BeatDivider {
+    division: Division,
+    triggers: Triggers,
+}
+
+impl BeatDivider {
+    pub fn new(division: Division) -> Self {
+        Self {
+            division,
+            triggers: Triggers::default(),
+        }
+    }
+
+    pub fn division(&self) -> Division {
+        self.division
+    }
+}
+
+impl Node for BeatDivider {
+    fn initialize() -> Self {
+        Self::default()
+    }
+
+    fn title(&self) -> &str {
+        "Beat divider"
+    }
+
+    fn fold(&self, out_id: &dyn OutId, lasy_fold: LasyFold, meta: Meta) -> anyhow::Result<Data> {
+        let Some(out_id) = out_id.as_any().downcast_ref::<BeatDividerOutId>() else {
+            return Err(anyhow!("not a valid out_id"));
+        };
+
+        let beat = if lasy_fold.is_patched(&BeatDividerInId::Beat) {
+            lasy_fold
+                .get_in(&BeatDividerInId::Beat, meta)?
+                .to_f64()
+                .context("invalid Beat")?
+        } else {
+            meta.time() * 2.0
+        };
+        let position = beat / self.division.beats();
+        let phase = position - position.floor();
+
+        let data = match out_id {
+            BeatDividerOutId::Trigger => self.triggers.step(meta, position.floor() as i64)?,
+            BeatDividerOutId::Gate => Data::new(if phase < 0.5 { 1.0f32 } else { 0.0 }),
+            BeatDividerOutId::Phase => Data::new(phase as f32),
+            BeatDividerOutId::Count => Data::new(position.floor() as i64),
+        };
+
+        Ok(data)
+    }
+
+    fn node_in_id(&self, in_id: &dyn InId, node_id: NodeId) -> Option<NodeInId> {
+        in_id
+            .as_any()
+            .downcast_ref::<BeatDividerInId>()
+            .map(|in_id| NodeInId::new(node_id, in_id))
+    }
+
+    fn node_out_id(&self, out_id: &dyn OutId, node_id: NodeId) -> Option<NodeOutId> {
+        out_id
+            .as_any()
+            .downcast_ref::<BeatDividerOutId>()
+            .map(|out_id| NodeOutId::new(node_id, out_id))
+    }
+
+    fn in_ids(&self) -> Vec<Box<dyn InId>> {
+        vec![Box::new(BeatDividerInId::Beat)]
+    }
+
+    fn out_ids(&self) -> Vec<Box<dyn OutId>> {
+        vec![
+            Box::new(BeatDividerOutId::Trigger),
+            Box::new(BeatDividerOutId::Gate),
+            Box::new(BeatDividerOutId::Phase),
+            Box::new(BeatDividerOutId::Count),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::fold_node_over;
+
+    /// Fold an output of a clock over the first `ticks` ticks, at 225 BPM, a beat lasting 16
+    /// ticks
+    fn fold(clock: Clock, out_id: ClockOutId, ticks: u64) -> Vec<f64> {
+        fold_node_over(
+            clock,
+            vec![
+                (&ClockInId::Bpm, Data::new(225.0f32)),
+                (&ClockInId::BeatsPerBar, Data::new(3i64)),
+            ],
+            &out_id,
+            0..ticks,
+        )
+        .unwrap()
+        .iter()
+        .map(|data| data.to_f64().unwrap())
+        .collect()
+    }
+
+    #[test]
+    fn divisions_parse() {
+        let beats = |source: &str| source.parse::<Division>().unwrap().beats();
+
+        assert_eq!(beats("1/4"), 1.0);
+        assert_eq!(beats("1"), 4.0);
+        assert_eq!(beats("3/8"), 1.5);
+        assert_eq!(beats("1/8."), 0.75);
+        assert!((beats("1/8T") - 1.0 / 3.0).abs() < 1e-12);
+
+        let error = |source: &str| source.parse::<Division>().unwrap_err().to_string();
+        assert_eq!(error(""), "expected a number at position 0");
+        assert_eq!(error("1/0"), "a division cannot be 0 at position 2");
+        assert_eq!(error("1/4T."), "unexpected `.` at position 4");
+    }
+
+    #[test]
+    fn clock_counts_beats_and_bars() {
+        let beats = fold(Clock::default(), ClockOutId::Beat, 33);
+        assert_eq!(beats[16], 1.0);
+        assert_eq!(beats[32], 2.0);
+
+        let phases = fold(Clock::default(), ClockOutId::BeatPhase, 20);
+        assert_eq!(phases[4], 0.25);
+        assert_eq!(phases[18], 0.125);
+
+        let bars = fold(Clock::default(), ClockOutId::Bar, 49);
+        assert_eq!((bars[47], bars[48]), (0.0, 1.0));
+        let bar_phases = fold(Clock::default(), ClockOutId::BarPhase, 25);
+        assert_eq!(bar_phases[24], 0.5);
+
+        // Ticks left unfolded still count
+        let beats = fold_node_over(
+            Clock::default(),
+            vec![(&ClockInId::Bpm, Data::new(225.0f32))],
+            &ClockOutId::Beat,
+            [0, 8, 40],
+        )
+        .unwrap();
+        let beats: Vec<f64> = beats.iter().map(|data| data.to_f64().unwrap()).collect();
+        assert_eq!(beats, [0.0, 0.5, 2.5]);
+    }
+
+    #[test]
+    fn clock_triggers_at_its_division() {
+        let triggers = fold(Clock::new("1/8".parse().unwrap()), ClockOutId::Trigger, 32);
+        let onsets: Vec<usize> = (0..32).filter(|tick| triggers[*tick] == 1.0).collect();
+        assert_eq!(onsets, [0, 8, 16, 24]);
+
+        let triggers = fold(Clock::new("1/4.".parse().unwrap()), ClockOutId::Trigger, 64);
+        let onsets: Vec<usize> = (0..64).filter(|tick| triggers[*tick] == 1.0).collect();
+        assert_eq!(onsets, [0, 24, 48]);
+    }
+
+    #[test]
+    fn divider_follows_its_beat() {
+        let fold = |out_id: BeatDividerOutId, beat: f64| {
+            fold_node_over(
+                BeatDivider::new("1/16".parse().unwrap()),
+                vec![(&BeatDividerInId::Beat, Data::new(beat))],
+                &out_id,
+                0..1,
+            )
+            .unwrap()[0]
+                .to_f64()
+                .unwrap()
+        };
+
+        assert_eq!(fold(BeatDividerOutId::Count, 2.5), 10.0);
+        assert_eq!(fold(BeatDividerOutId::Phase, 2.3125), 0.25);
+        assert_eq!(fold(BeatDividerOutId::Gate, 2.3125), 1.0);
+        assert_eq!(fold(BeatDividerOutId::Gate, 2.4375), 0.0);
+
+        // Without a beat, the divider follows the time at 120 BPM, a sixteenth lasting 7.5 ticks
+        let triggers: Vec<f64> = fold_node_over(
+            BeatDivider::new("1/16".parse().unwrap()),
+            vec![],
+            &BeatDividerOutId::Trigger,
+            0..16,
+        )
+        .unwrap()
+        .iter()
+        .map(|data| data.to_f64().unwrap())
+        .collect();
+        let onsets: Vec<usize> = (0..16).filter(|tick| triggers[*tick] == 1.0).collect();
+        assert_eq!(onsets, [0, 8, 15]);
+    }
+}
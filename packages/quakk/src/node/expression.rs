@@ -0,0 +1,703 @@
+//! A node evaluating a math formula, like `a * sin(b * t) + c`
+//!
+//! Formulas support :
+//! - numbers (`2`, `0.5`, `1e-3`) and the constants `pi`, `tau` and `e`
+//! - the operators `+`, `-`, `*`, `/`, `%` (euclidean modulo) and `^` (power), with the usual
+//!   precedence, and parentheses
+//! - the functions `sin`, `cos`, `tan`, `asin`, `acos`, `atan`, `sqrt`, `abs`, `sign`, `floor`,
+//!   `ceil`, `round`, `fract`, `exp`, `ln`, `log2`, `log10` of one argument, `atan2`, `pow`,
+//!   `min`, `max` of two, and `clamp`, `lerp` of three
+//! - `t`, the time in seconds, and `tick`, both given by [`Meta`]
+//!
+//! Any other name is a free variable, and becomes an input of the node named after it
+
+use anyhow::{Context, anyhow};
+
+use crate::{
+    Data, LasyFold, Meta, Node, ParseError,
+    id::{InId, NodeId, NodeInId, NodeOutId, OutId},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum BinaryOperator {
+    Add,
+    Substract,
+    Multiply,
+    Divide,
+    Modulo,
+    Power,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Function {
+    Sin,
+    Cos,
+    Tan,
+    Asin,
+    Acos,
+    Atan,
+    Atan2,
+    Sqrt,
+    Abs,
+    Sign,
+    Floor,
+    Ceil,
+    Round,
+    Fract,
+    Exp,
+    Ln,
+    Log2,
+    Log10,
+    Pow,
+    Min,
+    Max,
+    Clamp,
+    Lerp,
+}
+
+impl Function {
+    /// The function with the given name, along with its number of arguments
+    fn from_name(name: &str) -> Option<(Self, usize)> {
+        use Function::*;
+        let function = match name {
+            "sin" => (Sin, 1),
+            "cos" => (Cos, 1),
+            "tan" => (Tan, 1),
+            "asin" => (Asin, 1),
+            "acos" => (Acos, 1),
+            "atan" => (Atan, 1),
+            "atan2" => (Atan2, 2),
+            "sqrt" => (Sqrt, 1),
+            "abs" => (Abs, 1),
+            "sign" => (Sign, 1),
+            "floor" => (Floor, 1),
+            "ceil" => (Ceil, 1),
+            "round" => (Round, 1),
+            "fract" => (Fract, 1),
+            "exp" => (Exp, 1),
+            "ln" => (Ln, 1),
+            "log2" => (Log2, 1),
+            "log10" => (Log10, 1),
+            "pow" => (Pow, 2),
+            "min" => (Min, 2),
+            "max" => (Max, 2),
+            "clamp" => (Clamp, 3),
+            "lerp" => (Lerp, 3),
+            _ => return None,
+        };
+
+        Some(function)
+    }
+
+    fn apply(&self, args: &[f64]) -> f64 {
+        use Function::*;
+        match self {
+            Sin => args[0].sin(),
+            Cos => args[0].cos(),
+            Tan => args[0].tan(),
+            Asin => args[0].asin(),
+            Acos => args[0].acos(),
+            Atan => args[0].atan(),
+            Atan2 => args[0].atan2(args[1]),
+            Sqrt => args[0].sqrt(),
+            Abs => args[0].abs(),
+            Sign if args[0] == 0.0 => 0.0,
+            Sign => args[0].signum(),
+            Floor => args[0].floor(),
+            Ceil => args[0].ceil(),
+            Round => args[0].round(),
+            Fract => args[0] - args[0].floor(),
+            Exp => args[0].exp(),
+            Ln => args[0].ln(),
+            Log2 => args[0].log2(),
+            Log10 => args[0].log10(),
+            Pow => args[0].powf(args[1]),
+            Min => args[0].min(args[1]),
+            Max => args[0].max(args[1]),
+            // Written as `max` then `min` so swapped bounds never panic
+            Clamp => args[0].max(args[1]).min(args[2]),
+            Lerp => args[0] + (args[1] - args[0]) * args[2],
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Expr {
+    Number(f64),
+    /// A free variable, by index in [`Expression::variables`]
+    Variable(usize),
+    Time,
+    Tick,
+    Negate(Box<Expr>),
+    Binary(BinaryOperator, Box<Expr>, Box<Expr>),
+    Call(Function, Vec<Expr>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(f64),
+    Identifier(String),
+    Symbol(char),
+}
+
+/// Split a formula into tokens, along with their position
+fn tokenize(formula: &str) -> Result<Vec<(usize, Token)>, ParseError> {
+    let chars: Vec<char> = formula.chars().collect();
+    let mut tokens = Vec::new();
+    let mut position = 0;
+
+    while let Some(&char) = chars.get(position) {
+        let start = position;
+
+        if char.is_whitespace() {
+            position += 1;
+        } else if char.is_ascii_digit() || char == '.' {
+            while chars
+                .get(position)
+                .is_some_and(|char| char.is_ascii_digit() || *char == '.')
+            {
+                position += 1;
+            }
+
+            // An exponent, only when followed by digits, so `2e` is not mistaken for one
+            if chars
+                .get(position)
+                .is_some_and(|char| *char == 'e' || *char == 'E')
+            {
+                let digits_at = match chars.get(position + 1) {
+                    Some('+' | '-') => position + 2,
+                    _ => position + 1,
+                };
+
+                if chars.get(digits_at).is_some_and(char::is_ascii_digit) {
+                    position = digits_at;
+                    while chars.get(position).is_some_and(char::is_ascii_digit) {
+                        position += 1;
+                    }
+                }
+            }
+
+            let number: String = chars[start..position].iter().collect();
+            let number = number
+                .parse()
+                .map_err(|_| ParseError::new(start, format!("invalid number `{number}`")))?;
+            tokens.push((start, Token::Number(number)));
+        } else if char.is_alphabetic() || char == '_' {
+            while chars
+                .get(position)
+                .is_some_and(|char| char.is_alphanumeric() || *char == '_')
+            {
+                position += 1;
+            }
+
+            let identifier = chars[start..position].iter().collect();
+            tokens.push((start, Token::Identifier(identifier)));
+        } else if "+-*/%^(),".contains(char) {
+            position += 1;
+            tokens.push((start, Token::Symbol(char)));
+        } else {
+            return Err(ParseError::new(
+                start,
+                format!("unexpected character `{char}`"),
+            ));
+        }
+    }
+
+    Ok(tokens)
+}
+
+/// A recursive descent parser, from the lowest to the highest precedence :
+/// ```text
+/// sum     := product (("+" | "-") product)*
+/// product := unary (("*" | "/" | "%") unary)*
+/// unary   := "-" unary | power
+/// power   := atom ("^" unary)?
+/// atom    := number | name | name "(" sum ("," sum)* ")" | "(" sum ")"
+/// ```
+///
+/// Each level of nesting and each operator chained in `sum` or `product` makes the tree one level
+/// deeper, which errors past [`MAX_DEPTH`] levels rather than overflowing the stack when the tree
+/// is evaluated or dropped.
+struct Parser {
+    tokens: Vec<(usize, Token)>,
+    cursor: usize,
+    end: usize,
+    variables: Vec<String>,
+    depth: usize,
+}
+
+/// How deep parentheses, calls, negations, powers and chains of operators can go in a formula
+const MAX_DEPTH: usize = 256;
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.cursor).map(|(_, token)| token)
+    }
+
+    fn position(&self) -> usize {
+        self.tokens
+            .get(self.cursor)
+            .map(|(position, _)| *position)
+            .unwrap_or(self.end)
+    }
+
+    fn eat(&mut self, symbol: char) -> bool {
+        if self.peek() == Some(&Token::Symbol(symbol)) {
+            self.cursor += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn unexpected(&self, expected: &str) -> ParseError {
+        let found = match self.peek() {
+            Some(Token::Number(number)) => format!("`{number}`"),
+            Some(Token::Identifier(identifier)) => format!("`{identifier}`"),
+            Some(Token::Symbol(symbol)) => format!("`{symbol}`"),
+            None => "the end of the formula".to_string(),
+        };
+
+        ParseError::new(
+            self.position(),
+            format!("expected {expected}, found {found}"),
+        )
+    }
+
+    /// Go one level deeper in the tree, erroring past [`MAX_DEPTH`] levels
+    fn deepen(&mut self) -> Result<(), ParseError> {
+        if self.depth == MAX_DEPTH {
+            return Err(ParseError::new(
+                self.position(),
+                format!("the formula nests or chains more than {MAX_DEPTH} operations"),
+            ));
+        }
+
+        self.depth += 1;
+        Ok(())
+    }
+
+    fn sum(&mut self) -> Result<Expr, ParseError> {
+        let depth = self.depth;
+        let mut expr = self.product()?;
+
+        loop {
+            let operator = if self.eat('+') {
+                BinaryOperator::Add
+            } else if self.eat('-') {
+                BinaryOperator::Substract
+            } else {
+                self.depth = depth;
+                return Ok(expr);
+            };
+
+            self.deepen()?;
+            expr = Expr::Binary(operator, Box::new(expr), Box::new(self.product()?));
+        }
+    }
+
+    fn product(&mut self) -> Result<Expr, ParseError> {
+        let depth = self.depth;
+        let mut expr = self.unary()?;
+
+        loop {
+            let operator = if self.eat('*') {
+                BinaryOperator::Multiply
+            } else if self.eat('/') {
+                BinaryOperator::Divide
+            } else if self.eat('%') {
+                BinaryOperator::Modulo
+            } else {
+                self.depth = depth;
+                return Ok(expr);
+            };
+
+            self.deepen()?;
+            expr = Expr::Binary(operator, Box::new(expr), Box::new(self.unary()?));
+        }
+    }
+
+    fn unary(&mut self) -> Result<Expr, ParseError> {
+        self.deepen()?;
+        let expr = if self.eat('-') {
+            self.unary().map(|expr| Expr::Negate(Box::new(expr)))
+        } else {
+            self.power()
+        };
+        self.depth -= 1;
+
+        expr
+    }
+
+    fn power(&mut self) -> Result<Expr, ParseError> {
+        let base = self.atom()?;
+
+        if self.eat('^') {
+            let exponent = self.unary()?;
+            Ok(Expr::Binary(
+                BinaryOperator::Power,
+                Box::new(base),
+                Box::new(exponent),
+            ))
+        } else {
+            Ok(base)
+        }
+    }
+
+    fn atom(&mut self) -> Result<Expr, ParseError> {
+        let position = self.position();
+
+        match self.peek().cloned() {
+            Some(Token::Number(number)) => {
+                self.cursor += 1;
+                Ok(Expr::Number(number))
+            }
+            Some(Token::Symbol('(')) => {
+                self.cursor += 1;
+                let expr = self.sum()?;
+
+                if self.eat(')') {
+                    Ok(expr)
+                } else {
+                    Err(self.unexpected("`)`"))
+                }
+            }
+            Some(Token::Identifier(name)) => {
+                self.cursor += 1;
+
+                if self.eat('(') {
+                    self.call(&name, position)
+                } else {
+                    Ok(self.name(name))
+                }
+            }
+            _ => Err(self.unexpected("a number, a name or `(`")),
+        }
+    }
+
+    fn call(&mut self, name: &str, position: usize) -> Result<Expr, ParseError> {
+        let (function, arity) = Function::from_name(name).ok_or(ParseError::new(
+            position,
+            format!("unknown function `{name}`"),
+        ))?;
+
+        let mut args = vec![self.sum()?];
+        while self.eat(',') {
+            args.push(self.sum()?);
+        }
+
+        if !self.eat(')') {
+            return Err(self.unexpected("`,` or `)`"));
+        }
+
+        if args.len() != arity {
+            return Err(ParseError::new(
+                position,
+                format!(
+                    "the function `{name}` takes {arity} argument(s), but {} were given",
+                    args.len()
+                ),
+            ));
+        }
+
+        Ok(Expr::Call(function, args))
+    }
+
+    fn name(&mut self, name: String) -> Expr {
+        match name.as_str() {
+            "pi" => Expr::Number(std::f64::consts::PI),
+            "tau" => Expr::Number(std::f64::consts::TAU),
+            "e" => Expr::Number(std::f64::consts::E),
+            "t" => Expr::Time,
+            "tick" => Expr::Tick,
+            _ => match self.variables.iter().position(|variable| *variable == name) {
+                Some(index) => Expr::Variable(index),
+                None => {
+                    self.variables.push(name);
+                    Expr::Variable(self.variables.len() - 1)
+                }
+            },
+        }
+    }
+}
+
+/// An input of an [`Expression`], named after a free variable of the formula
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ExpressionInId {
+    name: String,
+}
+
+impl ExpressionInId {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+        }
+    }
+}
+
+impl InId for ExpressionInId {
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ExpressionOutId {
+    Out,
+}
+
+impl OutId for ExpressionOutId {}
+
+/// Evaluate a formula, each of its free variables being an input of the node
+/// ```
+/// # use quakk::expression::Expression;
+/// let expression = Expression::new("a * sin(b * t) + c").unwrap();
+/// assert_eq!(expression.variables(), ["a", "b", "c"]);
+///
+/// let error = Expression::new("a * (b + ").unwrap_err();
+/// assert_eq!(error.position(), 9);
+/// ```
+#[derive(Debug)]
+pub struct Expression {
+    formula: String,
+    expr: Expr,
+    variables: Vec<String>,
+}
+
+impl Expression {
+    /// Parse a formula, returning an error pointing to the position of the first issue found
+    pub fn new(formula: &str) -> Result<Self, ParseError> {
+        let mut parser = Parser {
+            tokens: tokenize(formula)?,
+            cursor: 0,
+            end: formula.chars().count(),
+            variables: Vec::new(),
+            depth: 0,
+        };
+
+        let expr = parser.sum()?;
+        if parser.peek().is_some() {
+            return Err(parser.unexpected("an operator"));
+        }
+
+        Ok(Self {
+            formula: formula.to_string(),
+            expr,
+            variables: parser.variables,
+        })
+    }
+
+    pub fn formula(&self) -> &str {
+        &self.formula
+    }
+
+    /// The free variables of the formula, in order of appearance
+    pub fn variables(&self) -> &[String] {
+        &self.variables
+    }
+
+    /// Evaluate the formula, `variable` is called at most once for each free variable used
+    pub fn evaluate(
+        &self,
+        meta: Meta,
+        mut variable: impl FnMut(&str) -> anyhow::Result<f64>,
+    ) -> anyhow::Result<f64> {
+        let mut values = vec![None; self.variables.len()];
+        self.evaluate_expr(&self.expr, meta, &mut |index| match values[index] {
+            Some(value) => Ok(value),
+            None => {
+                let value = variable(&self.variables[index])?;
+                values[index] = Some(value);
+                Ok(value)
+            }
+        })
+    }
+
+    fn evaluate_expr(
+        &self,
+        expr: &Expr,
+        meta: Meta,
+        variable: &mut impl FnMut(usize) -> anyhow::Result<f64>,
+    ) -> anyhow::Result<f64> {
+        let value = match expr {
+            Expr::Number(number) => *number,
+            Expr::Variable(index) => variable(*index)?,
+            Expr::Time => meta.time(),
+            Expr::Tick => meta.tick as f64,
+            Expr::Negate(expr) => -self.evaluate_expr(expr, meta, variable)?,
+            Expr::Binary(operator, left, right) => {
+                let left = self.evaluate_expr(left, meta, variable)?;
+                let right = self.evaluate_expr(right, meta, variable)?;
+
+                use BinaryOperator::*;
+                match operator {
+                    Add => left + right,
+                    Substract => left - right,
+                    Multiply => left * right,
+                    Divide => left / right,
+                    Modulo => left.rem_euclid(right),
+                    Power => left.powf(right),
+                }
+            }
+            Expr::Call(function, args) => {
+                let mut values = [0.0; 3];
+                for (value, arg) in values.iter_mut().zip(args) {
+                    *value = self.evaluate_expr(arg, meta, variable)?;
+                }
+
+                function.apply(&values[..args.len()])
+            }
+        };
+
+        Ok(value)
+    }
+}
+
+impl Default for Expression {
+    fn default() -> Self {
+        Self::new("0").expect("the default formula should be valid")
+    }
+}
+
+impl Node for Expression {
+    fn initialize() -> Self {
+        Self::default()
+    }
+
+    fn title(&self) -> &str {
+        "Expression"
+    }
+
+    fn fold(&self, _out_id: &dyn OutId, lasy_fold: LasyFold, meta: Meta) -> anyhow::Result<Data> {
+        let value = self.evaluate(meta, |name| {
+            lasy_fold
+                .get_in(&ExpressionInId::new(name), meta)?
+                .to_f64()
+                .with_context(|| format!("invalid {name}"))
+        })?;
+
+        Ok(Data::new(value as f32))
+    }
+
+    fn node_in_id(&self, in_id: &dyn InId, node_id: NodeId) -> Option<NodeInId> {
+        in_id
+            .as_any()
+            .downcast_ref::<ExpressionInId>()
+            .filter(|in_id| self.variables.contains(&in_id.name))
+            .map(|in_id| NodeInId::new(node_id, in_id))
+    }
+
+    fn node_out_id(&self, out_id: &dyn OutId, node_id: NodeId) -> Option<NodeOutId> {
+        out_id
+            .as_any()
+            .downcast_ref::<ExpressionOutId>()
+            .map(|out_id| NodeOutId::new(node_id, out_id))
+    }
+
+    fn in_ids(&self) -> Vec<Box<dyn InId>> {
+        self.variables
+            .iter()
+            .map(|name| Box::new(ExpressionInId::new(name)) as Box<dyn InId>)
+            .collect()
+    }
+
+    fn out_ids(&self) -> Vec<Box<dyn OutId>> {
+        vec![Box::new(ExpressionOutId::Out)]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{fold_node_at, meta_at};
+
+    fn evaluate(formula: &str) -> f64 {
+        Expression::new(formula)
+            .unwrap()
+            .evaluate(meta_at(120), |name| {
+                Err(anyhow!("unexpected variable {name}"))
+            })
+            .unwrap()
+    }
+
+    #[test]
+    fn precedence_and_associativity() {
+        assert_eq!(evaluate("1 + 2 * 3"), 7.0);
+        assert_eq!(evaluate("(1 + 2) * 3"), 9.0);
+        assert_eq!(evaluate("10 - 4 - 3"), 3.0);
+        assert_eq!(evaluate("2 ^ 3 ^ 2"), 512.0);
+        assert_eq!(evaluate("-2 ^ 2"), -4.0);
+        assert_eq!(evaluate("2 * -3"), -6.0);
+        assert_eq!(evaluate("-1 % 4"), 3.0);
+        assert_eq!(evaluate("1.5e1 + .5"), 15.5);
+    }
+
+    #[test]
+    fn functions_constants_and_meta() {
+        assert!((evaluate("sin(pi / 2)") - 1.0).abs() < 1e-12);
+        assert_eq!(evaluate("clamp(5, 0, 1)"), 1.0);
+        assert_eq!(evaluate("lerp(10, 20, 0.5)"), 15.0);
+        assert_eq!(evaluate("max(min(3, 4), 2)"), 3.0);
+        assert_eq!(evaluate("tick"), 120.0);
+        assert_eq!(evaluate("t"), 2.0);
+        assert!((evaluate("ln(e)") - 1.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn parse_errors_point_to_the_issue() {
+        let error = |formula: &str| Expression::new(formula).unwrap_err();
+
+        assert_eq!(error("1 + $").position(), 4);
+        assert_eq!(error("1 +").position(), 3);
+        assert_eq!(error("(1 + 2").position(), 6);
+        assert_eq!(error("1 2").position(), 2);
+        assert_eq!(error("3 * foo(1)").position(), 4);
+        assert_eq!(
+            error("min(1)").to_string(),
+            "the function `min` takes 2 argument(s), but 1 were given at position 0"
+        );
+        assert_eq!(error("é + ]").position(), 4);
+
+        let nested = format!("{}1{}", "(".repeat(100_000), ")".repeat(100_000));
+        assert_eq!(error(&nested).position(), 256);
+        assert_eq!(error(&"-".repeat(100_000)).position(), 256);
+        assert!(Expression::new(&format!("{}1{}", "(".repeat(200), ")".repeat(200))).is_ok());
+
+        // A flat chain of operators deepens the tree as much as nesting does
+        let chain =
+            |operator: &str, terms: usize| format!("{}1", format!("1{operator}").repeat(terms));
+        assert_eq!(
+            error(&chain("+", 100_000)).to_string(),
+            "the formula nests or chains more than 256 operations at position 512"
+        );
+        assert_eq!(error(&chain("*", 20_000)).position(), 512);
+        assert_eq!(evaluate(&chain("+", 200)), 201.0);
+    }
+
+    #[test]
+    fn variables_become_inputs() {
+        let expression = Expression::new("a * sin(b * t) + c + a").unwrap();
+        assert_eq!(expression.variables(), ["a", "b", "c"]);
+
+        let names: Vec<String> = expression.in_ids().iter().map(|id| id.name()).collect();
+        assert_eq!(names, ["a", "b", "c"]);
+        assert!(
+            expression
+                .node_in_id(&ExpressionInId::new("d"), NodeId::GraphIn)
+                .is_none()
+        );
+
+        let value = fold_node_at(
+            expression,
+            vec![
+                (&ExpressionInId::new("a"), Data::new(2.0f32)),
+                (&ExpressionInId::new("b"), Data::new(0.0f32)),
+                (&ExpressionInId::new("c"), Data::new(1i64)),
+            ],
+            &ExpressionOutId::Out,
+            meta_at(30),
+        )
+        .unwrap();
+        assert_eq!(value.as_f32().unwrap(), 3.0);
+    }
+}
@@ -0,0 +1,811 @@
+//! Nodes building and processing lists of values ([`DataList`])
+//!
+//! Higher-order nodes ([`ListMap`], [`ListFilter`] and [`ListFold`]) run a body [`Subgraph`]
+//! once per element. Inside the body, the element and its index are available as the
+//! [`GraphIn`](crate::GraphIn) outputs named [`ELEMENT`] and [`INDEX`] (and [`ACCUMULATOR`] for
+//! [`ListFold`]), and the body's result is the [`GraphOut`](crate::GraphOut) input named
+//! [`RESULT`] :
+//!
+//! ```
+//! # use quakk::{GraphInOutId, GraphOutInId, list::{self, ListMap}};
+//! let map = ListMap::default();
+//! let body = map.body();
+//! let mut body = body.lock().unwrap();
+//!
+//! let element = body.graph_in_out_id(&GraphInOutId::named(list::ELEMENT)).unwrap();
+//! let result = body.graph_out_in_id(&GraphOutInId::named(list::RESULT)).unwrap();
+//! body.patch(element, result).unwrap();
+//! ```
+
+use std::{
+    collections::HashMap,
+    rc::Rc,
+    sync::{Arc, Mutex},
+};
+
+use anyhow::{Context, anyhow};
+
+use crate::{
+    Data, DataList, Graph, GraphInputs, GraphOutOutId, LasyFold, Meta, Node, Subgraph,
+    id::{InId, NodeId, NodeInId, NodeOutId, OutId},
+};
+
+/// Name of the body input holding the current element
+pub const ELEMENT: &str = "element";
+
+/// Name of the body input holding the index of the current element
+pub const INDEX: &str = "index";
+
+/// Name of the body input holding the accumulated value of a [`ListFold`]
+pub const ACCUMULATOR: &str = "accumulator";
+
+/// Name of the body output holding the result for the current element
+pub const RESULT: &str = "result";
+
+/// The maximum number of elements a [`ListRange`] can produce
+pub const MAX_RANGE_LEN: usize = 1 << 20;
+
+/// Fold the [`RESULT`] of a body graph, its inputs being bound to the given values
+fn fold_body(body: &Subgraph, bindings: HashMap<String, Data>, meta: Meta) -> anyhow::Result<Data> {
+    let graph = body.graph();
+    let graph_out_handle = graph
+        .lock()
+        .expect("the body graph has been poisoned, who was it ?!")
+        .graph_out_handle();
+
+    graph_out_handle.node().fold(
+        &GraphOutOutId::from_name(RESULT),
+        LasyFold::new(NodeId::GraphOut, graph)
+            .with_graph_inputs(GraphInputs::Values(Rc::new(bindings))),
+        meta,
+    )
+}
+
+#[derive(Debug, Default)]
+pub struct ListRange;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ListRangeInId {
+    Start,
+    End,
+    /// Optional, defaults to `1`
+    Step,
+}
+
+impl InId for ListRangeInId {}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ListRangeOutId {
+    Out,
+}
+
+impl OutId for ListRangeOutId {}
+
+impl Node for ListRange {
+    fn initialize() -> Self {
+        Self
+    }
+
+    fn title(&self) -> &str {
+        "List Range"
+    }
+
+    fn fold(&self, _out_id: &dyn OutId, lasy_fold: LasyFold, meta: Meta) -> anyhow::Result<Data> {
+        let start = lasy_fold
+            .get_in(&ListRangeInId::Start, meta)?
+            .to_f32()
+            .context("invalid Start")?;
+        let end = lasy_fold
+            .get_in(&ListRangeInId::End, meta)?
+            .to_f32()
+            .context("invalid End")?;
+        let step = if lasy_fold.is_patched(&ListRangeInId::Step) {
+            lasy_fold
+                .get_in(&ListRangeInId::Step, meta)?
+                .to_f32()
+                .context("invalid Step")?
+        } else {
+            1.0
+        };
+
+        if !(start.is_finite() && end.is_finite() && step.is_finite()) {
+            return Err(anyhow!("the range bounds and step must be finite"));
+        }
+        if step == 0.0 {
+            return Err(anyhow!("the range step cannot be zero"));
+        }
+
+        let len = ((end - start) / step).ceil().max(0.0);
+        if len > MAX_RANGE_LEN as f32 {
+            return Err(anyhow!(
+                "the range would hold {len} elements, more than the maximum of {MAX_RANGE_LEN}"
+            ));
+        }
+
+        let list: DataList = (0..len as usize)
+            .map(|index| Data::new(start + step * index as f32))
+            .collect();
+
+        Ok(Data::new(list))
+    }
+
+    fn node_in_id(&self, in_id: &dyn InId, node_id: NodeId) -> Option<NodeInId> {
+        in_id
+            .as_any()
+            .downcast_ref::<ListRangeInId>()
+            .map(|in_id| NodeInId::new(node_id, in_id))
+    }
+
+    fn node_out_id(&self, out_id: &dyn OutId, node_id: NodeId) -> Option<NodeOutId> {
+        out_id
+            .as_any()
+            .downcast_ref::<ListRangeOutId>()
+            .map(|out_id| NodeOutId::new(node_id, out_id))
+    }
+
+    fn in_ids(&self) -> Vec<Box<dyn InId>> {
+        vec![
+            Box::new(ListRangeInId::Start),
+            Box::new(ListRangeInId::End),
+            Box::new(ListRangeInId::Step),
+        ]
+    }
+
+    fn out_ids(&self) -> Vec<Box<dyn OutId>> {
+        vec![Box::new(ListRangeOutId::Out)]
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct ListLength;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ListLengthInId {
+    List,
+}
+
+impl InId for ListLengthInId {}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ListLengthOutId {
+    Length,
+}
+
+impl OutId for ListLengthOutId {}
+
+impl Node for ListLength {
+    fn initialize() -> Self {
+        Self
+    }
+
+    fn title(&self) -> &str {
+        "List Length"
+    }
+
+    fn fold(&self, _out_id: &dyn OutId, lasy_fold: LasyFold, meta: Meta) -> anyhow::Result<Data> {
+        let list = lasy_fold.get_in(&ListLengthInId::List, meta)?;
+        let list = list.as_list().context("invalid List")?;
+
+        Ok(Data::new(list.len() as i64))
+    }
+
+    fn node_in_id(&self, in_id: &dyn InId, node_id: NodeId) -> Option<NodeInId> {
+        in_id
+            .as_any()
+            .downcast_ref::<ListLengthInId>()
+            .map(|in_id| NodeInId::new(node_id, in_id))
+    }
+
+    fn node_out_id(&self, out_id: &dyn OutId, node_id: NodeId) -> Option<NodeOutId> {
+        out_id
+            .as_any()
+            .downcast_ref::<ListLengthOutId>()
+            .map(|out_id| NodeOutId::new(node_id, out_id))
+    }
+
+    fn in_ids(&self) -> Vec<Box<dyn InId>> {
+        vec![Box::new(ListLengthInId::List)]
+    }
+
+    fn out_ids(&self) -> Vec<Box<dyn OutId>> {
+        vec![Box::new(ListLengthOutId::Length)]
+    }
+}
+
+/// Get an element of a list, negative indices count from the end of the list
+#[derive(Debug, Default)]
+pub struct ListIndex;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ListIndexInId {
+    List,
+    Index,
+}
+
+impl InId for ListIndexInId {}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ListIndexOutId {
+    Element,
+}
+
+impl OutId for ListIndexOutId {}
+
+impl Node for ListIndex {
+    fn initialize() -> Self {
+        Self
+    }
+
+    fn title(&self) -> &str {
+        "List Index"
+    }
+
+    fn fold(&self, _out_id: &dyn OutId, lasy_fold: LasyFold, meta: Meta) -> anyhow::Result<Data> {
+        let list = lasy_fold.get_in(&ListIndexInId::List, meta)?;
+        let list = list.as_list().context("invalid List")?;
+        let index = lasy_fold
+            .get_in(&ListIndexInId::Index, meta)?
+            .to_i64()
+            .context("invalid Index")?;
+
+        let position = if index < 0 {
+            (list.len() as i64).checked_add(index)
+        } else {
+            Some(index)
+        };
+
+        position
+            .and_then(|position| usize::try_from(position).ok())
+            .and_then(|position| list.get(position))
+            .cloned()
+            .ok_or(anyhow!(
+                "the index {index} is out of range for a list of {} elements",
+                list.len()
+            ))
+    }
+
+    fn node_in_id(&self, in_id: &dyn InId, node_id: NodeId) -> Option<NodeInId> {
+        in_id
+            .as_any()
+            .downcast_ref::<ListIndexInId>()
+            .map(|in_id| NodeInId::new(node_id, in_id))
+    }
+
+    fn node_out_id(&self, out_id: &dyn OutId, node_id: NodeId) -> Option<NodeOutId> {
+        out_id
+            .as_any()
+            .downcast_ref::<ListIndexOutId>()
+            .map(|out_id| NodeOutId::new(node_id, out_id))
+    }
+
+    fn in_ids(&self) -> Vec<Box<dyn InId>> {
+        vec![
+            Box::new(ListIndexInId::List),
+            Box::new(ListIndexInId::Index),
+        ]
+    }
+
+    fn out_ids(&self) -> Vec<Box<dyn OutId>> {
+        vec![Box::new(ListIndexOutId::Element)]
+    }
+}
+
+/// Pair the elements of two lists, the result is as long as the shortest list and each of its
+/// elements is a list of two elements
+#[derive(Debug, Default)]
+pub struct ListZip;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ListZipInId {
+    Left,
+    Right,
+}
+
+impl InId for ListZipInId {}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ListZipOutId {
+    Out,
+}
+
+impl OutId for ListZipOutId {}
+
+impl Node for ListZip {
+    fn initialize() -> Self {
+        Self
+    }
+
+    fn title(&self) -> &str {
+        "List Zip"
+    }
+
+    fn fold(&self, _out_id: &dyn OutId, lasy_fold: LasyFold, meta: Meta) -> anyhow::Result<Data> {
+        let left = lasy_fold.get_in(&ListZipInId::Left, meta)?;
+        let left = left.as_list().context("invalid Left")?;
+        let right = lasy_fold.get_in(&ListZipInId::Right, meta)?;
+        let right = right.as_list().context("invalid Right")?;
+
+        let list: DataList = left
+            .iter()
+            .zip(right)
+            .map(|(left, right)| Data::new(vec![left.clone(), right.clone()]))
+            .collect();
+
+        Ok(Data::new(list))
+    }
+
+    fn node_in_id(&self, in_id: &dyn InId, node_id: NodeId) -> Option<NodeInId> {
+        in_id
+            .as_any()
+            .downcast_ref::<ListZipInId>()
+            .map(|in_id| NodeInId::new(node_id, in_id))
+    }
+
+    fn node_out_id(&self, out_id: &dyn OutId, node_id: NodeId) -> Option<NodeOutId> {
+        out_id
+            .as_any()
+            .downcast_ref::<ListZipOutId>()
+            .map(|out_id| NodeOutId::new(node_id, out_id))
+    }
+
+    fn in_ids(&self) -> Vec<Box<dyn InId>> {
+        vec![Box::new(ListZipInId::Left), Box::new(ListZipInId::Right)]
+    }
+
+    fn out_ids(&self) -> Vec<Box<dyn OutId>> {
+        vec![Box::new(ListZipOutId::Out)]
+    }
+}
+
+/// Transform each element of a list with a body graph
+#[derive(Debug, Default)]
+pub struct ListMap {
+    body: Subgraph,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ListMapInId {
+    List,
+}
+
+impl InId for ListMapInId {}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ListMapOutId {
+    Out,
+}
+
+impl OutId for ListMapOutId {}
+
+impl ListMap {
+    pub fn new(body: Subgraph) -> Self {
+        Self { body }
+    }
+
+    /// The graph run for each element
+    pub fn body(&self) -> Arc<Mutex<Graph>> {
+        self.body.graph()
+    }
+}
+
+impl Node for ListMap {
+    fn initialize() -> Self {
+        Self::default()
+    }
+
+    fn title(&self) -> &str {
+        "List Map"
+    }
+
+    fn fold(&self, _out_id: &dyn OutId, lasy_fold: LasyFold, meta: Meta) -> anyhow::Result<Data> {
+        let list = lasy_fold.get_in(&ListMapInId::List, meta)?;
+        let list = list.as_list().context("invalid List")?;
+
+        let list = list
+            .iter()
+            .enumerate()
+            .map(|(index, element)| {
+                let bindings = HashMap::from([
+                    (ELEMENT.to_string(), element.clone()),
+                    (INDEX.to_string(), Data::new(index as i64)),
+                ]);
+
+                fold_body(&self.body, bindings, meta)
+                    .with_context(|| format!("Could not map the element at index {index}"))
+            })
+            .collect::<anyhow::Result<DataList>>()?;
+
+        Ok(Data::new(list))
+    }
+
+    fn node_in_id(&self, in_id: &dyn InId, node_id: NodeId) -> Option<NodeInId> {
+        in_id
+            .as_any()
+            .downcast_ref::<ListMapInId>()
+            .map(|in_id| NodeInId::new(node_id, in_id))
+    }
+
+    fn node_out_id(&self, out_id: &dyn OutId, node_id: NodeId) -> Option<NodeOutId> {
+        out_id
+            .as_any()
+            .downcast_ref::<ListMapOutId>()
+            .map(|out_id| NodeOutId::new(node_id, out_id))
+    }
+
+    fn in_ids(&self) -> Vec<Box<dyn InId>> {
+        vec![Box::new(ListMapInId::List)]
+    }
+
+    fn out_ids(&self) -> Vec<Box<dyn OutId>> {
+        vec![Box::new(ListMapOutId::Out)]
+    }
+
+    fn inner_graph(&self) -> Option<Arc<Mutex<Graph>>> {
+        Some(self.body.graph())
+    }
+}
+
+/// Keep the elements of a list for which the body graph results in `true`
+#[derive(Debug, Default)]
+pub struct ListFilter {
+    body: Subgraph,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ListFilterInId {
+    List,
+}
+
+impl InId for ListFilterInId {}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ListFilterOutId {
+    Out,
+}
+
+impl OutId for ListFilterOutId {}
+
+impl ListFilter {
+    pub fn new(body: Subgraph) -> Self {
+        Self { body }
+    }
+
+    /// The graph run for each element
+    pub fn body(&self) -> Arc<Mutex<Graph>> {
+        self.body.graph()
+    }
+}
+
+impl Node for ListFilter {
+    fn initialize() -> Self {
+        Self::default()
+    }
+
+    fn title(&self) -> &str {
+        "List Filter"
+    }
+
+    fn fold(&self, _out_id: &dyn OutId, lasy_fold: LasyFold, meta: Meta) -> anyhow::Result<Data> {
+        let list = lasy_fold.get_in(&ListFilterInId::List, meta)?;
+        let list = list.as_list().context("invalid List")?;
+
+        let mut filtered = DataList::new();
+        for (index, element) in list.iter().enumerate() {
+            let bindings = HashMap::from([
+                (ELEMENT.to_string(), element.clone()),
+                (INDEX.to_string(), Data::new(index as i64)),
+            ]);
+
+            let keep = fold_body(&self.body, bindings, meta)
+                .and_then(|keep| keep.to_bool())
+                .with_context(|| format!("Could not filter the element at index {index}"))?;
+
+            if keep {
+                filtered.push(element.clone());
+            }
+        }
+
+        Ok(Data::new(filtered))
+    }
+
+    fn node_in_id(&self, in_id: &dyn InId, node_id: NodeId) -> Option<NodeInId> {
+        in_id
+            .as_any()
+            .downcast_ref::<ListFilterInId>()
+            .map(|in_id| NodeInId::new(node_id, in_id))
+    }
+
+    fn node_out_id(&self, out_id: &dyn OutId, node_id: NodeId) -> Option<NodeOutId> {
+        out_id
+            .as_any()
+            .downcast_ref::<ListFilterOutId>()
+            .map(|out_id| NodeOutId::new(node_id, out_id))
+    }
+
+    fn in_ids(&self) -> Vec<Box<dyn InId>> {
+        vec![Box::new(ListFilterInId::List)]
+    }
+
+    fn out_ids(&self) -> Vec<Box<dyn OutId>> {
+        vec![Box::new(ListFilterOutId::Out)]
+    }
+
+    fn inner_graph(&self) -> Option<Arc<Mutex<Graph>>> {
+        Some(self.body.graph())
+    }
+}
+
+/// Fold (or reduce) a list into a single value, starting from `Initial`, the body graph
+/// computes the next accumulated value from the current one and an element
+#[derive(Debug, Default)]
+pub struct ListFold {
+    body: Subgraph,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ListFoldInId {
+    List,
+    Initial,
+}
+
+impl InId for ListFoldInId {}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ListFoldOutId {
+    Out,
+}
+
+impl OutId for ListFoldOutId {}
+
+impl ListFold {
+    pub fn new(body: Subgraph) -> Self {
+        Self { body }
+    }
+
+    /// The graph run for each element
+    pub fn body(&self) -> Arc<Mutex<Graph>> {
+        self.body.graph()
+    }
+}
+
+impl Node for ListFold {
+    fn initialize() -> Self {
+        Self::default()
+    }
+
+    fn title(&self) -> &str {
+        "List Fold"
+    }
+
+    fn fold(&self, _out_id: &dyn OutId, lasy_fold: LasyFold, meta: Meta) -> anyhow::Result<Data> {
+        let list = lasy_fold.get_in(&ListFoldInId::List, meta)?;
+        let list = list.as_list().context("invalid List")?;
+
+        list.iter().enumerate().try_fold(
+            lasy_fold.get_in(&ListFoldInId::Initial, meta)?,
+            |accumulator, (index, element)| {
+                let bindings = HashMap::from([
+                    (ACCUMULATOR.to_string(), accumulator),
+                    (ELEMENT.to_string(), element.clone()),
+                    (INDEX.to_string(), Data::new(index as i64)),
+                ]);
+
+                fold_body(&self.body, bindings, meta)
+                    .with_context(|| format!("Could not fold the element at index {index}"))
+            },
+        )
+    }
+
+    fn node_in_id(&self, in_id: &dyn InId, node_id: NodeId) -> Option<NodeInId> {
+        in_id
+            .as_any()
+            .downcast_ref::<ListFoldInId>()
+            .map(|in_id| NodeInId::new(node_id, in_id))
+    }
+
+    fn node_out_id(&self, out_id: &dyn OutId, node_id: NodeId) -> Option<NodeOutId> {
+        out_id
+            .as_any()
+            .downcast_ref::<ListFoldOutId>()
+            .map(|out_id| NodeOutId::new(node_id, out_id))
+    }
+
+    fn in_ids(&self) -> Vec<Box<dyn InId>> {
+        vec![
+            Box::new(ListFoldInId::List),
+            Box::new(ListFoldInId::Initial),
+        ]
+    }
+
+    fn out_ids(&self) -> Vec<Box<dyn OutId>> {
+        vec![Box::new(ListFoldOutId::Out)]
+    }
+
+    fn inner_graph(&self) -> Option<Arc<Mutex<Graph>>> {
+        Some(self.body.graph())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        GraphInOutId, GraphOutInId,
+        numeric::{ArithmeticOperation, Arithmetics, ArithmeticsInId, ArithmeticsOutId},
+        testing::fold_node,
+    };
+
+    fn numbers(values: &[f32]) -> Data {
+        Data::new(
+            values
+                .iter()
+                .map(|value| Data::new(*value))
+                .collect::<DataList>(),
+        )
+    }
+
+    fn as_numbers(data: &Data) -> Vec<f32> {
+        data.as_list()
+            .unwrap()
+            .iter()
+            .map(|value| value.to_f32().unwrap())
+            .collect()
+    }
+
+    /// Patch `term1 <operation> term2` into a body graph, returning its result
+    fn patch_arithmetics(
+        body: &mut Graph,
+        operation: ArithmeticOperation,
+        term1: &str,
+        term2: &str,
+    ) {
+        let arithmetics = body.insert(Box::new(Arithmetics::new(operation)));
+
+        for (name, in_id) in [
+            (term1, ArithmeticsInId::Term1),
+            (term2, ArithmeticsInId::Term2),
+        ] {
+            let graph_in = body.graph_in_out_id(&GraphInOutId::named(name)).unwrap();
+            body.patch(graph_in, arithmetics.node_in_id(&in_id).unwrap())
+                .unwrap();
+        }
+
+        let result = body.graph_out_in_id(&GraphOutInId::named(RESULT)).unwrap();
+        body.patch(
+            arithmetics.node_out_id(&ArithmeticsOutId::Out).unwrap(),
+            result,
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn range_length_and_index() {
+        let range = fold_node(
+            ListRange,
+            vec![
+                (&ListRangeInId::Start, Data::new(1.0f32)),
+                (&ListRangeInId::End, Data::new(2.0f32)),
+                (&ListRangeInId::Step, Data::new(0.25f32)),
+            ],
+            &ListRangeOutId::Out,
+        )
+        .unwrap();
+        assert_eq!(as_numbers(&range), vec![1.0, 1.25, 1.5, 1.75]);
+
+        let length = fold_node(
+            ListLength,
+            vec![(&ListLengthInId::List, range.clone())],
+            &ListLengthOutId::Length,
+        )
+        .unwrap();
+        assert_eq!(length.as_i64().unwrap(), 4);
+
+        let last = fold_node(
+            ListIndex,
+            vec![
+                (&ListIndexInId::List, range.clone()),
+                (&ListIndexInId::Index, Data::new(-1i64)),
+            ],
+            &ListIndexOutId::Element,
+        )
+        .unwrap();
+        assert_eq!(last.as_f32().unwrap(), 1.75);
+
+        let out_of_range = fold_node(
+            ListIndex,
+            vec![
+                (&ListIndexInId::List, range),
+                (&ListIndexInId::Index, Data::new(4i64)),
+            ],
+            &ListIndexOutId::Element,
+        );
+        assert!(out_of_range.is_err());
+
+        let zero_step = fold_node(
+            ListRange,
+            vec![
+                (&ListRangeInId::Start, Data::new(0.0f32)),
+                (&ListRangeInId::End, Data::new(1.0f32)),
+                (&ListRangeInId::Step, Data::new(0.0f32)),
+            ],
+            &ListRangeOutId::Out,
+        );
+        assert!(zero_step.is_err());
+    }
+
+    #[test]
+    fn zip_pairs_elements() {
+        let zipped = fold_node(
+            ListZip,
+            vec![
+                (&ListZipInId::Left, numbers(&[1.0, 2.0, 3.0])),
+                (&ListZipInId::Right, numbers(&[4.0, 5.0])),
+            ],
+            &ListZipOutId::Out,
+        )
+        .unwrap();
+
+        let pairs: Vec<Vec<f32>> = zipped.as_list().unwrap().iter().map(as_numbers).collect();
+        assert_eq!(pairs, vec![vec![1.0, 4.0], vec![2.0, 5.0]]);
+    }
+
+    #[test]
+    fn map_runs_the_body_per_element() {
+        let map = ListMap::default();
+        patch_arithmetics(
+            &mut map.body().lock().unwrap(),
+            ArithmeticOperation::Multiplication,
+            ELEMENT,
+            INDEX,
+        );
+
+        let mapped = fold_node(
+            map,
+            vec![(&ListMapInId::List, numbers(&[5.0, 5.0, 5.0]))],
+            &ListMapOutId::Out,
+        )
+        .unwrap();
+        assert_eq!(as_numbers(&mapped), vec![0.0, 5.0, 10.0]);
+    }
+
+    #[test]
+    fn filter_keeps_truthy_results() {
+        let filter = ListFilter::default();
+        patch_arithmetics(
+            &mut filter.body().lock().unwrap(),
+            ArithmeticOperation::Substraction,
+            ELEMENT,
+            INDEX,
+        );
+
+        let filtered = fold_node(
+            filter,
+            vec![(&ListFilterInId::List, numbers(&[0.0, 2.0, 2.0, 4.0]))],
+            &ListFilterOutId::Out,
+        )
+        .unwrap();
+        assert_eq!(as_numbers(&filtered), vec![2.0, 4.0]);
+    }
+
+    #[test]
+    fn fold_accumulates() {
+        let fold = ListFold::default();
+        patch_arithmetics(
+            &mut fold.body().lock().unwrap(),
+            ArithmeticOperation::Addition,
+            ACCUMULATOR,
+            ELEMENT,
+        );
+
+        let sum = fold_node(
+            fold,
+            vec![
+                (&ListFoldInId::List, numbers(&[1.0, 2.0, 3.0])),
+                (&ListFoldInId::Initial, Data::new(10.0f32)),
+            ],
+            &ListFoldOutId::Out,
+        )
+        .unwrap();
+        assert_eq!(sum.to_f32().unwrap(), 16.0);
+    }
+}
@@ -0,0 +1,634 @@
+//! Nodes for comparisons, boolean logic and conditional folding
+//!
+//! [`Logic`], [`Select`] and [`Switch`] take advantage of [`LasyFold`] being lasy, and only fold
+//! the inputs needed to compute their output : a [`Select`] only folds the chosen branch, and an
+//! `And` whose first term is `false` never folds its second term.
+
+use anyhow::{Context, anyhow};
+
+use crate::{
+    Data, LasyFold, Meta, Node,
+    id::{InId, NodeId, NodeInId, NodeOutId, OutId},
+};
+
+/// Fold an input of the node, converting it to a `bool`
+fn get_bool(lasy_fold: &LasyFold, in_id: &dyn InId, meta: Meta) -> anyhow::Result<bool> {
+    lasy_fold
+        .get_in(in_id, meta)?
+        .to_bool()
+        .with_context(|| format!("invalid {}", in_id.name()))
+}
+
+#[derive(Debug, Default)]
+pub enum ComparisonOperation {
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+    #[default]
+    Equal,
+    NotEqual,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ComparisonInId {
+    Term1,
+    Term2,
+}
+
+impl InId for ComparisonInId {}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ComparisonOutId {
+    Out,
+}
+
+impl OutId for ComparisonOutId {}
+
+/// Compare two numbers, or two texts
+///
+/// Numbers of different types are compared by value, texts are compared lexicographically
+#[derive(Debug, Default)]
+pub struct Comparison {
+    operation: ComparisonOperation,
+}
+
+impl Comparison {
+    pub fn new(operation: ComparisonOperation) -> Self {
+        Self { operation }
+    }
+}
+
+impl Node for Comparison {
+    fn initialize() -> Self {
+        Self::default()
+    }
+
+    fn title(&self) -> &str {
+        "Comparison"
+    }
+
+    fn fold(&self, _out_id: &dyn OutId, lasy_fold: LasyFold, meta: Meta) -> anyhow::Result<Data> {
+        let term1 = lasy_fold.get_in(&ComparisonInId::Term1, meta)?;
+        let term2 = lasy_fold.get_in(&ComparisonInId::Term2, meta)?;
+
+        let ordering = match (term1.as_text(), term2.as_text()) {
+            (Ok(text1), Ok(text2)) => Some(text1.cmp(text2)),
+            _ => {
+                let number1 = term1.to_f64().context("invalid Term1")?;
+                let number2 = term2.to_f64().context("invalid Term2")?;
+                number1.partial_cmp(&number2)
+            }
+        };
+
+        // An unordered comparison (involving NaN) is only ever "not equal"
+        use ComparisonOperation::*;
+        let res = match ordering {
+            Some(ordering) => match self.operation {
+                Less => ordering.is_lt(),
+                LessOrEqual => ordering.is_le(),
+                Greater => ordering.is_gt(),
+                GreaterOrEqual => ordering.is_ge(),
+                Equal => ordering.is_eq(),
+                NotEqual => ordering.is_ne(),
+            },
+            None => matches!(self.operation, NotEqual),
+        };
+
+        Ok(Data::new(res))
+    }
+
+    fn node_in_id(&self, in_id: &dyn InId, node_id: NodeId) -> Option<NodeInId> {
+        in_id
+            .as_any()
+            .downcast_ref::<ComparisonInId>()
+            .map(|in_id| NodeInId::new(node_id, in_id))
+    }
+
+    fn node_out_id(&self, out_id: &dyn OutId, node_id: NodeId) -> Option<NodeOutId> {
+        out_id
+            .as_any()
+            .downcast_ref::<ComparisonOutId>()
+            .map(|out_id| NodeOutId::new(node_id, out_id))
+    }
+
+    fn in_ids(&self) -> Vec<Box<dyn InId>> {
+        vec![
+            Box::new(ComparisonInId::Term1),
+            Box::new(ComparisonInId::Term2),
+        ]
+    }
+
+    fn out_ids(&self) -> Vec<Box<dyn OutId>> {
+        vec![Box::new(ComparisonOutId::Out)]
+    }
+}
+
+/// Is the distance between two numbers at most `Epsilon`
+#[derive(Debug, Default)]
+pub struct ApproxEqual;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ApproxEqualInId {
+    Term1,
+    Term2,
+    Epsilon,
+}
+
+impl InId for ApproxEqualInId {}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ApproxEqualOutId {
+    Out,
+}
+
+impl OutId for ApproxEqualOutId {}
+
+impl Node for ApproxEqual {
+    fn initialize() -> Self {
+        Self
+    }
+
+    fn title(&self) -> &str {
+        "Approx Equal"
+    }
+
+    fn fold(&self, _out_id: &dyn OutId, lasy_fold: LasyFold, meta: Meta) -> anyhow::Result<Data> {
+        let term1 = lasy_fold
+            .get_in(&ApproxEqualInId::Term1, meta)?
+            .to_f64()
+            .context("invalid Term1")?;
+        let term2 = lasy_fold
+            .get_in(&ApproxEqualInId::Term2, meta)?
+            .to_f64()
+            .context("invalid Term2")?;
+        let epsilon = lasy_fold
+            .get_in(&ApproxEqualInId::Epsilon, meta)?
+            .to_f64()
+            .context("invalid Epsilon")?;
+
+        Ok(Data::new((term1 - term2).abs() <= epsilon.abs()))
+    }
+
+    fn node_in_id(&self, in_id: &dyn InId, node_id: NodeId) -> Option<NodeInId> {
+        in_id
+            .as_any()
+            .downcast_ref::<ApproxEqualInId>()
+            .map(|in_id| NodeInId::new(node_id, in_id))
+    }
+
+    fn node_out_id(&self, out_id: &dyn OutId, node_id: NodeId) -> Option<NodeOutId> {
+        out_id
+            .as_any()
+            .downcast_ref::<ApproxEqualOutId>()
+            .map(|out_id| NodeOutId::new(node_id, out_id))
+    }
+
+    fn in_ids(&self) -> Vec<Box<dyn InId>> {
+        vec![
+            Box::new(ApproxEqualInId::Term1),
+            Box::new(ApproxEqualInId::Term2),
+            Box::new(ApproxEqualInId::Epsilon),
+        ]
+    }
+
+    fn out_ids(&self) -> Vec<Box<dyn OutId>> {
+        vec![Box::new(ApproxEqualOutId::Out)]
+    }
+}
+
+#[derive(Debug, Default)]
+pub enum LogicOperation {
+    #[default]
+    And,
+    Or,
+    Xor,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum LogicInId {
+    Term1,
+    Term2,
+}
+
+impl InId for LogicInId {}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum LogicOutId {
+    Out,
+}
+
+impl OutId for LogicOutId {}
+
+/// A boolean operation, `And` and `Or` short-circuit, only folding `Term2` when needed
+#[derive(Debug, Default)]
+pub struct Logic {
+    operation: LogicOperation,
+}
+
+impl Logic {
+    pub fn new(operation: LogicOperation) -> Self {
+        Self { operation }
+    }
+}
+
+impl Node for Logic {
+    fn initialize() -> Self {
+        Self::default()
+    }
+
+    fn title(&self) -> &str {
+        "Logic"
+    }
+
+    fn fold(&self, _out_id: &dyn OutId, lasy_fold: LasyFold, meta: Meta) -> anyhow::Result<Data> {
+        let term1 = get_bool(&lasy_fold, &LogicInId::Term1, meta)?;
+
+        use LogicOperation::*;
+        let res = match self.operation {
+            And => term1 && get_bool(&lasy_fold, &LogicInId::Term2, meta)?,
+            Or => term1 || get_bool(&lasy_fold, &LogicInId::Term2, meta)?,
+            Xor => term1 ^ get_bool(&lasy_fold, &LogicInId::Term2, meta)?,
+        };
+
+        Ok(Data::new(res))
+    }
+
+    fn node_in_id(&self, in_id: &dyn InId, node_id: NodeId) -> Option<NodeInId> {
+        in_id
+            .as_any()
+            .downcast_ref::<LogicInId>()
+            .map(|in_id| NodeInId::new(node_id, in_id))
+    }
+
+    fn node_out_id(&self, out_id: &dyn OutId, node_id: NodeId) -> Option<NodeOutId> {
+        out_id
+            .as_any()
+            .downcast_ref::<LogicOutId>()
+            .map(|out_id| NodeOutId::new(node_id, out_id))
+    }
+
+    fn in_ids(&self) -> Vec<Box<dyn InId>> {
+        vec![Box::new(LogicInId::Term1), Box::new(LogicInId::Term2)]
+    }
+
+    fn out_ids(&self) -> Vec<Box<dyn OutId>> {
+        vec![Box::new(LogicOutId::Out)]
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Not;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum NotInId {
+    In,
+}
+
+impl InId for NotInId {}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum NotOutId {
+    Out,
+}
+
+impl OutId for NotOutId {}
+
+impl Node for Not {
+    fn initialize() -> Self {
+        Self
+    }
+
+    fn title(&self) -> &str {
+        "Not"
+    }
+
+    fn fold(&self, _out_id: &dyn OutId, lasy_fold: LasyFold, meta: Meta) -> anyhow::Result<Data> {
+        Ok(Data::new(!get_bool(&lasy_fold, &NotInId::In, meta)?))
+    }
+
+    fn node_in_id(&self, in_id: &dyn InId, node_id: NodeId) -> Option<NodeInId> {
+        in_id
+            .as_any()
+            .downcast_ref::<NotInId>()
+            .map(|in_id| NodeInId::new(node_id, in_id))
+    }
+
+    fn node_out_id(&self, out_id: &dyn OutId, node_id: NodeId) -> Option<NodeOutId> {
+        out_id
+            .as_any()
+            .downcast_ref::<NotOutId>()
+            .map(|out_id| NodeOutId::new(node_id, out_id))
+    }
+
+    fn in_ids(&self) -> Vec<Box<dyn InId>> {
+        vec![Box::new(NotInId::In)]
+    }
+
+    fn out_ids(&self) -> Vec<Box<dyn OutId>> {
+        vec![Box::new(NotOutId::Out)]
+    }
+}
+
+/// Forward `IfTrue` or `IfFalse` depending on `Condition`, only the chosen branch is folded
+#[derive(Debug, Default)]
+pub struct Select;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum SelectInId {
+    Condition,
+    IfTrue,
+    IfFalse,
+}
+
+impl InId for SelectInId {}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum SelectOutId {
+    Out,
+}
+
+impl OutId for SelectOutId {}
+
+impl Node for Select {
+    fn initialize() -> Self {
+        Self
+    }
+
+    fn title(&self) -> &str {
+        "Select"
+    }
+
+    fn fold(&self, _out_id: &dyn OutId, lasy_fold: LasyFold, meta: Meta) -> anyhow::Result<Data> {
+        if get_bool(&lasy_fold, &SelectInId::Condition, meta)? {
+            lasy_fold.get_in(&SelectInId::IfTrue, meta)
+        } else {
+            lasy_fold.get_in(&SelectInId::IfFalse, meta)
+        }
+    }
+
+    fn node_in_id(&self, in_id: &dyn InId, node_id: NodeId) -> Option<NodeInId> {
+        in_id
+            .as_any()
+            .downcast_ref::<SelectInId>()
+            .map(|in_id| NodeInId::new(node_id, in_id))
+    }
+
+    fn node_out_id(&self, out_id: &dyn OutId, node_id: NodeId) -> Option<NodeOutId> {
+        out_id
+            .as_any()
+            .downcast_ref::<SelectOutId>()
+            .map(|out_id| NodeOutId::new(node_id, out_id))
+    }
+
+    fn in_ids(&self) -> Vec<Box<dyn InId>> {
+        vec![
+            Box::new(SelectInId::Condition),
+            Box::new(SelectInId::IfTrue),
+            Box::new(SelectInId::IfFalse),
+        ]
+    }
+
+    fn out_ids(&self) -> Vec<Box<dyn OutId>> {
+        vec![Box::new(SelectOutId::Out)]
+    }
+}
+
+/// Forward one of its branches depending on `Index`, only the chosen branch is folded
+#[derive(Debug)]
+pub struct Switch {
+    branches: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum SwitchInId {
+    Index,
+    Branch(usize),
+}
+
+impl InId for SwitchInId {}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum SwitchOutId {
+    Out,
+}
+
+impl OutId for SwitchOutId {}
+
+impl Switch {
+    /// Create a switch between the given number of branches
+    pub fn new(branches: usize) -> Self {
+        Self { branches }
+    }
+}
+
+impl Default for Switch {
+    fn default() -> Self {
+        Self::new(2)
+    }
+}
+
+impl Node for Switch {
+    fn initialize() -> Self {
+        Self::default()
+    }
+
+    fn title(&self) -> &str {
+        "Switch"
+    }
+
+    fn fold(&self, _out_id: &dyn OutId, lasy_fold: LasyFold, meta: Meta) -> anyhow::Result<Data> {
+        let index = lasy_fold
+            .get_in(&SwitchInId::Index, meta)?
+            .to_i64()
+            .context("invalid Index")?;
+
+        let branch = usize::try_from(index)
+            .ok()
+            .filter(|branch| *branch < self.branches)
+            .ok_or(anyhow!(
+                "the index {index} is out of range for a switch of {} branches",
+                self.branches
+            ))?;
+
+        lasy_fold.get_in(&SwitchInId::Branch(branch), meta)
+    }
+
+    fn node_in_id(&self, in_id: &dyn InId, node_id: NodeId) -> Option<NodeInId> {
+        in_id
+            .as_any()
+            .downcast_ref::<SwitchInId>()
+            .filter(|in_id| match in_id {
+                SwitchInId::Index => true,
+                SwitchInId::Branch(branch) => *branch < self.branches,
+            })
+            .map(|in_id| NodeInId::new(node_id, in_id))
+    }
+
+    fn node_out_id(&self, out_id: &dyn OutId, node_id: NodeId) -> Option<NodeOutId> {
+        out_id
+            .as_any()
+            .downcast_ref::<SwitchOutId>()
+            .map(|out_id| NodeOutId::new(node_id, out_id))
+    }
+
+    fn in_ids(&self) -> Vec<Box<dyn InId>> {
+        let mut in_ids: Vec<Box<dyn InId>> = vec![Box::new(SwitchInId::Index)];
+        in_ids.extend(
+            (0..self.branches).map(|branch| Box::new(SwitchInId::Branch(branch)) as Box<dyn InId>),
+        );
+        in_ids
+    }
+
+    fn out_ids(&self) -> Vec<Box<dyn OutId>> {
+        vec![Box::new(SwitchOutId::Out)]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{Constant, Failing, fold_node, fold_node_with, meta_at};
+
+    fn compare(operation: ComparisonOperation, term1: Data, term2: Data) -> anyhow::Result<bool> {
+        fold_node(
+            Comparison::new(operation),
+            vec![
+                (&ComparisonInId::Term1, term1),
+                (&ComparisonInId::Term2, term2),
+            ],
+            &ComparisonOutId::Out,
+        )?
+        .as_bool()
+    }
+
+    #[test]
+    fn comparisons() {
+        use ComparisonOperation::*;
+
+        assert!(compare(Less, Data::new(1.0f32), Data::new(2i64)).unwrap());
+        assert!(!compare(Greater, Data::new(1.0f32), Data::new(2.0f32)).unwrap());
+        assert!(compare(Equal, Data::new(true), Data::new(1.0f32)).unwrap());
+        assert!(compare(NotEqual, Data::new(f32::NAN), Data::new(f32::NAN)).unwrap());
+        assert!(!compare(LessOrEqual, Data::new(f32::NAN), Data::new(1.0f32)).unwrap());
+        assert!(
+            compare(
+                GreaterOrEqual,
+                Data::new("b".to_string()),
+                Data::new("a".to_string())
+            )
+            .unwrap()
+        );
+        assert!(compare(Equal, Data::new("1".to_string()), Data::new(1.0f32)).is_err());
+
+        let approx = |epsilon: f32| {
+            fold_node(
+                ApproxEqual,
+                vec![
+                    (&ApproxEqualInId::Term1, Data::new(1.0f32)),
+                    (&ApproxEqualInId::Term2, Data::new(1.05f32)),
+                    (&ApproxEqualInId::Epsilon, Data::new(epsilon)),
+                ],
+                &ApproxEqualOutId::Out,
+            )
+            .unwrap()
+            .as_bool()
+            .unwrap()
+        };
+        assert!(approx(0.1));
+        assert!(!approx(0.01));
+    }
+
+    #[test]
+    fn logic_short_circuits() {
+        let logic = |operation: LogicOperation, term1: bool| {
+            fold_node_with(
+                Logic::new(operation),
+                vec![
+                    (&LogicInId::Term1, Box::new(Constant::new(Data::new(term1)))),
+                    (&LogicInId::Term2, Box::new(Failing)),
+                ],
+                &LogicOutId::Out,
+                meta_at(0),
+            )
+        };
+
+        assert!(
+            !logic(LogicOperation::And, false)
+                .unwrap()
+                .as_bool()
+                .unwrap()
+        );
+        assert!(logic(LogicOperation::Or, true).unwrap().as_bool().unwrap());
+        assert!(logic(LogicOperation::And, true).is_err());
+        assert!(logic(LogicOperation::Xor, true).is_err());
+
+        let xor = fold_node(
+            Logic::new(LogicOperation::Xor),
+            vec![
+                (&LogicInId::Term1, Data::new(true)),
+                (&LogicInId::Term2, Data::new(1.0f32)),
+            ],
+            &LogicOutId::Out,
+        )
+        .unwrap();
+        assert!(!xor.as_bool().unwrap());
+
+        let not = fold_node(Not, vec![(&NotInId::In, Data::new(0i64))], &NotOutId::Out).unwrap();
+        assert!(not.as_bool().unwrap());
+    }
+
+    #[test]
+    fn select_only_folds_the_chosen_branch() {
+        let selected = fold_node_with(
+            Select,
+            vec![
+                (
+                    &SelectInId::Condition,
+                    Box::new(Constant::new(Data::new(false))),
+                ),
+                (&SelectInId::IfTrue, Box::new(Failing)),
+                (
+                    &SelectInId::IfFalse,
+                    Box::new(Constant::new(Data::new("scene b".to_string()))),
+                ),
+            ],
+            &SelectOutId::Out,
+            meta_at(0),
+        )
+        .unwrap();
+        assert_eq!(selected.as_text().unwrap(), "scene b");
+    }
+
+    #[test]
+    fn switch_only_folds_the_chosen_branch() {
+        let switch = |index: i64| {
+            fold_node_with(
+                Switch::new(3),
+                vec![
+                    (
+                        &SwitchInId::Index,
+                        Box::new(Constant::new(Data::new(index))),
+                    ),
+                    (&SwitchInId::Branch(0), Box::new(Failing)),
+                    (
+                        &SwitchInId::Branch(1),
+                        Box::new(Constant::new(Data::new(1.0f32))),
+                    ),
+                    (&SwitchInId::Branch(2), Box::new(Failing)),
+                ],
+                &SwitchOutId::Out,
+                meta_at(0),
+            )
+        };
+
+        assert_eq!(switch(1).unwrap().as_f32().unwrap(), 1.0);
+        assert!(switch(3).is_err());
+        assert!(switch(-1).is_err());
+        assert!(
+            Switch::new(3)
+                .node_in_id(&SwitchInId::Branch(3), NodeId::GraphIn)
+                .is_none()
+        );
+    }
+}
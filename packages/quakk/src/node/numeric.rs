@@ -1,6 +1,6 @@
 use std::ops::{Add as opsAdd, Div, Mul as opsMul, Sub};
 
-use anyhow::Context;
+use anyhow::{Context, anyhow};
 
 use crate::{
     Data, LasyFold, Meta, Node,
@@ -45,6 +45,14 @@ impl Node for NumericConstant {
             .map(|out_id| NodeOutId::new(node_id, out_id))
     }
 
+    fn in_ids(&self) -> Vec<Box<dyn InId>> {
+        Vec::new()
+    }
+
+    fn out_ids(&self) -> Vec<Box<dyn OutId>> {
+        vec![Box::new(NumericConstantOutId::Out)]
+    }
+
     fn fold(&self, _out_id: &dyn OutId, _lasy_fold: LasyFold, _meta: Meta) -> anyhow::Result<Data> {
         Ok(Data::new(self.value))
     }
@@ -57,6 +65,12 @@ pub enum ArithmeticOperation {
     Substraction,
     Multiplication,
     Division,
+    /// The euclidean modulo, always positive for a positive `Term2`, handy to wrap phases
+    Modulo,
+    /// `Term1` raised to the power of `Term2`
+    Power,
+    Minimum,
+    Maximum,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
@@ -97,12 +111,12 @@ impl Node for Arithmetics {
     fn fold(&self, _out_id: &dyn OutId, lasy_fold: LasyFold, meta: Meta) -> anyhow::Result<Data> {
         let term1 = lasy_fold
             .get_in(&ArithmeticsInId::Term1, meta)?
-            .into_f32()
-            .context("type mismatch for Term1")?;
+            .to_f32()
+            .context("invalid Term1")?;
         let term2 = lasy_fold
             .get_in(&ArithmeticsInId::Term2, meta)?
-            .into_f32()
-            .context("type mismatch for Term2")?;
+            .to_f32()
+            .context("invalid Term2")?;
 
         use ArithmeticOperation::*;
         let res = match self.operation {
@@ -110,6 +124,10 @@ impl Node for Arithmetics {
             Substraction => term1.sub(term2),
             Multiplication => term1.mul(term2),
             Division => term1.div(term2),
+            Modulo => term1.rem_euclid(term2),
+            Power => term1.powf(term2),
+            Minimum => term1.min(term2),
+            Maximum => term1.max(term2),
         };
 
         Ok(Data::new(res))
@@ -128,4 +146,473 @@ impl Node for Arithmetics {
             .downcast_ref::<ArithmeticsOutId>()
             .map(|out_id| NodeOutId::new(node_id, out_id))
     }
+
+    fn in_ids(&self) -> Vec<Box<dyn InId>> {
+        vec![
+            Box::new(ArithmeticsInId::Term1),
+            Box::new(ArithmeticsInId::Term2),
+        ]
+    }
+
+    fn out_ids(&self) -> Vec<Box<dyn OutId>> {
+        vec![Box::new(ArithmeticsOutId::Out)]
+    }
+}
+
+/// Fold an input of the node, converting it to an `f32`
+fn get_f32(lasy_fold: &LasyFold, in_id: &dyn InId, meta: Meta) -> anyhow::Result<f32> {
+    lasy_fold
+        .get_in(in_id, meta)?
+        .to_f32()
+        .with_context(|| format!("invalid {}", in_id.name()))
+}
+
+/// A function of a single number
+#[derive(Debug, Default)]
+pub enum MathFunction {
+    #[default]
+    Absolute,
+    /// `-1`, `0` or `1` depending on the sign of the input
+    Sign,
+    SquareRoot,
+    Floor,
+    Ceil,
+    /// Round half-way cases away from zero
+    Round,
+    /// The fractional part, `In - Floor(In)`
+    Fract,
+    Negate,
+    Sine,
+    Cosine,
+    Tangent,
+    ArcSine,
+    ArcCosine,
+    ArcTangent,
+}
+
+impl MathFunction {
+    fn apply(&self, value: f32) -> f32 {
+        use MathFunction::*;
+        match self {
+            Absolute => value.abs(),
+            Sign if value == 0.0 => 0.0,
+            Sign => value.signum(),
+            SquareRoot => value.sqrt(),
+            Floor => value.floor(),
+            Ceil => value.ceil(),
+            Round => value.round(),
+            Fract => value - value.floor(),
+            Negate => -value,
+            Sine => value.sin(),
+            Cosine => value.cos(),
+            Tangent => value.tan(),
+            ArcSine => value.asin(),
+            ArcCosine => value.acos(),
+            ArcTangent => value.atan(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum MathInId {
+    In,
+}
+
+impl InId for MathInId {}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum MathOutId {
+    Out,
+}
+
+impl OutId for MathOutId {}
+
+/// Apply a [`MathFunction`] to a number, trigonometric functions work in radians
+#[derive(Debug, Default)]
+pub struct Math {
+    function: MathFunction,
+}
+
+impl Math {
+    pub fn new(function: MathFunction) -> Self {
+        Self { function }
+    }
+}
+
+impl Node for Math {
+    fn initialize() -> Self {
+        Self::default()
+    }
+
+    fn title(&self) -> &str {
+        "Math"
+    }
+
+    fn fold(&self, _out_id: &dyn OutId, lasy_fold: LasyFold, meta: Meta) -> anyhow::Result<Data> {
+        let value = get_f32(&lasy_fold, &MathInId::In, meta)?;
+
+        Ok(Data::new(self.function.apply(value)))
+    }
+
+    fn node_in_id(&self, in_id: &dyn InId, node_id: NodeId) -> Option<NodeInId> {
+        in_id
+            .as_any()
+            .downcast_ref::<MathInId>()
+            .map(|in_id| NodeInId::new(node_id, in_id))
+    }
+
+    fn node_out_id(&self, out_id: &dyn OutId, node_id: NodeId) -> Option<NodeOutId> {
+        out_id
+            .as_any()
+            .downcast_ref::<MathOutId>()
+            .map(|out_id| NodeOutId::new(node_id, out_id))
+    }
+
+    fn in_ids(&self) -> Vec<Box<dyn InId>> {
+        vec![Box::new(MathInId::In)]
+    }
+
+    fn out_ids(&self) -> Vec<Box<dyn OutId>> {
+        vec![Box::new(MathOutId::Out)]
+    }
+}
+
+/// Restrict a value between two bounds, bounds given in the wrong order are swapped
+#[derive(Debug, Default)]
+pub struct Clamp;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ClampInId {
+    Value,
+    Min,
+    Max,
+}
+
+impl InId for ClampInId {}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ClampOutId {
+    Out,
+}
+
+impl OutId for ClampOutId {}
+
+impl Node for Clamp {
+    fn initialize() -> Self {
+        Self
+    }
+
+    fn title(&self) -> &str {
+        "Clamp"
+    }
+
+    fn fold(&self, _out_id: &dyn OutId, lasy_fold: LasyFold, meta: Meta) -> anyhow::Result<Data> {
+        let value = get_f32(&lasy_fold, &ClampInId::Value, meta)?;
+        let min = get_f32(&lasy_fold, &ClampInId::Min, meta)?;
+        let max = get_f32(&lasy_fold, &ClampInId::Max, meta)?;
+
+        if min.is_nan() || max.is_nan() {
+            return Err(anyhow!("the clamp bounds cannot be NaN"));
+        }
+
+        Ok(Data::new(value.clamp(min.min(max), min.max(max))))
+    }
+
+    fn node_in_id(&self, in_id: &dyn InId, node_id: NodeId) -> Option<NodeInId> {
+        in_id
+            .as_any()
+            .downcast_ref::<ClampInId>()
+            .map(|in_id| NodeInId::new(node_id, in_id))
+    }
+
+    fn node_out_id(&self, out_id: &dyn OutId, node_id: NodeId) -> Option<NodeOutId> {
+        out_id
+            .as_any()
+            .downcast_ref::<ClampOutId>()
+            .map(|out_id| NodeOutId::new(node_id, out_id))
+    }
+
+    fn in_ids(&self) -> Vec<Box<dyn InId>> {
+        vec![
+            Box::new(ClampInId::Value),
+            Box::new(ClampInId::Min),
+            Box::new(ClampInId::Max),
+        ]
+    }
+
+    fn out_ids(&self) -> Vec<Box<dyn OutId>> {
+        vec![Box::new(ClampOutId::Out)]
+    }
+}
+
+/// Linear interpolation between `From` and `To`, `Factor` going from `0` to `1`
+#[derive(Debug, Default)]
+pub struct Lerp;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum LerpInId {
+    From,
+    To,
+    Factor,
+}
+
+impl InId for LerpInId {}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum LerpOutId {
+    Out,
+}
+
+impl OutId for LerpOutId {}
+
+impl Node for Lerp {
+    fn initialize() -> Self {
+        Self
+    }
+
+    fn title(&self) -> &str {
+        "Lerp"
+    }
+
+    fn fold(&self, _out_id: &dyn OutId, lasy_fold: LasyFold, meta: Meta) -> anyhow::Result<Data> {
+        let from = get_f32(&lasy_fold, &LerpInId::From, meta)?;
+        let to = get_f32(&lasy_fold, &LerpInId::To, meta)?;
+        let factor = get_f32(&lasy_fold, &LerpInId::Factor, meta)?;
+
+        Ok(Data::new(from + (to - from) * factor))
+    }
+
+    fn node_in_id(&self, in_id: &dyn InId, node_id: NodeId) -> Option<NodeInId> {
+        in_id
+            .as_any()
+            .downcast_ref::<LerpInId>()
+            .map(|in_id| NodeInId::new(node_id, in_id))
+    }
+
+    fn node_out_id(&self, out_id: &dyn OutId, node_id: NodeId) -> Option<NodeOutId> {
+        out_id
+            .as_any()
+            .downcast_ref::<LerpOutId>()
+            .map(|out_id| NodeOutId::new(node_id, out_id))
+    }
+
+    fn in_ids(&self) -> Vec<Box<dyn InId>> {
+        vec![
+            Box::new(LerpInId::From),
+            Box::new(LerpInId::To),
+            Box::new(LerpInId::Factor),
+        ]
+    }
+
+    fn out_ids(&self) -> Vec<Box<dyn OutId>> {
+        vec![Box::new(LerpOutId::Out)]
+    }
+}
+
+/// Map a value from a range to another, optionally clamping the result to the target range
+///
+/// An empty source range maps everything to `ToMin`
+#[derive(Debug, Default)]
+pub struct MapRange {
+    clamp: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum MapRangeInId {
+    Value,
+    FromMin,
+    FromMax,
+    ToMin,
+    ToMax,
+}
+
+impl InId for MapRangeInId {}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum MapRangeOutId {
+    Out,
+}
+
+impl OutId for MapRangeOutId {}
+
+impl MapRange {
+    pub fn new(clamp: bool) -> Self {
+        Self { clamp }
+    }
+}
+
+impl Node for MapRange {
+    fn initialize() -> Self {
+        Self::default()
+    }
+
+    fn title(&self) -> &str {
+        "Map Range"
+    }
+
+    fn fold(&self, _out_id: &dyn OutId, lasy_fold: LasyFold, meta: Meta) -> anyhow::Result<Data> {
+        let value = get_f32(&lasy_fold, &MapRangeInId::Value, meta)?;
+        let from_min = get_f32(&lasy_fold, &MapRangeInId::FromMin, meta)?;
+        let from_max = get_f32(&lasy_fold, &MapRangeInId::FromMax, meta)?;
+        let to_min = get_f32(&lasy_fold, &MapRangeInId::ToMin, meta)?;
+        let to_max = get_f32(&lasy_fold, &MapRangeInId::ToMax, meta)?;
+
+        if from_min == from_max {
+            return Ok(Data::new(to_min));
+        }
+
+        let mut factor = (value - from_min) / (from_max - from_min);
+        if self.clamp {
+            factor = factor.clamp(0.0, 1.0);
+        }
+
+        Ok(Data::new(to_min + (to_max - to_min) * factor))
+    }
+
+    fn node_in_id(&self, in_id: &dyn InId, node_id: NodeId) -> Option<NodeInId> {
+        in_id
+            .as_any()
+            .downcast_ref::<MapRangeInId>()
+            .map(|in_id| NodeInId::new(node_id, in_id))
+    }
+
+    fn node_out_id(&self, out_id: &dyn OutId, node_id: NodeId) -> Option<NodeOutId> {
+        out_id
+            .as_any()
+            .downcast_ref::<MapRangeOutId>()
+            .map(|out_id| NodeOutId::new(node_id, out_id))
+    }
+
+    fn in_ids(&self) -> Vec<Box<dyn InId>> {
+        vec![
+            Box::new(MapRangeInId::Value),
+            Box::new(MapRangeInId::FromMin),
+            Box::new(MapRangeInId::FromMax),
+            Box::new(MapRangeInId::ToMin),
+            Box::new(MapRangeInId::ToMax),
+        ]
+    }
+
+    fn out_ids(&self) -> Vec<Box<dyn OutId>> {
+        vec![Box::new(MapRangeOutId::Out)]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::PI;
+
+    use super::*;
+    use crate::testing::fold_node;
+
+    fn arithmetics(operation: ArithmeticOperation, term1: f32, term2: f32) -> f32 {
+        fold_node(
+            Arithmetics::new(operation),
+            vec![
+                (&ArithmeticsInId::Term1, Data::new(term1)),
+                (&ArithmeticsInId::Term2, Data::new(term2)),
+            ],
+            &ArithmeticsOutId::Out,
+        )
+        .unwrap()
+        .as_f32()
+        .unwrap()
+    }
+
+    fn math(function: MathFunction, value: f32) -> f32 {
+        fold_node(
+            Math::new(function),
+            vec![(&MathInId::In, Data::new(value))],
+            &MathOutId::Out,
+        )
+        .unwrap()
+        .as_f32()
+        .unwrap()
+    }
+
+    #[test]
+    fn arithmetic_operations() {
+        use ArithmeticOperation::*;
+
+        assert_eq!(arithmetics(Addition, 2.0, 3.0), 5.0);
+        assert_eq!(arithmetics(Substraction, 2.0, 3.0), -1.0);
+        assert_eq!(arithmetics(Multiplication, 2.0, 3.0), 6.0);
+        assert_eq!(arithmetics(Division, 3.0, 2.0), 1.5);
+        assert_eq!(arithmetics(Modulo, -1.0, 4.0), 3.0);
+        assert_eq!(arithmetics(Power, 2.0, 10.0), 1024.0);
+        assert_eq!(arithmetics(Minimum, 2.0, 3.0), 2.0);
+        assert_eq!(arithmetics(Maximum, 2.0, 3.0), 3.0);
+    }
+
+    #[test]
+    fn math_functions() {
+        use MathFunction::*;
+
+        assert_eq!(math(Absolute, -2.5), 2.5);
+        assert_eq!(math(Sign, -2.5), -1.0);
+        assert_eq!(math(Sign, 0.0), 0.0);
+        assert_eq!(math(SquareRoot, 16.0), 4.0);
+        assert!(math(SquareRoot, -1.0).is_nan());
+        assert_eq!(math(Floor, -1.5), -2.0);
+        assert_eq!(math(Ceil, 1.2), 2.0);
+        assert_eq!(math(Round, 2.5), 3.0);
+        assert_eq!(math(Fract, -0.25), 0.75);
+        assert_eq!(math(Negate, 1.0), -1.0);
+        assert!((math(Sine, PI / 2.0) - 1.0).abs() < 1e-6);
+        assert!((math(Cosine, PI) + 1.0).abs() < 1e-6);
+        assert!((math(Tangent, PI / 4.0) - 1.0).abs() < 1e-6);
+        assert!((math(ArcSine, 1.0) - PI / 2.0).abs() < 1e-6);
+        assert!((math(ArcCosine, -1.0) - PI).abs() < 1e-6);
+        assert!((math(ArcTangent, 1.0) - PI / 4.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn clamp_lerp_and_map_range() {
+        let clamp = |value: f32, min: f32, max: f32| {
+            fold_node(
+                Clamp,
+                vec![
+                    (&ClampInId::Value, Data::new(value)),
+                    (&ClampInId::Min, Data::new(min)),
+                    (&ClampInId::Max, Data::new(max)),
+                ],
+                &ClampOutId::Out,
+            )
+        };
+        assert_eq!(clamp(5.0, 0.0, 1.0).unwrap().as_f32().unwrap(), 1.0);
+        assert_eq!(clamp(0.5, 1.0, 0.0).unwrap().as_f32().unwrap(), 0.5);
+        assert!(clamp(0.5, f32::NAN, 0.0).is_err());
+
+        let lerp = fold_node(
+            Lerp,
+            vec![
+                (&LerpInId::From, Data::new(10.0f32)),
+                (&LerpInId::To, Data::new(20.0f32)),
+                (&LerpInId::Factor, Data::new(0.25f32)),
+            ],
+            &LerpOutId::Out,
+        )
+        .unwrap();
+        assert_eq!(lerp.as_f32().unwrap(), 12.5);
+
+        let map_range = |clamp: bool, value: f32| {
+            fold_node(
+                MapRange::new(clamp),
+                vec![
+                    (&MapRangeInId::Value, Data::new(value)),
+                    (&MapRangeInId::FromMin, Data::new(-1.0f32)),
+                    (&MapRangeInId::FromMax, Data::new(1.0f32)),
+                    (&MapRangeInId::ToMin, Data::new(0.0f32)),
+                    (&MapRangeInId::ToMax, Data::new(100.0f32)),
+                ],
+                &MapRangeOutId::Out,
+            )
+            .unwrap()
+            .as_f32()
+            .unwrap()
+        };
+        assert_eq!(map_range(false, 0.5), 75.0);
+        assert_eq!(map_range(false, 2.0), 150.0);
+        assert_eq!(map_range(true, 2.0), 100.0);
+    }
 }
@@ -0,0 +1,697 @@
+//! A mini-notation for rhythms, after the one of TidalCycles
+//!
+//! A pattern is a sequence of steps sharing a cycle equally, each step being :
+//! - a word, like `bd` or `60`, the value of the step
+//! - `~`, a rest
+//! - `[a b]`, a subdivision, playing its own sequence in the time of one step
+//! - `<a b>`, an alternation, playing one of its steps each cycle, in turn
+//!
+//! Steps take modifiers, that can be chained like in `[a b]*2?` :
+//! - `a*2` repeats the step twice in its time, `a/2` plays it over twice its time
+//! - `a!3` replicates the step, `a!3` being the same as `a a a`
+//! - `a@3` makes the step last three times longer than the others
+//! - `a?` plays the step half of the times, `a?0.2` dropping it with a chance of `0.2`
+
+use anyhow::{Context, anyhow};
+
+use crate::{
+    Data, LasyFold, Meta, Node, NodeState, ParseError,
+    id::{InId, NodeId, NodeInId, NodeOutId, OutId},
+    random::{get_f64_or, hash, unit},
+};
+
+#[derive(Debug, Clone, PartialEq)]
+enum Term {
+    Word(String),
+    Rest,
+    /// Steps sharing the cycle by weight
+    Sequence(Vec<(Term, f64)>),
+    Alternation(Vec<Term>),
+    Fast(Box<Term>, f64),
+    Slow(Box<Term>, f64),
+    /// A step dropped with a chance, the index telling apart the `?`s of a pattern
+    Degrade(Box<Term>, f64, u64),
+}
+
+/// An occurrence of a word, timed in cycles
+#[derive(Debug, Clone, PartialEq)]
+struct Event<'a> {
+    value: &'a str,
+    start: f64,
+    end: f64,
+}
+
+impl Event<'_> {
+    /// Map the times of an event with `map`
+    fn map(self, map: impl Fn(f64) -> f64) -> Self {
+        Self {
+            start: map(self.start),
+            end: map(self.end),
+            ..self
+        }
+    }
+}
+
+fn is_word_char(char: char) -> bool {
+    char.is_alphanumeric() || matches!(char, '.' | '_' | '-' | '#' | ':' | '\'')
+}
+
+/// How deep terms can be nested, by brackets or modifiers
+const MAX_DEPTH: usize = 64;
+
+/// How many terms a pattern can hold once its copies are expanded
+const MAX_TERMS: usize = 16_384;
+
+/// A recursive descent parser, bounding the nesting and the size of patterns so that a typo
+/// cannot freeze the node
+struct Parser {
+    chars: Vec<char>,
+    at: usize,
+    degrades: u64,
+    /// The count of brackets the parser is in
+    nesting: usize,
+    /// The count of terms parsed so far, copies included
+    terms: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.at).copied()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().is_some_and(char::is_whitespace) {
+            self.at += 1;
+        }
+    }
+
+    /// Count `count` more terms, erroring past [`MAX_TERMS`]
+    fn count_terms(&mut self, count: usize, position: usize) -> Result<(), ParseError> {
+        self.terms = self
+            .terms
+            .checked_add(count)
+            .filter(|terms| *terms <= MAX_TERMS)
+            .ok_or(ParseError::new(
+                position,
+                format!("the pattern has more than {MAX_TERMS} steps"),
+            ))?;
+
+        Ok(())
+    }
+
+    /// Parse steps until `closing`, given with the position of its opening bracket, returning
+    /// them with the depth of the deepest
+    fn sequence(
+        &mut self,
+        closing: Option<(char, usize)>,
+    ) -> Result<(Vec<(Term, f64)>, usize), ParseError> {
+        let mut steps = Vec::new();
+        let mut depth = 0;
+
+        loop {
+            self.skip_whitespace();
+            match (self.peek(), closing) {
+                (None, None) => break,
+                (None, Some((closing, opening))) => {
+                    let open = if closing == ']' { '[' } else { '<' };
+                    return Err(ParseError::new(
+                        opening,
+                        format!("`{open}` is never closed"),
+                    ));
+                }
+                (Some(char), Some((closing, _))) if char == closing => {
+                    self.at += 1;
+                    break;
+                }
+                (Some(char @ (']' | '>')), _) => {
+                    return Err(ParseError::new(self.at, format!("unexpected `{char}`")));
+                }
+                _ => {}
+            }
+
+            let start = self.at;
+            let terms = self.terms;
+            let (mut term, mut term_depth) = self.term()?;
+            let mut weight = 1.0;
+            let mut copies = 1;
+
+            while let Some(modifier @ ('*' | '/' | '!' | '@' | '?')) = self.peek() {
+                let position = self.at;
+                self.at += 1;
+
+                if matches!(modifier, '*' | '/' | '?') {
+                    term_depth += 1;
+                    if term_depth > MAX_DEPTH {
+                        return Err(ParseError::new(
+                            position,
+                            format!("the pattern is nested more than {MAX_DEPTH} levels deep"),
+                        ));
+                    }
+                    self.count_terms(1, position)?;
+                }
+
+                match modifier {
+                    '*' => term = Term::Fast(Box::new(term), self.factor(modifier, position)?),
+                    '/' => term = Term::Slow(Box::new(term), self.factor(modifier, position)?),
+                    '@' => weight = self.factor(modifier, position)?,
+                    '!' => {
+                        copies = match self.number() {
+                            None => 2,
+                            Some(copies) if copies >= 1.0 && copies.fract() == 0.0 => {
+                                copies as usize
+                            }
+                            Some(_) => {
+                                return Err(ParseError::new(
+                                    position + 1,
+                                    "the count of copies should be a whole number",
+                                ));
+                            }
+                        }
+                    }
+                    _ => {
+                        let chance = self.number().unwrap_or(0.5);
+                        if !(0.0..=1.0).contains(&chance) {
+                            return Err(ParseError::new(
+                                position + 1,
+                                "the chance should be between 0 and 1",
+                            ));
+                        }
+                        term = Term::Degrade(Box::new(term), chance, self.degrades);
+                        self.degrades += 1;
+                    }
+                }
+            }
+
+            if self
+                .peek()
+                .is_some_and(|char| !char.is_whitespace() && !"]>".contains(char))
+            {
+                return Err(ParseError::new(
+                    self.at,
+                    format!("unexpected `{}`", self.chars[self.at]),
+                ));
+            }
+            let size = self.terms - terms;
+            self.count_terms(size.saturating_mul(copies - 1), start)?;
+
+            depth = depth.max(term_depth);
+            steps.extend(std::iter::repeat_n((term, weight), copies));
+        }
+
+        Ok((steps, depth))
+    }
+
+    /// Parse a term, returning it with its depth
+    fn term(&mut self) -> Result<(Term, usize), ParseError> {
+        let start = self.at;
+        self.count_terms(1, start)?;
+
+        match self.peek() {
+            Some('~') => {
+                self.at += 1;
+                Ok((Term::Rest, 1))
+            }
+            Some(open @ ('[' | '<')) => {
+                if self.nesting == MAX_DEPTH {
+                    return Err(ParseError::new(
+                        start,
+                        format!("the pattern is nested more than {MAX_DEPTH} levels deep"),
+                    ));
+                }
+
+                self.at += 1;
+                let closing = if open == '[' { ']' } else { '>' };
+                self.nesting += 1;
+                let (steps, depth) = self.sequence(Some((closing, start)))?;
+                self.nesting -= 1;
+
+                if steps.is_empty() {
+                    return Err(ParseError::new(
+                        start,
+                        format!("`{open}{closing}` is empty"),
+                    ));
+                }
+                let term = if open == '[' {
+                    Term::Sequence(steps)
+                } else {
+                    Term::Alternation(steps.into_iter().map(|(term, _)| term).collect())
+                };
+                Ok((term, depth + 1))
+            }
+            Some(char) if is_word_char(char) => {
+                while self.peek().is_some_and(is_word_char) {
+                    self.at += 1;
+                }
+                Ok((Term::Word(self.chars[start..self.at].iter().collect()), 1))
+            }
+            Some(char) => Err(ParseError::new(start, format!("unexpected `{char}`"))),
+            None => Err(ParseError::new(start, "unexpected end of the pattern")),
+        }
+    }
+
+    /// A number, if one follows
+    fn number(&mut self) -> Option<f64> {
+        let start = self.at;
+        while self
+            .peek()
+            .is_some_and(|char| char.is_ascii_digit() || char == '.')
+        {
+            self.at += 1;
+        }
+
+        let number: String = self.chars[start..self.at].iter().collect();
+        let number = number.parse().ok();
+        if number.is_none() {
+            self.at = start;
+        }
+
+        number
+    }
+
+    /// The positive number following a modifier
+    fn factor(&mut self, modifier: char, position: usize) -> Result<f64, ParseError> {
+        let factor = self.number().ok_or(ParseError::new(
+            position + 1,
+            format!("expected a number after `{modifier}`"),
+        ))?;
+
+        if factor > 0.0 && factor.is_finite() {
+            Ok(factor)
+        } else {
+            Err(ParseError::new(
+                position + 1,
+                format!("the number after `{modifier}` should be greater than 0"),
+            ))
+        }
+    }
+}
+
+/// A parsed pattern, querying the word played at a position in cycles
+#[derive(Debug, Clone, PartialEq)]
+struct Notation {
+    root: Term,
+}
+
+impl Notation {
+    fn parse(notation: &str) -> Result<Self, ParseError> {
+        let mut parser = Parser {
+            chars: notation.chars().collect(),
+            at: 0,
+            degrades: 0,
+            nesting: 0,
+            terms: 0,
+        };
+
+        let (steps, _) = parser.sequence(None)?;
+        if steps.is_empty() {
+            return Err(ParseError::new(0, "the pattern is empty"));
+        }
+
+        Ok(Self {
+            root: Term::Sequence(steps),
+        })
+    }
+
+    /// The event playing at `position`, in cycles, `None` during a rest
+    fn query(&self, position: f64, seed: u64) -> Option<Event<'_>> {
+        Self::query_term(&self.root, position, seed)
+    }
+
+    fn query_term(term: &Term, position: f64, seed: u64) -> Option<Event<'_>> {
+        let cycle = position.floor();
+
+        match term {
+            Term::Word(value) => Some(Event {
+                value,
+                start: cycle,
+                end: cycle + 1.0,
+            }),
+            Term::Rest => None,
+            Term::Sequence(steps) => {
+                let total: f64 = steps.iter().map(|(_, weight)| weight).sum();
+                let target = (position - cycle) * total;
+
+                let mut start = 0.0;
+                let (term, start, end) = steps
+                    .iter()
+                    .map(|(term, weight)| {
+                        let step = (term, start, start + weight);
+                        start += weight;
+                        step
+                    })
+                    .find(|(_, _, end)| target < *end)
+                    // Rounding errors may leave the target past the last step
+                    .unwrap_or_else(|| {
+                        let (term, weight) = steps.last().expect("sequences are not empty");
+                        (term, total - weight, total)
+                    });
+
+                let (start, end) = (start / total, end / total);
+                let local = cycle + (position - cycle - start) / (end - start);
+
+                Self::query_term(term, local, seed)
+                    .map(|event| event.map(|time| cycle + start + (time - cycle) * (end - start)))
+            }
+            Term::Alternation(terms) => {
+                let count = terms.len() as f64;
+                let index = cycle.rem_euclid(count) as usize;
+                // Nested alternations move on each time they are played
+                let inner_cycle = (cycle / count).floor();
+
+                Self::query_term(&terms[index], inner_cycle + position - cycle, seed)
+                    .map(|event| event.map(|time| time - inner_cycle + cycle))
+            }
+            Term::Fast(term, factor) => Self::query_term(term, position * factor, seed)
+                .map(|event| event.map(|time| time / factor)),
+            Term::Slow(term, factor) => Self::query_term(term, position / factor, seed)
+                .map(|event| event.map(|time| time * factor)),
+            Term::Degrade(term, chance, index) => {
+                let event = Self::query_term(term, position, seed)?;
+                let key = (event.start * 65_536.0).round() as i64 as u64;
+
+                (unit(hash(hash(seed, *index), key)) >= *chance).then_some(event)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum PatternInId {
+    /// The position in beats, following the time at 120 BPM when not patched
+    Beat,
+    /// How many beats a cycle of the pattern lasts, `4` when not patched
+    Beats,
+}
+
+impl InId for PatternInId {}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum PatternOutId {
+    /// The word of the current event, as a number when it reads as one and as a text otherwise,
+    /// held during rests
+    Value,
+    /// `1` during the first half of each event, `0` otherwise
+    Gate,
+    /// How far the current event is, from `0` at its start to `1` at its end, `0` during rests
+    Phase,
+}
+
+impl OutId for PatternOutId {}
+
+/// Play a pattern written in a mini-notation, see the [module](self) for the notation
+/// ```
+/// # use quakk::pattern::Pattern;
+/// assert!(Pattern::new("bd*2 [sn cp] ~ hh?", 0).is_ok());
+///
+/// let error = Pattern::new("bd [sn cp", 0).unwrap_err();
+/// assert_eq!(error.to_string(), "`[` is never closed at position 3");
+/// ```
+#[derive(Debug)]
+pub struct Pattern {
+    source: String,
+    notation: Notation,
+    seed: u64,
+    /// The value of the last event played
+    value: NodeState<Data>,
+}
+
+impl Pattern {
+    /// Parse a pattern, the seed deciding which steps marked with `?` are played
+    pub fn new(source: &str, seed: u64) -> Result<Self, ParseError> {
+        Ok(Self {
+            source: source.to_string(),
+            notation: Notation::parse(source)?,
+            seed,
+            value: NodeState::new(Data::new(0.0f32)),
+        })
+    }
+
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// The position in cycles at this tick
+    fn position(&self, lasy_fold: &LasyFold, meta: Meta) -> anyhow::Result<f64> {
+        let beat = if lasy_fold.is_patched(&PatternInId::Beat) {
+            lasy_fold
+                .get_in(&PatternInId::Beat, meta)?
+                .to_f64()
+                .context("invalid Beat")?
+        } else {
+            meta.time() * 2.0
+        };
+        let beats = get_f64_or(lasy_fold, &PatternInId::Beats, meta, 4.0)?;
+
+        if beats > 0.0 && beats.is_finite() {
+            Ok(beat / beats)
+        } else {
+            Err(anyhow!(
+                "a cycle should last more than 0 beats, found {beats}"
+            ))
+        }
+    }
+}
+
+impl Default for Pattern {
+    fn default() -> Self {
+        Self::new("~", 0).expect("the default pattern should be valid")
+    }
+}
+
+impl Node for Pattern {
+    fn initialize() -> Self {
+        Self::default()
+    }
+
+    fn title(&self) -> &str {
+        "Pattern"
+    }
+
+    fn fold(&self, out_id: &dyn OutId, lasy_fold: LasyFold, meta: Meta) -> anyhow::Result<Data> {
+        let Some(out_id) = out_id.as_any().downcast_ref::<PatternOutId>() else {
+            return Err(anyhow!("not a valid out_id"));
+        };
+
+        let position = self.position(&lasy_fold, meta)?;
+        let event = self.notation.query(position, self.seed);
+        let phase = event
+            .as_ref()
+            .map(|event| (position - event.start) / (event.end - event.start))
+            .unwrap_or(0.0);
+
+        let data = match out_id {
+            PatternOutId::Value => self.value.step(meta, |value| {
+                if let Some(event) = &event {
+                    *value = match event.value.parse::<f32>() {
+                        Ok(number) => Data::new(number),
+                        Err(_) => Data::new(event.value.to_string()),
+                    };
+                }
+                Ok(value.clone())
+            })?,
+            PatternOutId::Gate => Data::new(if event.is_some() && phase < 0.5 {
+                1.0f32
+            } else {
+                0.0
+            }),
+            PatternOutId::Phase => Data::new(phase as f32),
+        };
+
+        Ok(data)
+    }
+
+    fn node_in_id(&self, in_id: &dyn InId, node_id: NodeId) -> Option<NodeInId> {
+        in_id
+            .as_any()
+            .downcast_ref::<PatternInId>()
+            .map(|in_id| NodeInId::new(node_id, in_id))
+    }
+
+    fn node_out_id(&self, out_id: &dyn OutId, node_id: NodeId) -> Option<NodeOutId> {
+        out_id
+            .as_any()
+            .downcast_ref::<PatternOutId>()
+            .map(|out_id| NodeOutId::new(node_id, out_id))
+    }
+
+    fn in_ids(&self) -> Vec<Box<dyn InId>> {
+        vec![Box::new(PatternInId::Beat), Box::new(PatternInId::Beats)]
+    }
+
+    fn out_ids(&self) -> Vec<Box<dyn OutId>> {
+        vec![
+            Box::new(PatternOutId::Value),
+            Box::new(PatternOutId::Gate),
+            Box::new(PatternOutId::Phase),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{fold_node, fold_node_over};
+
+    /// The word played at each of `positions`, in cycles
+    fn play(notation: &str, positions: &[f64]) -> Vec<Option<String>> {
+        let notation = Notation::parse(notation).unwrap();
+        positions
+            .iter()
+            .map(|position| {
+                notation
+                    .query(*position, 0)
+                    .map(|event| event.value.to_string())
+            })
+            .collect()
+    }
+
+    fn words(words: &[&str]) -> Vec<Option<String>> {
+        words
+            .iter()
+            .map(|word| (*word != "~").then(|| word.to_string()))
+            .collect()
+    }
+
+    fn error(notation: &str) -> String {
+        Notation::parse(notation).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn sequences_and_subdivisions() {
+        let eighths: Vec<f64> = (0..8).map(|eighth| eighth as f64 / 8.0).collect();
+
+        assert_eq!(
+            play("bd*2 [sn cp] ~ hh", &eighths),
+            words(&["bd", "bd", "sn", "cp", "~", "~", "hh", "hh"])
+        );
+        assert_eq!(
+            play("a!3 b", &eighths),
+            words(&["a", "a", "a", "a", "a", "a", "b", "b"])
+        );
+        assert_eq!(
+            play("a@3 b", &eighths),
+            words(&["a", "a", "a", "a", "a", "a", "b", "b"])
+        );
+
+        let notation = Notation::parse("~ [a b]").unwrap();
+        let event = notation.query(1.8, 0).unwrap();
+        assert_eq!((event.value, event.start, event.end), ("b", 1.75, 2.0));
+    }
+
+    #[test]
+    fn alternations_and_slow_steps() {
+        let cycles = [0.5, 1.5, 2.5, 3.5];
+
+        assert_eq!(play("<a b c>", &cycles), words(&["a", "b", "c", "a"]));
+        assert_eq!(play("<a <b c>>", &cycles), words(&["a", "b", "a", "c"]));
+        assert_eq!(play("<a b>*2", &[0.25, 0.75]), words(&["a", "b"]));
+        assert_eq!(play("[a b]/2", &cycles), words(&["a", "b", "a", "b"]));
+
+        let notation = Notation::parse("a/2").unwrap();
+        let event = notation.query(1.5, 0).unwrap();
+        assert_eq!((event.start, event.end), (0.0, 2.0));
+    }
+
+    #[test]
+    fn probabilities_are_replayable() {
+        let notation = Notation::parse("a?").unwrap();
+        let played: Vec<bool> = (0..200)
+            .map(|cycle| notation.query(cycle as f64, 7).is_some())
+            .collect();
+
+        let count = played.iter().filter(|played| **played).count();
+        assert!((60..140).contains(&count));
+        assert!((0..200).all(|cycle| notation.query(cycle as f64, 7).is_some() == played[cycle]));
+
+        assert_eq!(play("a?0", &[0.0, 1.0, 2.0]), words(&["a", "a", "a"]));
+        assert_eq!(play("a?1", &[0.0, 1.0, 2.0]), words(&["~", "~", "~"]));
+    }
+
+    #[test]
+    fn errors_point_at_the_failing_character() {
+        assert_eq!(error("bd [sn cp"), "`[` is never closed at position 3");
+        assert_eq!(error("bd <sn"), "`<` is never closed at position 3");
+        assert_eq!(error("bd ] sn"), "unexpected `]` at position 3");
+        assert_eq!(error("bd*"), "expected a number after `*` at position 3");
+        assert_eq!(
+            error("bd/0"),
+            "the number after `/` should be greater than 0 at position 3"
+        );
+        assert_eq!(error("bd $"), "unexpected `$` at position 3");
+        assert_eq!(error("bd~"), "unexpected `~` at position 2");
+        assert_eq!(error("a []"), "`[]` is empty at position 2");
+        assert_eq!(
+            error("a?2"),
+            "the chance should be between 0 and 1 at position 2"
+        );
+        assert_eq!(error("  "), "the pattern is empty at position 0");
+        // Positions count characters, not bytes
+        assert_eq!(error("é ["), "`[` is never closed at position 2");
+    }
+
+    #[test]
+    fn patterns_are_bounded() {
+        assert!(Notation::parse("[a b!100]!100").is_ok());
+        assert_eq!(
+            error("[[[a!1024]!1024]!64]"),
+            "the pattern has more than 16384 steps at position 2"
+        );
+        assert_eq!(
+            error("a!100000000000000000000"),
+            "the pattern has more than 16384 steps at position 0"
+        );
+
+        let nested = format!("{}a{}", "[".repeat(100_000), "]".repeat(100_000));
+        assert_eq!(
+            error(&nested),
+            "the pattern is nested more than 64 levels deep at position 64"
+        );
+        assert_eq!(
+            error(&format!("a{}", "*2".repeat(100_000))),
+            "the pattern is nested more than 64 levels deep at position 127"
+        );
+    }
+
+    #[test]
+    fn node_outputs() {
+        let fold = |out_id: PatternOutId, beat: f32| {
+            fold_node(
+                Pattern::new("60 [62 ~]", 0).unwrap(),
+                vec![
+                    (&PatternInId::Beat, Data::new(beat)),
+                    (&PatternInId::Beats, Data::new(1.0f32)),
+                ],
+                &out_id,
+            )
+            .unwrap()
+        };
+
+        assert_eq!(fold(PatternOutId::Value, 0.6).as_f32().unwrap(), 62.0);
+        assert_eq!(fold(PatternOutId::Gate, 0.1).as_f32().unwrap(), 1.0);
+        assert_eq!(fold(PatternOutId::Gate, 0.3).as_f32().unwrap(), 0.0);
+        assert_eq!(fold(PatternOutId::Phase, 0.125).as_f32().unwrap(), 0.25);
+        assert_eq!(fold(PatternOutId::Gate, 0.8).as_f32().unwrap(), 0.0);
+
+        let text = fold_node(
+            Pattern::new("bd", 0).unwrap(),
+            vec![(&PatternInId::Beat, Data::new(0.0f32))],
+            &PatternOutId::Value,
+        )
+        .unwrap();
+        assert_eq!(text.as_text().unwrap(), "bd");
+    }
+
+    #[test]
+    fn value_holds_during_rests() {
+        // At 120 BPM and 60 ticks per second, a cycle of 4 beats lasts 120 ticks
+        let values = fold_node_over(
+            Pattern::new("60 ~", 0).unwrap(),
+            vec![],
+            &PatternOutId::Value,
+            [0, 59, 60, 119, 120],
+        )
+        .unwrap();
+
+        assert!(values.iter().all(|value| value.as_f32().unwrap() == 60.0));
+    }
+}
@@ -0,0 +1,691 @@
+//! Deterministic random and noise nodes
+//!
+//! Every node is given a seed when created, and derives its output from that seed and the
+//! [`Meta::tick`] only : folding the same graph with the same ticks always gives the same
+//! values, allowing a whole performance to be replayed exactly.
+//!
+//! The hashing used is fixed and documented, it does not depend on the standard library's
+//! hasher which may change between releases.
+
+use anyhow::{Context, anyhow};
+
+use crate::{
+    Data, DataList, LasyFold, Meta, Node,
+    id::{InId, NodeId, NodeInId, NodeOutId, OutId},
+};
+
+const GOLDEN_GAMMA: u64 = 0x9e37_79b9_7f4a_7c15;
+
+/// The SplitMix64 finalizer, a fast bijective mixing of the bits of `value`
+fn mix(mut value: u64) -> u64 {
+    value = (value ^ (value >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    value = (value ^ (value >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    value ^ (value >> 31)
+}
+
+/// Hash a value with a seed, the same inputs always give the same output
+pub(crate) fn hash(seed: u64, value: u64) -> u64 {
+    mix(mix(seed.wrapping_add(GOLDEN_GAMMA)) ^ value.wrapping_mul(GOLDEN_GAMMA))
+}
+
+/// Hash two coordinates with a seed
+fn hash2(seed: u64, x: i64, y: i64) -> u64 {
+    hash(hash(seed, x as u64), y as u64)
+}
+
+/// Map a hash to a float uniformly distributed in `[0, 1)`
+pub(crate) fn unit(hash: u64) -> f64 {
+    (hash >> 11) as f64 / (1u64 << 53) as f64
+}
+
+/// Fold an optional input of the node as an `f64`, or return `default` when it is not patched
+pub(crate) fn get_f64_or(
+    lasy_fold: &LasyFold,
+    in_id: &dyn InId,
+    meta: Meta,
+    default: f64,
+) -> anyhow::Result<f64> {
+    if !lasy_fold.is_patched(in_id) {
+        return Ok(default);
+    }
+
+    lasy_fold
+        .get_in(in_id, meta)?
+        .to_f64()
+        .with_context(|| format!("invalid {}", in_id.name()))
+}
+
+/// A random number between `Min` (inclusive, default `0`) and `Max` (exclusive, default `1`),
+/// changing every tick
+#[derive(Debug, Default)]
+pub struct RandomValue {
+    seed: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum RandomValueInId {
+    Min,
+    Max,
+}
+
+impl InId for RandomValueInId {}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum RandomValueOutId {
+    Out,
+}
+
+impl OutId for RandomValueOutId {}
+
+impl RandomValue {
+    pub fn new(seed: u64) -> Self {
+        Self { seed }
+    }
+}
+
+impl Node for RandomValue {
+    fn initialize() -> Self {
+        Self::default()
+    }
+
+    fn title(&self) -> &str {
+        "Random Value"
+    }
+
+    fn fold(&self, _out_id: &dyn OutId, lasy_fold: LasyFold, meta: Meta) -> anyhow::Result<Data> {
+        let min = get_f64_or(&lasy_fold, &RandomValueInId::Min, meta, 0.0)?;
+        let max = get_f64_or(&lasy_fold, &RandomValueInId::Max, meta, 1.0)?;
+
+        let factor = unit(hash(self.seed, meta.tick));
+        Ok(Data::new((min + (max - min) * factor) as f32))
+    }
+
+    fn node_in_id(&self, in_id: &dyn InId, node_id: NodeId) -> Option<NodeInId> {
+        in_id
+            .as_any()
+            .downcast_ref::<RandomValueInId>()
+            .map(|in_id| NodeInId::new(node_id, in_id))
+    }
+
+    fn node_out_id(&self, out_id: &dyn OutId, node_id: NodeId) -> Option<NodeOutId> {
+        out_id
+            .as_any()
+            .downcast_ref::<RandomValueOutId>()
+            .map(|out_id| NodeOutId::new(node_id, out_id))
+    }
+
+    fn in_ids(&self) -> Vec<Box<dyn InId>> {
+        vec![
+            Box::new(RandomValueInId::Min),
+            Box::new(RandomValueInId::Max),
+        ]
+    }
+
+    fn out_ids(&self) -> Vec<Box<dyn OutId>> {
+        vec![Box::new(RandomValueOutId::Out)]
+    }
+}
+
+/// A random integer between `Min` and `Max`, both inclusive, changing every tick
+#[derive(Debug, Default)]
+pub struct RandomInteger {
+    seed: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum RandomIntegerInId {
+    Min,
+    Max,
+}
+
+impl InId for RandomIntegerInId {}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum RandomIntegerOutId {
+    Out,
+}
+
+impl OutId for RandomIntegerOutId {}
+
+impl RandomInteger {
+    pub fn new(seed: u64) -> Self {
+        Self { seed }
+    }
+}
+
+impl Node for RandomInteger {
+    fn initialize() -> Self {
+        Self::default()
+    }
+
+    fn title(&self) -> &str {
+        "Random Integer"
+    }
+
+    fn fold(&self, _out_id: &dyn OutId, lasy_fold: LasyFold, meta: Meta) -> anyhow::Result<Data> {
+        let min = lasy_fold
+            .get_in(&RandomIntegerInId::Min, meta)?
+            .to_i64()
+            .context("invalid Min")?;
+        let max = lasy_fold
+            .get_in(&RandomIntegerInId::Max, meta)?
+            .to_i64()
+            .context("invalid Max")?;
+
+        let (min, max) = (min.min(max), min.max(max));
+        let span = (max as i128 - min as i128 + 1) as u128;
+
+        // Multiply-shift reduction, avoiding the bias of a modulo
+        let offset = (hash(self.seed, meta.tick) as u128 * span) >> 64;
+        Ok(Data::new((min as i128 + offset as i128) as i64))
+    }
+
+    fn node_in_id(&self, in_id: &dyn InId, node_id: NodeId) -> Option<NodeInId> {
+        in_id
+            .as_any()
+            .downcast_ref::<RandomIntegerInId>()
+            .map(|in_id| NodeInId::new(node_id, in_id))
+    }
+
+    fn node_out_id(&self, out_id: &dyn OutId, node_id: NodeId) -> Option<NodeOutId> {
+        out_id
+            .as_any()
+            .downcast_ref::<RandomIntegerOutId>()
+            .map(|out_id| NodeOutId::new(node_id, out_id))
+    }
+
+    fn in_ids(&self) -> Vec<Box<dyn InId>> {
+        vec![
+            Box::new(RandomIntegerInId::Min),
+            Box::new(RandomIntegerInId::Max),
+        ]
+    }
+
+    fn out_ids(&self) -> Vec<Box<dyn OutId>> {
+        vec![Box::new(RandomIntegerOutId::Out)]
+    }
+}
+
+/// Randomly choose an index from a list of `Weights`, each index being chosen with a probability
+/// proportional to its weight, changing every tick
+///
+/// If `Choices` is patched, `Choice` outputs its element at the chosen index
+#[derive(Debug, Default)]
+pub struct WeightedChoice {
+    seed: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum WeightedChoiceInId {
+    Weights,
+    Choices,
+}
+
+impl InId for WeightedChoiceInId {}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum WeightedChoiceOutId {
+    Index,
+    Choice,
+}
+
+impl OutId for WeightedChoiceOutId {}
+
+impl WeightedChoice {
+    pub fn new(seed: u64) -> Self {
+        Self { seed }
+    }
+
+    fn choose(&self, weights: &[Data], tick: u64) -> anyhow::Result<usize> {
+        let weights = weights
+            .iter()
+            .enumerate()
+            .map(|(index, weight)| {
+                let weight = weight
+                    .to_f64()
+                    .with_context(|| format!("invalid weight at index {index}"))?;
+
+                if weight.is_finite() && weight >= 0.0 {
+                    Ok(weight)
+                } else {
+                    Err(anyhow!(
+                        "the weight at index {index} should be a positive number, found {weight}"
+                    ))
+                }
+            })
+            .collect::<anyhow::Result<Vec<f64>>>()?;
+
+        let total: f64 = weights.iter().sum();
+        if total <= 0.0 {
+            return Err(anyhow!("the weights should not all be zero"));
+        }
+
+        let mut target = unit(hash(self.seed, tick)) * total;
+        for (index, weight) in weights.iter().enumerate() {
+            if target < *weight {
+                return Ok(index);
+            }
+            target -= weight;
+        }
+
+        // Rounding errors may leave a tiny remainder, fall back to the last weighted index
+        Ok(weights
+            .iter()
+            .rposition(|weight| *weight > 0.0)
+            .unwrap_or_default())
+    }
+}
+
+impl Node for WeightedChoice {
+    fn initialize() -> Self {
+        Self::default()
+    }
+
+    fn title(&self) -> &str {
+        "Weighted Choice"
+    }
+
+    fn fold(&self, out_id: &dyn OutId, lasy_fold: LasyFold, meta: Meta) -> anyhow::Result<Data> {
+        let weights = lasy_fold.get_in(&WeightedChoiceInId::Weights, meta)?;
+        let weights = weights.as_list().context("invalid Weights")?;
+        let index = self.choose(weights, meta.tick)?;
+
+        match out_id.as_any().downcast_ref::<WeightedChoiceOutId>() {
+            Some(WeightedChoiceOutId::Index) => Ok(Data::new(index as i64)),
+            Some(WeightedChoiceOutId::Choice) => {
+                let choices = lasy_fold.get_in(&WeightedChoiceInId::Choices, meta)?;
+                let choices: &[Data] = choices.as_list().context("invalid Choices")?;
+
+                choices.get(index).cloned().ok_or(anyhow!(
+                    "there is no choice at index {index}, only {} choices for {} weights",
+                    choices.len(),
+                    weights.len()
+                ))
+            }
+            None => Err(anyhow!("not a valid out_id")),
+        }
+    }
+
+    fn node_in_id(&self, in_id: &dyn InId, node_id: NodeId) -> Option<NodeInId> {
+        in_id
+            .as_any()
+            .downcast_ref::<WeightedChoiceInId>()
+            .map(|in_id| NodeInId::new(node_id, in_id))
+    }
+
+    fn node_out_id(&self, out_id: &dyn OutId, node_id: NodeId) -> Option<NodeOutId> {
+        out_id
+            .as_any()
+            .downcast_ref::<WeightedChoiceOutId>()
+            .map(|out_id| NodeOutId::new(node_id, out_id))
+    }
+
+    fn in_ids(&self) -> Vec<Box<dyn InId>> {
+        vec![
+            Box::new(WeightedChoiceInId::Weights),
+            Box::new(WeightedChoiceInId::Choices),
+        ]
+    }
+
+    fn out_ids(&self) -> Vec<Box<dyn OutId>> {
+        vec![
+            Box::new(WeightedChoiceOutId::Index),
+            Box::new(WeightedChoiceOutId::Choice),
+        ]
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+pub enum NoiseKind {
+    /// Random values at integer coordinates, smoothly interpolated
+    Value,
+    /// Random gradients at integer coordinates
+    #[default]
+    Perlin,
+    /// Random gradients on a triangular grid, with fewer directional artifacts than Perlin
+    Simplex,
+}
+
+/// Smooth two dimensional noise, between `-1` and `1`
+///
+/// The noise is sampled at `(X, Y) * Frequency`, `X` defaults to the tick, `Y` to `0` and
+/// `Frequency` to `1`
+#[derive(Debug, Default)]
+pub struct Noise {
+    kind: NoiseKind,
+    seed: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum NoiseInId {
+    X,
+    Y,
+    Frequency,
+}
+
+impl InId for NoiseInId {}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum NoiseOutId {
+    Out,
+}
+
+impl OutId for NoiseOutId {}
+
+/// The quintic fade curve used by Perlin noise, with zero first and second derivatives at 0 and 1
+fn fade(t: f64) -> f64 {
+    t * t * t * (t * (t * 6.0 - 15.0) + 10.0)
+}
+
+fn lerp(from: f64, to: f64, factor: f64) -> f64 {
+    from + (to - from) * factor
+}
+
+/// The dot product of a pseudo random gradient and `(x, y)`
+fn gradient(hash: u64, x: f64, y: f64) -> f64 {
+    match hash & 7 {
+        0 => x + y,
+        1 => x - y,
+        2 => -x + y,
+        3 => -x - y,
+        4 => x,
+        5 => -x,
+        6 => y,
+        _ => -y,
+    }
+}
+
+impl Noise {
+    pub fn new(kind: NoiseKind, seed: u64) -> Self {
+        Self { kind, seed }
+    }
+
+    /// Sample the noise at the given coordinates
+    pub fn sample(&self, x: f64, y: f64) -> f64 {
+        match self.kind {
+            NoiseKind::Value => self.value(x, y),
+            NoiseKind::Perlin => self.perlin(x, y),
+            NoiseKind::Simplex => self.simplex(x, y),
+        }
+    }
+
+    fn value(&self, x: f64, y: f64) -> f64 {
+        let (x0, y0) = (x.floor(), y.floor());
+        let (ix, iy) = (x0 as i64, y0 as i64);
+        let (tx, ty) = (fade(x - x0), fade(y - y0));
+
+        let corner = |dx: i64, dy: i64| {
+            unit(hash2(self.seed, ix.wrapping_add(dx), iy.wrapping_add(dy))) * 2.0 - 1.0
+        };
+
+        lerp(
+            lerp(corner(0, 0), corner(1, 0), tx),
+            lerp(corner(0, 1), corner(1, 1), tx),
+            ty,
+        )
+    }
+
+    fn perlin(&self, x: f64, y: f64) -> f64 {
+        let (x0, y0) = (x.floor(), y.floor());
+        let (ix, iy) = (x0 as i64, y0 as i64);
+        let (fx, fy) = (x - x0, y - y0);
+
+        let corner = |dx: i64, dy: i64| {
+            gradient(
+                hash2(self.seed, ix.wrapping_add(dx), iy.wrapping_add(dy)),
+                fx - dx as f64,
+                fy - dy as f64,
+            )
+        };
+
+        let (tx, ty) = (fade(fx), fade(fy));
+        let value = lerp(
+            lerp(corner(0, 0), corner(1, 0), tx),
+            lerp(corner(0, 1), corner(1, 1), tx),
+            ty,
+        );
+
+        // The gradients reach at most `sqrt(2) / 2` in magnitude, scale to `[-1, 1]`
+        (value * std::f64::consts::SQRT_2).clamp(-1.0, 1.0)
+    }
+
+    fn simplex(&self, x: f64, y: f64) -> f64 {
+        const F2: f64 = 0.366_025_403_784_438_6; // (sqrt(3) - 1) / 2
+        const G2: f64 = 0.211_324_865_405_187_1; // (3 - sqrt(3)) / 6
+
+        // Skew the input space to find the simplex cell
+        let skew = (x + y) * F2;
+        let (i, j) = ((x + skew).floor(), (y + skew).floor());
+        let unskew = (i + j) * G2;
+        let (x0, y0) = (x - (i - unskew), y - (j - unskew));
+
+        // Find in which of the two triangles of the cell the point is
+        let (i1, j1) = if x0 > y0 { (1, 0) } else { (0, 1) };
+
+        let (x1, y1) = (x0 - i1 as f64 + G2, y0 - j1 as f64 + G2);
+        let (x2, y2) = (x0 - 1.0 + 2.0 * G2, y0 - 1.0 + 2.0 * G2);
+
+        let (ii, jj) = (i as i64, j as i64);
+        let corner = |dx: i64, dy: i64, x: f64, y: f64| {
+            let t = 0.5 - x * x - y * y;
+            if t < 0.0 {
+                0.0
+            } else {
+                t.powi(4)
+                    * gradient(
+                        hash2(self.seed, ii.wrapping_add(dx), jj.wrapping_add(dy)),
+                        x,
+                        y,
+                    )
+            }
+        };
+
+        let value = corner(0, 0, x0, y0) + corner(i1, j1, x1, y1) + corner(1, 1, x2, y2);
+
+        // Scale to roughly fit `[-1, 1]`
+        (value * 70.0).clamp(-1.0, 1.0)
+    }
+}
+
+impl Node for Noise {
+    fn initialize() -> Self {
+        Self::default()
+    }
+
+    fn title(&self) -> &str {
+        "Noise"
+    }
+
+    fn fold(&self, _out_id: &dyn OutId, lasy_fold: LasyFold, meta: Meta) -> anyhow::Result<Data> {
+        let x = get_f64_or(&lasy_fold, &NoiseInId::X, meta, meta.tick as f64)?;
+        let y = get_f64_or(&lasy_fold, &NoiseInId::Y, meta, 0.0)?;
+        let frequency = get_f64_or(&lasy_fold, &NoiseInId::Frequency, meta, 1.0)?;
+
+        Ok(Data::new(self.sample(x * frequency, y * frequency) as f32))
+    }
+
+    fn node_in_id(&self, in_id: &dyn InId, node_id: NodeId) -> Option<NodeInId> {
+        in_id
+            .as_any()
+            .downcast_ref::<NoiseInId>()
+            .map(|in_id| NodeInId::new(node_id, in_id))
+    }
+
+    fn node_out_id(&self, out_id: &dyn OutId, node_id: NodeId) -> Option<NodeOutId> {
+        out_id
+            .as_any()
+            .downcast_ref::<NoiseOutId>()
+            .map(|out_id| NodeOutId::new(node_id, out_id))
+    }
+
+    fn in_ids(&self) -> Vec<Box<dyn InId>> {
+        vec![
+            Box::new(NoiseInId::X),
+            Box::new(NoiseInId::Y),
+            Box::new(NoiseInId::Frequency),
+        ]
+    }
+
+    fn out_ids(&self) -> Vec<Box<dyn OutId>> {
+        vec![Box::new(NoiseOutId::Out)]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{fold_node_at, meta_at};
+
+    fn random_value(seed: u64, tick: u64) -> f32 {
+        fold_node_at(
+            RandomValue::new(seed),
+            vec![
+                (&RandomValueInId::Min, Data::new(-2.0f32)),
+                (&RandomValueInId::Max, Data::new(2.0f32)),
+            ],
+            &RandomValueOutId::Out,
+            meta_at(tick),
+        )
+        .unwrap()
+        .as_f32()
+        .unwrap()
+    }
+
+    #[test]
+    fn random_values_are_replayable() {
+        let values: Vec<f32> = (0..100).map(|tick| random_value(7, tick)).collect();
+
+        assert_eq!(
+            values,
+            (0..100)
+                .map(|tick| random_value(7, tick))
+                .collect::<Vec<_>>()
+        );
+        assert_ne!(values[0], values[1]);
+        assert_ne!(random_value(7, 0), random_value(8, 0));
+        assert!(values.iter().all(|value| (-2.0..2.0).contains(value)));
+    }
+
+    #[test]
+    fn random_integers_cover_their_inclusive_range() {
+        let integers: Vec<i64> = (0..200)
+            .map(|tick| {
+                fold_node_at(
+                    RandomInteger::new(1),
+                    vec![
+                        (&RandomIntegerInId::Min, Data::new(3i64)),
+                        (&RandomIntegerInId::Max, Data::new(1i64)),
+                    ],
+                    &RandomIntegerOutId::Out,
+                    meta_at(tick),
+                )
+                .unwrap()
+                .as_i64()
+                .unwrap()
+            })
+            .collect();
+
+        assert!(integers.iter().all(|value| (1..=3).contains(value)));
+        for expected in 1..=3 {
+            assert!(integers.contains(&expected));
+        }
+    }
+
+    #[test]
+    fn weighted_choice_follows_weights() {
+        let choose = |weights: Vec<f32>, tick: u64| {
+            let weights: DataList = weights.into_iter().map(Data::new).collect();
+            let choices: DataList = ["a", "b", "c"]
+                .into_iter()
+                .map(|choice| Data::new(choice.to_string()))
+                .collect();
+
+            fold_node_at(
+                WeightedChoice::new(3),
+                vec![
+                    (&WeightedChoiceInId::Weights, Data::new(weights)),
+                    (&WeightedChoiceInId::Choices, Data::new(choices)),
+                ],
+                &WeightedChoiceOutId::Choice,
+                meta_at(tick),
+            )
+        };
+
+        for tick in 0..50 {
+            let choice = choose(vec![0.0, 1.0, 0.0], tick).unwrap();
+            assert_eq!(choice.as_text().unwrap(), "b");
+        }
+
+        let mostly_c = (0..1000)
+            .filter(|tick| {
+                choose(vec![1.0, 1.0, 8.0], *tick)
+                    .unwrap()
+                    .as_text()
+                    .unwrap()
+                    == "c"
+            })
+            .count();
+        assert!((700..900).contains(&mostly_c));
+
+        assert!(choose(vec![0.0, 0.0, 0.0], 0).is_err());
+        assert!(choose(vec![1.0, -1.0, 0.0], 0).is_err());
+    }
+
+    #[test]
+    fn noise_is_smooth_and_bounded() {
+        for kind in [NoiseKind::Value, NoiseKind::Perlin, NoiseKind::Simplex] {
+            let noise = Noise::new(kind, 42);
+
+            let mut previous = noise.sample(0.0, 0.5);
+            for step in 1..2000 {
+                let value = noise.sample(step as f64 * 0.01, 0.5);
+                assert!((-1.0..=1.0).contains(&value), "{kind:?} out of range");
+                assert!((value - previous).abs() < 0.1, "{kind:?} is not smooth");
+                previous = value;
+            }
+
+            assert_eq!(
+                noise.sample(1.3, 2.7),
+                Noise::new(kind, 42).sample(1.3, 2.7)
+            );
+            assert_ne!(
+                noise.sample(1.3, 2.7),
+                Noise::new(kind, 43).sample(1.3, 2.7)
+            );
+        }
+
+        // Perlin noise is zero on integer coordinates
+        assert_eq!(Noise::new(NoiseKind::Perlin, 1).sample(3.0, -2.0), 0.0);
+    }
+
+    #[test]
+    fn noise_takes_huge_coordinates() {
+        for kind in [NoiseKind::Value, NoiseKind::Perlin, NoiseKind::Simplex] {
+            let noise = Noise::new(kind, 42);
+
+            // The cells past the last integer wrap around instead of overflowing
+            let value = noise.sample(f64::MAX, -f64::MAX);
+            assert!((-1.0..=1.0).contains(&value), "{kind:?} out of range");
+            noise.sample(f64::INFINITY, f64::NEG_INFINITY);
+            noise.sample(f64::NAN, 0.0);
+        }
+    }
+
+    #[test]
+    fn noise_follows_ticks() {
+        let sample = |tick: u64| {
+            fold_node_at(
+                Noise::new(NoiseKind::Simplex, 5),
+                vec![(&NoiseInId::Frequency, Data::new(0.05f32))],
+                &NoiseOutId::Out,
+                meta_at(tick),
+            )
+            .unwrap()
+            .as_f32()
+            .unwrap()
+        };
+
+        assert_eq!(sample(10), sample(10));
+        assert_ne!(sample(10), sample(30));
+    }
+}
@@ -0,0 +1,359 @@
+//! Step sequencing, playing patterns of steps in time with a tempo
+
+use anyhow::{Context, anyhow};
+
+use crate::{
+    Data, LasyFold, Meta, Node, NodeState, Progress,
+    id::{InId, NodeId, NodeInId, NodeOutId, OutId},
+    random::{get_f64_or, hash, unit},
+};
+
+/// A step of a [`StepSequencer`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Step {
+    /// The value held for the whole step, like a pitch or a cutoff
+    pub value: f64,
+    /// Whether the step opens the gate, a rest otherwise
+    pub gate: bool,
+    /// The chance the gate opens each time the step is played, between `0` and `1`
+    pub probability: f64,
+    /// How many times the gate opens during the step, at least `1`
+    pub ratchet: u32,
+}
+
+impl Step {
+    /// A step opening the gate once, every time
+    pub fn new(value: f64) -> Self {
+        Self {
+            value,
+            gate: true,
+            probability: 1.0,
+            ratchet: 1,
+        }
+    }
+
+    /// A step keeping the gate closed, holding `value`
+    pub fn rest(value: f64) -> Self {
+        Self {
+            gate: false,
+            ..Self::new(value)
+        }
+    }
+}
+
+impl Default for Step {
+    fn default() -> Self {
+        Self::new(0.0)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum StepSequencerInId {
+    /// The tempo in beats per minute, `120` when not patched
+    Bpm,
+    /// The count of beats, like the `Beat` output of a [`Clock`](crate::clock::Clock), the
+    /// sequencer following it instead of `Bpm` when patched
+    Beat,
+    /// How many steps are played each beat, `4` when not patched
+    Division,
+    /// How many steps of the pattern are played before looping, every step when not patched
+    Length,
+    /// The part of each gate, or of each ratchet, during which the gate is open, between `0`
+    /// and `1`, `0.5` when not patched
+    GateLength,
+}
+
+impl InId for StepSequencerInId {}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum StepSequencerOutId {
+    /// The value of the current step
+    Value,
+    /// `1` while the gate of the current step is open, `0` otherwise
+    Gate,
+    /// The index of the current step, from `0`
+    Step,
+}
+
+impl OutId for StepSequencerOutId {}
+
+/// Play a pattern of [`Step`]s, advancing by `Division` steps each beat
+///
+/// The position is a [`Progress`], moving forward by the ticks elapsed at the current tempo and
+/// division. With `Beat` patched, the position is instead read from the beat, locking several
+/// nodes to the same clock. Probabilities are drawn from the seed of the node and the count of
+/// steps played, so a performance replays exactly. Gates are computed once per tick, ratchets
+/// faster than [`Meta::tick_rate`] are not heard.
+#[derive(Debug, Default)]
+pub struct StepSequencer {
+    steps: Vec<Step>,
+    seed: u64,
+    /// The count of steps played since the first tick, with the part of the current step
+    position: NodeState<Progress>,
+}
+
+impl StepSequencer {
+    pub fn new(steps: Vec<Step>, seed: u64) -> Self {
+        Self {
+            steps,
+            seed,
+            position: NodeState::default(),
+        }
+    }
+
+    pub fn steps(&self) -> &[Step] {
+        &self.steps
+    }
+
+    /// The position of this tick, in steps
+    fn position(&self, lasy_fold: &LasyFold, meta: Meta) -> anyhow::Result<f64> {
+        if lasy_fold.is_patched(&StepSequencerInId::Beat) {
+            let beat = lasy_fold
+                .get_in(&StepSequencerInId::Beat, meta)?
+                .to_f64()
+                .context("invalid Beat")?;
+            let division = get_f64_or(lasy_fold, &StepSequencerInId::Division, meta, 4.0)?;
+
+            return Ok(beat * division);
+        }
+
+        let position = self.position.step(meta, |progress| {
+            let bpm = get_f64_or(lasy_fold, &StepSequencerInId::Bpm, meta, 120.0)?;
+            let division = get_f64_or(lasy_fold, &StepSequencerInId::Division, meta, 4.0)?;
+
+            let steps_per_tick = bpm / 60.0 * division / meta.tick_rate;
+            if !(steps_per_tick.is_finite() && steps_per_tick >= 0.0) {
+                return Err(anyhow!(
+                    "the tempo and division should be positive, found {bpm} BPM and {division} steps per beat"
+                ));
+            }
+
+            Ok(Data::new(progress.advance(meta.tick, steps_per_tick)))
+        })?;
+
+        position.as_f64()
+    }
+}
+
+impl Node for StepSequencer {
+    fn initialize() -> Self {
+        Self::default()
+    }
+
+    fn title(&self) -> &str {
+        "Step Sequencer"
+    }
+
+    fn fold(&self, out_id: &dyn OutId, lasy_fold: LasyFold, meta: Meta) -> anyhow::Result<Data> {
+        let Some(out_id) = out_id.as_any().downcast_ref::<StepSequencerOutId>() else {
+            return Err(anyhow!("not a valid out_id"));
+        };
+        if self.steps.is_empty() {
+            return Err(anyhow!("the pattern has no step"));
+        }
+
+        let position = self.position(&lasy_fold, meta)?;
+        let length = get_f64_or(
+            &lasy_fold,
+            &StepSequencerInId::Length,
+            meta,
+            self.steps.len() as f64,
+        )?;
+        let length = if length.is_nan() {
+            self.steps.len()
+        } else {
+            (length as usize).clamp(1, self.steps.len())
+        };
+
+        let played = position.floor() as u64;
+        let index = (played % length as u64) as usize;
+        let step = &self.steps[index];
+
+        let data = match out_id {
+            StepSequencerOutId::Value => Data::new(step.value as f32),
+            StepSequencerOutId::Step => Data::new(index as i64),
+            StepSequencerOutId::Gate => {
+                let gate_length =
+                    get_f64_or(&lasy_fold, &StepSequencerInId::GateLength, meta, 0.5)?;
+
+                let drawn = unit(hash(self.seed, played)) < step.probability;
+                let ratchet = (position.fract() * step.ratchet.max(1) as f64).fract();
+                let open = step.gate && drawn && ratchet < gate_length.clamp(0.0, 1.0);
+
+                Data::new(if open { 1.0f32 } else { 0.0 })
+            }
+        };
+
+        Ok(data)
+    }
+
+    fn node_in_id(&self, in_id: &dyn InId, node_id: NodeId) -> Option<NodeInId> {
+        in_id
+            .as_any()
+            .downcast_ref::<StepSequencerInId>()
+            .map(|in_id| NodeInId::new(node_id, in_id))
+    }
+
+    fn node_out_id(&self, out_id: &dyn OutId, node_id: NodeId) -> Option<NodeOutId> {
+        out_id
+            .as_any()
+            .downcast_ref::<StepSequencerOutId>()
+            .map(|out_id| NodeOutId::new(node_id, out_id))
+    }
+
+    fn in_ids(&self) -> Vec<Box<dyn InId>> {
+        vec![
+            Box::new(StepSequencerInId::Bpm),
+            Box::new(StepSequencerInId::Beat),
+            Box::new(StepSequencerInId::Division),
+            Box::new(StepSequencerInId::Length),
+            Box::new(StepSequencerInId::GateLength),
+        ]
+    }
+
+    fn out_ids(&self) -> Vec<Box<dyn OutId>> {
+        vec![
+            Box::new(StepSequencerOutId::Value),
+            Box::new(StepSequencerOutId::Gate),
+            Box::new(StepSequencerOutId::Step),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::fold_node_over;
+
+    /// Fold an output over the first `ticks` ticks, at 225 BPM, a step lasting 4 ticks
+    fn fold(
+        sequencer: StepSequencer,
+        out_id: StepSequencerOutId,
+        inputs: Vec<(&dyn InId, Data)>,
+        ticks: u64,
+    ) -> Vec<f64> {
+        let mut inputs = inputs;
+        inputs.push((&StepSequencerInId::Bpm, Data::new(225.0f32)));
+
+        fold_node_over(sequencer, inputs, &out_id, 0..ticks)
+            .unwrap()
+            .iter()
+            .map(|data| data.to_f64().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn steps_advance_with_the_tempo() {
+        let steps = vec![Step::new(1.0), Step::rest(2.0), Step::new(3.0)];
+
+        let indexes = fold(
+            StepSequencer::new(steps.clone(), 0),
+            StepSequencerOutId::Step,
+            vec![],
+            16,
+        );
+        assert_eq!(
+            indexes,
+            [
+                0., 0., 0., 0., 1., 1., 1., 1., 2., 2., 2., 2., 0., 0., 0., 0.
+            ]
+        );
+
+        let gates = fold(
+            StepSequencer::new(steps.clone(), 0),
+            StepSequencerOutId::Gate,
+            vec![],
+            12,
+        );
+        assert_eq!(gates, [1., 1., 0., 0., 0., 0., 0., 0., 1., 1., 0., 0.]);
+
+        // A shorter pattern loops earlier
+        let values = fold(
+            StepSequencer::new(steps.clone(), 0),
+            StepSequencerOutId::Value,
+            vec![(&StepSequencerInId::Length, Data::new(2i64))],
+            12,
+        );
+        assert_eq!(values, [1., 1., 1., 1., 2., 2., 2., 2., 1., 1., 1., 1.]);
+
+        // Ticks left unfolded still count
+        let indexes = fold_node_over(
+            StepSequencer::new(steps, 0),
+            vec![(&StepSequencerInId::Bpm, Data::new(225.0f32))],
+            &StepSequencerOutId::Step,
+            [0, 5, 6, 13],
+        )
+        .unwrap();
+        let indexes: Vec<i64> = indexes.iter().map(|data| data.to_i64().unwrap()).collect();
+        assert_eq!(indexes, [0, 1, 1, 0]);
+    }
+
+    #[test]
+    fn ratchets_and_probabilities() {
+        let steps = vec![Step {
+            ratchet: 2,
+            ..Step::new(0.0)
+        }];
+        let gates = fold(
+            StepSequencer::new(steps, 0),
+            StepSequencerOutId::Gate,
+            vec![],
+            8,
+        );
+        assert_eq!(gates, [1., 0., 1., 0., 1., 0., 1., 0.]);
+
+        let steps = vec![Step {
+            probability: 0.5,
+            ..Step::new(0.0)
+        }];
+        let gates = fold(
+            StepSequencer::new(steps.clone(), 3),
+            StepSequencerOutId::Gate,
+            vec![],
+            400,
+        );
+        let played = gates.iter().step_by(4).filter(|gate| **gate == 1.0).count();
+        assert!((30..70).contains(&played));
+        // The same seed replays the same steps
+        assert_eq!(
+            gates,
+            fold(
+                StepSequencer::new(steps, 3),
+                StepSequencerOutId::Gate,
+                vec![],
+                400
+            )
+        );
+    }
+
+    #[test]
+    fn steps_can_follow_a_beat() {
+        let steps = vec![Step::new(1.0), Step::new(2.0), Step::new(3.0)];
+        let fold = |beat: f64| {
+            fold_node_over(
+                StepSequencer::new(steps.clone(), 0),
+                vec![(&StepSequencerInId::Beat, Data::new(beat))],
+                &StepSequencerOutId::Step,
+                0..1,
+            )
+            .unwrap()[0]
+                .to_f64()
+                .unwrap()
+        };
+
+        assert_eq!(fold(0.0), 0.0);
+        assert_eq!(fold(0.5), 2.0);
+        assert_eq!(fold(1.0), 1.0);
+    }
+
+    #[test]
+    fn empty_patterns_are_an_error() {
+        let result = fold_node_over(
+            StepSequencer::new(vec![], 0),
+            vec![],
+            &StepSequencerOutId::Value,
+            0..1,
+        );
+        assert!(result.is_err());
+    }
+}
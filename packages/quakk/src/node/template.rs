@@ -0,0 +1,319 @@
+//! A node filling a text template, like `BPM: {bpm} – {title}`
+//!
+//! Each placeholder between braces becomes an input of the node named after it. A placeholder can
+//! fix the count of decimals of a number, as in `{bpm:.1}`, and `{{` and `}}` stand for literal
+//! braces.
+
+use anyhow::Context;
+
+use crate::{
+    Data, LasyFold, Meta, Node, ParseError,
+    id::{InId, NodeId, NodeInId, NodeOutId, OutId},
+    textual::{MAX_DECIMALS, format_number},
+};
+
+#[derive(Debug, Clone, PartialEq)]
+enum Segment {
+    Literal(String),
+    /// A placeholder, by index in [`TextTemplate::placeholders`]
+    Placeholder {
+        index: usize,
+        decimals: Option<usize>,
+    },
+}
+
+/// Parse the part of a placeholder after `:`, starting at `position`
+fn parse_format(format: &str, position: usize) -> Result<usize, ParseError> {
+    let decimals = format
+        .strip_prefix('.')
+        .filter(|decimals| !decimals.is_empty() && decimals.chars().all(|c| c.is_ascii_digit()))
+        .ok_or(ParseError::new(
+            position,
+            format!("invalid format `{format}`, expected `.` followed by a count of decimals"),
+        ))?;
+
+    decimals
+        .parse()
+        .ok()
+        .filter(|decimals| *decimals <= MAX_DECIMALS)
+        .ok_or(ParseError::new(
+            position + 1,
+            format!("the count of decimals {decimals} is over {MAX_DECIMALS}"),
+        ))
+}
+
+fn parse(template: &str) -> Result<(Vec<Segment>, Vec<String>), ParseError> {
+    let mut segments = Vec::new();
+    let mut placeholders: Vec<String> = Vec::new();
+    let mut literal = String::new();
+    let mut chars = template.chars().enumerate().peekable();
+
+    while let Some((position, char)) = chars.next() {
+        match char {
+            '{' if chars.next_if(|(_, char)| *char == '{').is_some() => literal.push('{'),
+            '}' if chars.next_if(|(_, char)| *char == '}').is_some() => literal.push('}'),
+            '}' => {
+                return Err(ParseError::new(
+                    position,
+                    "unexpected `}`, write `}}` for a literal brace",
+                ));
+            }
+            '{' => {
+                let mut content = String::new();
+                loop {
+                    match chars.next() {
+                        Some((_, '}')) => break,
+                        Some((position, '{')) => {
+                            return Err(ParseError::new(
+                                position,
+                                "unexpected `{` inside a placeholder",
+                            ));
+                        }
+                        Some((_, char)) => content.push(char),
+                        None => {
+                            return Err(ParseError::new(position, "unclosed placeholder"));
+                        }
+                    }
+                }
+
+                let (name, decimals) = match content.split_once(':') {
+                    Some((name, format)) => {
+                        let format_at = position + 1 + name.chars().count() + 1;
+                        (name, Some(parse_format(format, format_at)?))
+                    }
+                    None => (content.as_str(), None),
+                };
+
+                let name = name.trim();
+                if name.is_empty() {
+                    return Err(ParseError::new(position, "empty placeholder name"));
+                }
+
+                let index = match placeholders.iter().position(|known| known == name) {
+                    Some(index) => index,
+                    None => {
+                        placeholders.push(name.to_string());
+                        placeholders.len() - 1
+                    }
+                };
+
+                if !literal.is_empty() {
+                    segments.push(Segment::Literal(std::mem::take(&mut literal)));
+                }
+                segments.push(Segment::Placeholder { index, decimals });
+            }
+            char => literal.push(char),
+        }
+    }
+
+    if !literal.is_empty() {
+        segments.push(Segment::Literal(literal));
+    }
+
+    Ok((segments, placeholders))
+}
+
+/// An input of a [`TextTemplate`], named after a placeholder of the template
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct TextTemplateInId {
+    name: String,
+}
+
+impl TextTemplateInId {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+        }
+    }
+}
+
+impl InId for TextTemplateInId {
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum TextTemplateOutId {
+    Out,
+}
+
+impl OutId for TextTemplateOutId {}
+
+/// Substitute each placeholder of a template with the value of its input
+///
+/// Numbers and booleans are written out, and placeholders with a count of decimals only accept
+/// numbers
+/// ```
+/// # use quakk::template::TextTemplate;
+/// let template = TextTemplate::new("BPM: {bpm:.1} – {title}").unwrap();
+/// assert_eq!(template.placeholders(), ["bpm", "title"]);
+///
+/// let error = TextTemplate::new("{bpm").unwrap_err();
+/// assert_eq!(error.position(), 0);
+/// ```
+#[derive(Debug)]
+pub struct TextTemplate {
+    template: String,
+    segments: Vec<Segment>,
+    placeholders: Vec<String>,
+}
+
+impl TextTemplate {
+    /// Parse a template, returning an error pointing to the position of the first issue found
+    pub fn new(template: &str) -> Result<Self, ParseError> {
+        let (segments, placeholders) = parse(template)?;
+
+        Ok(Self {
+            template: template.to_string(),
+            segments,
+            placeholders,
+        })
+    }
+
+    pub fn template(&self) -> &str {
+        &self.template
+    }
+
+    /// The names of the placeholders of the template, in order of appearance
+    pub fn placeholders(&self) -> &[String] {
+        &self.placeholders
+    }
+}
+
+impl Default for TextTemplate {
+    fn default() -> Self {
+        Self::new("").expect("the default template should be valid")
+    }
+}
+
+impl Node for TextTemplate {
+    fn initialize() -> Self {
+        Self::default()
+    }
+
+    fn title(&self) -> &str {
+        "Text Template"
+    }
+
+    fn fold(&self, _out_id: &dyn OutId, lasy_fold: LasyFold, meta: Meta) -> anyhow::Result<Data> {
+        let mut values: Vec<Option<Data>> = vec![None; self.placeholders.len()];
+        let mut text = String::new();
+
+        for segment in &self.segments {
+            match segment {
+                Segment::Literal(literal) => text.push_str(literal),
+                Segment::Placeholder { index, decimals } => {
+                    let name = &self.placeholders[*index];
+                    let value = match &values[*index] {
+                        Some(value) => value.clone(),
+                        None => {
+                            let value = lasy_fold.get_in(&TextTemplateInId::new(name), meta)?;
+                            values[*index] = Some(value.clone());
+                            value
+                        }
+                    };
+
+                    let value = match decimals {
+                        Some(_) => value.to_f64().map(|value| format_number(value, *decimals)),
+                        None => value.to_text(),
+                    };
+                    text.push_str(&value.with_context(|| format!("invalid {name}"))?);
+                }
+            }
+        }
+
+        Ok(Data::new(text))
+    }
+
+    fn node_in_id(&self, in_id: &dyn InId, node_id: NodeId) -> Option<NodeInId> {
+        in_id
+            .as_any()
+            .downcast_ref::<TextTemplateInId>()
+            .filter(|in_id| self.placeholders.contains(&in_id.name))
+            .map(|in_id| NodeInId::new(node_id, in_id))
+    }
+
+    fn node_out_id(&self, out_id: &dyn OutId, node_id: NodeId) -> Option<NodeOutId> {
+        out_id
+            .as_any()
+            .downcast_ref::<TextTemplateOutId>()
+            .map(|out_id| NodeOutId::new(node_id, out_id))
+    }
+
+    fn in_ids(&self) -> Vec<Box<dyn InId>> {
+        self.placeholders
+            .iter()
+            .map(|name| Box::new(TextTemplateInId::new(name)) as Box<dyn InId>)
+            .collect()
+    }
+
+    fn out_ids(&self) -> Vec<Box<dyn OutId>> {
+        vec![Box::new(TextTemplateOutId::Out)]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::fold_node;
+
+    #[test]
+    fn substitutes_placeholders() {
+        let template = TextTemplate::new("BPM: {bpm:.1} – {title} ({bpm}) {{ok}}").unwrap();
+        assert_eq!(template.placeholders(), ["bpm", "title"]);
+
+        let text = fold_node(
+            template,
+            vec![
+                (&TextTemplateInId::new("bpm"), Data::new(120.25f32)),
+                (
+                    &TextTemplateInId::new("title"),
+                    Data::new("Drift".to_string()),
+                ),
+            ],
+            &TextTemplateOutId::Out,
+        )
+        .unwrap();
+        assert_eq!(text.as_text().unwrap(), "BPM: 120.2 – Drift (120.25) {ok}");
+    }
+
+    #[test]
+    fn decimals_need_a_number() {
+        let text = fold_node(
+            TextTemplate::new("{title:.2}").unwrap(),
+            vec![(&TextTemplateInId::new("title"), Data::new("x".to_string()))],
+            &TextTemplateOutId::Out,
+        );
+        assert!(text.is_err());
+    }
+
+    #[test]
+    fn parse_errors_point_to_the_issue() {
+        let error = |template: &str| TextTemplate::new(template).unwrap_err();
+
+        assert_eq!(error("a } b").position(), 2);
+        assert_eq!(error("é {name").position(), 2);
+        assert_eq!(error("{}").position(), 0);
+        assert_eq!(error("{a{b}}").position(), 2);
+        assert_eq!(error("{bpm:2}").position(), 5);
+        assert_eq!(error("{bpm:.x}").position(), 5);
+        assert_eq!(
+            error("{bpm:.2000000000}").to_string(),
+            "the count of decimals 2000000000 is over 32 at position 6"
+        );
+    }
+
+    #[test]
+    fn placeholders_become_inputs() {
+        let template = TextTemplate::new("/synth/{voice}/{param}").unwrap();
+
+        let names: Vec<String> = template.in_ids().iter().map(|id| id.name()).collect();
+        assert_eq!(names, ["voice", "param"]);
+        assert!(
+            template
+                .node_in_id(&TextTemplateInId::new("other"), NodeId::GraphIn)
+                .is_none()
+        );
+    }
+}
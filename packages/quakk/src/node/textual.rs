@@ -1,7 +1,13 @@
+//! Nodes for textual manipulation
+//!
+//! Texts are indexed in characters rather than bytes, so no index can fall inside a character
+
 use anyhow::{Context, anyhow};
 
+use unicode_segmentation::UnicodeSegmentation;
+
 use crate::{
-    Data, LasyFold, Meta, Node,
+    Data, DataList, LasyFold, Meta, Node,
     id::{InId, NodeId, NodeInId, NodeOutId, OutId},
 };
 
@@ -43,18 +49,57 @@ impl Node for TextConstant {
             .map(|out_id| NodeOutId::new(node_id, out_id))
     }
 
+    fn in_ids(&self) -> Vec<Box<dyn InId>> {
+        Vec::new()
+    }
+
+    fn out_ids(&self) -> Vec<Box<dyn OutId>> {
+        vec![Box::new(TextConstantOutId::Out)]
+    }
+
     fn fold(&self, _out_id: &dyn OutId, _lasy_fold: LasyFold, _meta: Meta) -> anyhow::Result<Data> {
         Ok(Data::new(self.value.clone()))
     }
 }
 
+/// How a [`TextSplit`] cuts its text
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum TextSplitMode {
+    /// At the `At` index, counted in characters
+    #[default]
+    Char,
+    /// At the `At` index, counted in graphemes, so an emoji or an accented letter made of several
+    /// characters is never cut in half
+    Grapheme,
+    /// Around the first occurrence of `Delimiter`
+    First,
+    /// Around the last occurrence of `Delimiter`
+    Last,
+    /// Around every occurrence of `Delimiter`, `Start` and `End` being the first and last parts
+    All,
+}
+
+impl TextSplitMode {
+    fn uses_delimiter(&self) -> bool {
+        matches!(self, Self::First | Self::Last | Self::All)
+    }
+}
+
+/// Split a text in two, or in many parts with [`TextSplitMode::All`]
+///
+/// Negative indices count from the end of the text, and out of range indices are clamped. When
+/// the delimiter is not found, `Start` is the whole text and `End` is empty.
+/// `Parts` is the list of every part, `Start` and `End` included.
 #[derive(Debug, Default)]
-pub struct TextSplit;
+pub struct TextSplit {
+    mode: TextSplitMode,
+}
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum TextSplitInId {
     Text,
     At,
+    Delimiter,
 }
 
 impl InId for TextSplitInId {}
@@ -63,10 +108,71 @@ impl InId for TextSplitInId {}
 pub enum TextSplitOutId {
     Start,
     End,
+    Parts,
 }
 
 impl OutId for TextSplitOutId {}
 
+impl TextSplit {
+    pub fn new(mode: TextSplitMode) -> Self {
+        Self { mode }
+    }
+
+    pub fn mode(&self) -> TextSplitMode {
+        self.mode
+    }
+
+    /// The byte offset in `text` to split at, given the boundaries allowed by the mode
+    fn offset(&self, text: &str, at: f64) -> anyhow::Result<usize> {
+        if at.is_nan() {
+            return Err(anyhow!("the index is not a number"));
+        }
+
+        let boundaries: Vec<usize> = match self.mode {
+            TextSplitMode::Grapheme => text.grapheme_indices(true).map(|(i, _)| i).collect(),
+            _ => text.char_indices().map(|(i, _)| i).collect(),
+        };
+
+        let len = boundaries.len() as f64;
+        let at = if at < 0.0 { len + at } else { at };
+        let at = at.clamp(0.0, len) as usize;
+
+        Ok(boundaries.get(at).copied().unwrap_or(text.len()))
+    }
+
+    fn parts<'a>(
+        &self,
+        text: &'a str,
+        lasy_fold: &LasyFold,
+        meta: Meta,
+    ) -> anyhow::Result<Vec<&'a str>> {
+        if !self.mode.uses_delimiter() {
+            let at = lasy_fold
+                .get_in(&TextSplitInId::At, meta)?
+                .to_f64()
+                .context("invalid At")?;
+            let (start, end) = text.split_at(self.offset(text, at).context("invalid At")?);
+
+            return Ok(vec![start, end]);
+        }
+
+        let delimiter = lasy_fold.get_in(&TextSplitInId::Delimiter, meta)?;
+        let delimiter = delimiter.as_text().context("invalid Delimiter")?;
+        if delimiter.is_empty() {
+            return Err(anyhow!("the Delimiter is empty"));
+        }
+
+        let split = match self.mode {
+            TextSplitMode::All => return Ok(text.split(delimiter).collect()),
+            TextSplitMode::Last => text.rsplit_once(delimiter),
+            _ => text.split_once(delimiter),
+        };
+        let (start, end) = split.unwrap_or((text, ""));
+
+        Ok(vec![start, end])
+    }
+}
+
 impl Node for TextSplit {
     fn title(&self) -> &str {
         "Text Split"
@@ -83,7 +189,12 @@ impl Node for TextSplit {
         in_id
             .as_any()
             .downcast_ref::<TextSplitInId>()
-            .map(|out_id| NodeInId::new(node_id, in_id))
+            .filter(|in_id| match in_id {
+                TextSplitInId::Text => true,
+                TextSplitInId::At => !self.mode.uses_delimiter(),
+                TextSplitInId::Delimiter => self.mode.uses_delimiter(),
+            })
+            .map(|in_id| NodeInId::new(node_id, in_id))
     }
 
     fn node_out_id(&self, out_id: &dyn OutId, node_id: NodeId) -> Option<NodeOutId> {
@@ -93,31 +204,1188 @@ impl Node for TextSplit {
             .map(|out_id| NodeOutId::new(node_id, out_id))
     }
 
+    fn in_ids(&self) -> Vec<Box<dyn InId>> {
+        if self.mode.uses_delimiter() {
+            vec![
+                Box::new(TextSplitInId::Text),
+                Box::new(TextSplitInId::Delimiter),
+            ]
+        } else {
+            vec![Box::new(TextSplitInId::Text), Box::new(TextSplitInId::At)]
+        }
+    }
+
+    fn out_ids(&self) -> Vec<Box<dyn OutId>> {
+        vec![
+            Box::new(TextSplitOutId::Start),
+            Box::new(TextSplitOutId::End),
+            Box::new(TextSplitOutId::Parts),
+        ]
+    }
+
     fn fold(&self, out_id: &dyn OutId, lasy_fold: LasyFold, meta: Meta) -> anyhow::Result<Data> {
-        let text = lasy_fold
-            .get_in(&TextSplitInId::Text, meta)?
-            .into_string()
-            .context("type mismatch for Text, expected String")?;
-        let at = lasy_fold
-            .get_in(&TextSplitInId::At, meta)?
-            .into_f32()
-            .context("type mismatch for Term2, expected f32")?;
-
-        dbg!(at as usize);
-        match out_id.as_any().downcast_ref::<TextSplitOutId>() {
-            Some(out_id) => {
-                let split = text.split_at(at as usize);
-
-                match out_id {
-                    TextSplitOutId::Start => Ok(Data::new(split.0.to_string())),
-                    TextSplitOutId::End => Ok(Data::new(split.1.to_string())),
-                }
-            }
-            None => Err(anyhow!("Not a valid out_id")),
+        let Some(out_id) = out_id.as_any().downcast_ref::<TextSplitOutId>() else {
+            return Err(anyhow!("not a valid out_id"));
+        };
+
+        let text = lasy_fold.get_in(&TextSplitInId::Text, meta)?;
+        let text = text.as_text().context("invalid Text")?;
+        let parts = self.parts(text, &lasy_fold, meta)?;
+
+        match out_id {
+            TextSplitOutId::Start => Ok(Data::new(parts[0].to_string())),
+            TextSplitOutId::End => Ok(Data::new(parts[parts.len() - 1].to_string())),
+            TextSplitOutId::Parts => Ok(Data::new(
+                parts
+                    .into_iter()
+                    .map(|part| Data::new(part.to_string()))
+                    .collect::<DataList>(),
+            )),
+        }
+    }
+}
+
+/// The maximum length, in characters, of a text built by [`TextRepeat`] or [`TextPad`]
+pub const MAX_TEXT_LEN: usize = 1 << 20;
+
+/// Fold an input of the node, converting it to a text, numbers and booleans being written out
+fn get_text(lasy_fold: &LasyFold, in_id: &dyn InId, meta: Meta) -> anyhow::Result<String> {
+    lasy_fold
+        .get_in(in_id, meta)?
+        .to_text()
+        .with_context(|| format!("invalid {}", in_id.name()))
+}
+
+/// Fold an input of the node, converting it to an `i64`
+fn get_i64(lasy_fold: &LasyFold, in_id: &dyn InId, meta: Meta) -> anyhow::Result<i64> {
+    lasy_fold
+        .get_in(in_id, meta)?
+        .to_i64()
+        .with_context(|| format!("invalid {}", in_id.name()))
+}
+
+/// The largest count of decimals numbers are written with
+pub(crate) const MAX_DECIMALS: usize = 32;
+
+/// Write a number with a fixed count of decimals, or with as few as needed when `None`
+pub(crate) fn format_number(value: f64, decimals: Option<usize>) -> String {
+    match decimals {
+        Some(decimals) => format!("{value:.decimals$}"),
+        None => value.to_string(),
+    }
+}
+
+/// A character position in a text of `len` characters, negative indices counting from the end
+fn char_position(len: usize, index: i64) -> usize {
+    if index < 0 {
+        len.saturating_sub(index.unsigned_abs() as usize)
+    } else {
+        len.min(index as usize)
+    }
+}
+
+/// Put two texts end to end, numbers and booleans are written out
+#[derive(Debug, Default)]
+pub struct TextConcat;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum TextConcatInId {
+    Term1,
+    Term2,
+}
+
+impl InId for TextConcatInId {}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum TextConcatOutId {
+    Out,
+}
+
+impl OutId for TextConcatOutId {}
+
+impl Node for TextConcat {
+    fn initialize() -> Self {
+        Self
+    }
+
+    fn title(&self) -> &str {
+        "Text Concat"
+    }
+
+    fn fold(&self, _out_id: &dyn OutId, lasy_fold: LasyFold, meta: Meta) -> anyhow::Result<Data> {
+        let term1 = get_text(&lasy_fold, &TextConcatInId::Term1, meta)?;
+        let term2 = get_text(&lasy_fold, &TextConcatInId::Term2, meta)?;
+
+        Ok(Data::new(term1 + &term2))
+    }
+
+    fn node_in_id(&self, in_id: &dyn InId, node_id: NodeId) -> Option<NodeInId> {
+        in_id
+            .as_any()
+            .downcast_ref::<TextConcatInId>()
+            .map(|in_id| NodeInId::new(node_id, in_id))
+    }
+
+    fn node_out_id(&self, out_id: &dyn OutId, node_id: NodeId) -> Option<NodeOutId> {
+        out_id
+            .as_any()
+            .downcast_ref::<TextConcatOutId>()
+            .map(|out_id| NodeOutId::new(node_id, out_id))
+    }
+
+    fn in_ids(&self) -> Vec<Box<dyn InId>> {
+        vec![
+            Box::new(TextConcatInId::Term1),
+            Box::new(TextConcatInId::Term2),
+        ]
+    }
+
+    fn out_ids(&self) -> Vec<Box<dyn OutId>> {
+        vec![Box::new(TextConcatOutId::Out)]
+    }
+}
+
+/// Join the elements of a list into a single text, with an optional `Separator` between them
+#[derive(Debug, Default)]
+pub struct TextJoin;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum TextJoinInId {
+    List,
+    Separator,
+}
+
+impl InId for TextJoinInId {}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum TextJoinOutId {
+    Out,
+}
+
+impl OutId for TextJoinOutId {}
+
+impl Node for TextJoin {
+    fn initialize() -> Self {
+        Self
+    }
+
+    fn title(&self) -> &str {
+        "Text Join"
+    }
+
+    fn fold(&self, _out_id: &dyn OutId, lasy_fold: LasyFold, meta: Meta) -> anyhow::Result<Data> {
+        let list = lasy_fold.get_in(&TextJoinInId::List, meta)?;
+        let list = list.as_list().context("invalid List")?;
+        let separator = if lasy_fold.is_patched(&TextJoinInId::Separator) {
+            get_text(&lasy_fold, &TextJoinInId::Separator, meta)?
+        } else {
+            String::new()
+        };
+
+        let elements = list
+            .iter()
+            .map(Data::to_text)
+            .collect::<anyhow::Result<Vec<_>>>()
+            .context("invalid List")?;
+
+        Ok(Data::new(elements.join(&separator)))
+    }
+
+    fn node_in_id(&self, in_id: &dyn InId, node_id: NodeId) -> Option<NodeInId> {
+        in_id
+            .as_any()
+            .downcast_ref::<TextJoinInId>()
+            .map(|in_id| NodeInId::new(node_id, in_id))
+    }
+
+    fn node_out_id(&self, out_id: &dyn OutId, node_id: NodeId) -> Option<NodeOutId> {
+        out_id
+            .as_any()
+            .downcast_ref::<TextJoinOutId>()
+            .map(|out_id| NodeOutId::new(node_id, out_id))
+    }
+
+    fn in_ids(&self) -> Vec<Box<dyn InId>> {
+        vec![
+            Box::new(TextJoinInId::List),
+            Box::new(TextJoinInId::Separator),
+        ]
+    }
+
+    fn out_ids(&self) -> Vec<Box<dyn OutId>> {
+        vec![Box::new(TextJoinOutId::Out)]
+    }
+}
+
+/// The number of characters of a text, as an `i64`
+#[derive(Debug, Default)]
+pub struct TextLength;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum TextLengthInId {
+    Text,
+}
+
+impl InId for TextLengthInId {}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum TextLengthOutId {
+    Length,
+}
+
+impl OutId for TextLengthOutId {}
+
+impl Node for TextLength {
+    fn initialize() -> Self {
+        Self
+    }
+
+    fn title(&self) -> &str {
+        "Text Length"
+    }
+
+    fn fold(&self, _out_id: &dyn OutId, lasy_fold: LasyFold, meta: Meta) -> anyhow::Result<Data> {
+        let text = lasy_fold.get_in(&TextLengthInId::Text, meta)?;
+        let text = text.as_text().context("invalid Text")?;
+
+        Ok(Data::new(text.chars().count() as i64))
+    }
+
+    fn node_in_id(&self, in_id: &dyn InId, node_id: NodeId) -> Option<NodeInId> {
+        in_id
+            .as_any()
+            .downcast_ref::<TextLengthInId>()
+            .map(|in_id| NodeInId::new(node_id, in_id))
+    }
+
+    fn node_out_id(&self, out_id: &dyn OutId, node_id: NodeId) -> Option<NodeOutId> {
+        out_id
+            .as_any()
+            .downcast_ref::<TextLengthOutId>()
+            .map(|out_id| NodeOutId::new(node_id, out_id))
+    }
+
+    fn in_ids(&self) -> Vec<Box<dyn InId>> {
+        vec![Box::new(TextLengthInId::Text)]
+    }
+
+    fn out_ids(&self) -> Vec<Box<dyn OutId>> {
+        vec![Box::new(TextLengthOutId::Length)]
+    }
+}
+
+/// Take `Length` characters of a text from `Start`, or up to its end when `Length` is not patched
+///
+/// A negative `Start` counts from the end of the text, and the substring is clamped to the text
+#[derive(Debug, Default)]
+pub struct TextSubstring;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum TextSubstringInId {
+    Text,
+    Start,
+    Length,
+}
+
+impl InId for TextSubstringInId {}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum TextSubstringOutId {
+    Out,
+}
+
+impl OutId for TextSubstringOutId {}
+
+impl Node for TextSubstring {
+    fn initialize() -> Self {
+        Self
+    }
+
+    fn title(&self) -> &str {
+        "Text Substring"
+    }
+
+    fn fold(&self, _out_id: &dyn OutId, lasy_fold: LasyFold, meta: Meta) -> anyhow::Result<Data> {
+        let text = lasy_fold.get_in(&TextSubstringInId::Text, meta)?;
+        let text = text.as_text().context("invalid Text")?;
+        let start = get_i64(&lasy_fold, &TextSubstringInId::Start, meta)?;
+
+        let start = char_position(text.chars().count(), start);
+        let substring = text.chars().skip(start);
+
+        let substring = if lasy_fold.is_patched(&TextSubstringInId::Length) {
+            let length = get_i64(&lasy_fold, &TextSubstringInId::Length, meta)?;
+            let length = usize::try_from(length)
+                .map_err(|_| anyhow!("the length {length} is negative"))
+                .context("invalid Length")?;
+
+            substring.take(length).collect::<String>()
+        } else {
+            substring.collect()
+        };
+
+        Ok(Data::new(substring))
+    }
+
+    fn node_in_id(&self, in_id: &dyn InId, node_id: NodeId) -> Option<NodeInId> {
+        in_id
+            .as_any()
+            .downcast_ref::<TextSubstringInId>()
+            .map(|in_id| NodeInId::new(node_id, in_id))
+    }
+
+    fn node_out_id(&self, out_id: &dyn OutId, node_id: NodeId) -> Option<NodeOutId> {
+        out_id
+            .as_any()
+            .downcast_ref::<TextSubstringOutId>()
+            .map(|out_id| NodeOutId::new(node_id, out_id))
+    }
+
+    fn in_ids(&self) -> Vec<Box<dyn InId>> {
+        vec![
+            Box::new(TextSubstringInId::Text),
+            Box::new(TextSubstringInId::Start),
+            Box::new(TextSubstringInId::Length),
+        ]
+    }
+
+    fn out_ids(&self) -> Vec<Box<dyn OutId>> {
+        vec![Box::new(TextSubstringOutId::Out)]
+    }
+}
+
+/// Replace every occurrence of `Pattern` in a text by `Replacement`
+#[derive(Debug, Default)]
+pub struct TextReplace;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum TextReplaceInId {
+    Text,
+    Pattern,
+    Replacement,
+}
+
+impl InId for TextReplaceInId {}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum TextReplaceOutId {
+    Out,
+}
+
+impl OutId for TextReplaceOutId {}
+
+impl Node for TextReplace {
+    fn initialize() -> Self {
+        Self
+    }
+
+    fn title(&self) -> &str {
+        "Text Replace"
+    }
+
+    fn fold(&self, _out_id: &dyn OutId, lasy_fold: LasyFold, meta: Meta) -> anyhow::Result<Data> {
+        let text = lasy_fold.get_in(&TextReplaceInId::Text, meta)?;
+        let text = text.as_text().context("invalid Text")?;
+        let pattern = get_text(&lasy_fold, &TextReplaceInId::Pattern, meta)?;
+        let replacement = get_text(&lasy_fold, &TextReplaceInId::Replacement, meta)?;
+
+        if pattern.is_empty() {
+            return Err(anyhow!("the Pattern is empty"));
         }
 
-        // .map(|out_id| NodeInId::new(node_id, in_id))
+        Ok(Data::new(text.replace(&pattern, &replacement)))
+    }
+
+    fn node_in_id(&self, in_id: &dyn InId, node_id: NodeId) -> Option<NodeInId> {
+        in_id
+            .as_any()
+            .downcast_ref::<TextReplaceInId>()
+            .map(|in_id| NodeInId::new(node_id, in_id))
+    }
+
+    fn node_out_id(&self, out_id: &dyn OutId, node_id: NodeId) -> Option<NodeOutId> {
+        out_id
+            .as_any()
+            .downcast_ref::<TextReplaceOutId>()
+            .map(|out_id| NodeOutId::new(node_id, out_id))
+    }
+
+    fn in_ids(&self) -> Vec<Box<dyn InId>> {
+        vec![
+            Box::new(TextReplaceInId::Text),
+            Box::new(TextReplaceInId::Pattern),
+            Box::new(TextReplaceInId::Replacement),
+        ]
+    }
+
+    fn out_ids(&self) -> Vec<Box<dyn OutId>> {
+        vec![Box::new(TextReplaceOutId::Out)]
+    }
+}
+
+/// A transformation of a single text
+#[derive(Debug, Default)]
+pub enum TextTransformation {
+    #[default]
+    Uppercase,
+    Lowercase,
+    /// Remove the whitespace at both ends
+    Trim,
+    TrimStart,
+    TrimEnd,
+    /// Reverse the order of the graphemes, so accented letters stay intact
+    Reverse,
+}
+
+impl TextTransformation {
+    fn apply(&self, text: &str) -> String {
+        use TextTransformation::*;
+        match self {
+            Uppercase => text.to_uppercase(),
+            Lowercase => text.to_lowercase(),
+            Trim => text.trim().to_string(),
+            TrimStart => text.trim_start().to_string(),
+            TrimEnd => text.trim_end().to_string(),
+            Reverse => text.graphemes(true).rev().collect(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum TextTransformInId {
+    In,
+}
+
+impl InId for TextTransformInId {}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum TextTransformOutId {
+    Out,
+}
+
+impl OutId for TextTransformOutId {}
+
+/// Apply a [`TextTransformation`] to a text
+#[derive(Debug, Default)]
+pub struct TextTransform {
+    transformation: TextTransformation,
+}
+
+impl TextTransform {
+    pub fn new(transformation: TextTransformation) -> Self {
+        Self { transformation }
+    }
+}
+
+impl Node for TextTransform {
+    fn initialize() -> Self {
+        Self::default()
+    }
+
+    fn title(&self) -> &str {
+        "Text Transform"
+    }
+
+    fn fold(&self, _out_id: &dyn OutId, lasy_fold: LasyFold, meta: Meta) -> anyhow::Result<Data> {
+        let text = lasy_fold.get_in(&TextTransformInId::In, meta)?;
+        let text = text.as_text().context("invalid In")?;
+
+        Ok(Data::new(self.transformation.apply(text)))
+    }
+
+    fn node_in_id(&self, in_id: &dyn InId, node_id: NodeId) -> Option<NodeInId> {
+        in_id
+            .as_any()
+            .downcast_ref::<TextTransformInId>()
+            .map(|in_id| NodeInId::new(node_id, in_id))
+    }
+
+    fn node_out_id(&self, out_id: &dyn OutId, node_id: NodeId) -> Option<NodeOutId> {
+        out_id
+            .as_any()
+            .downcast_ref::<TextTransformOutId>()
+            .map(|out_id| NodeOutId::new(node_id, out_id))
+    }
+
+    fn in_ids(&self) -> Vec<Box<dyn InId>> {
+        vec![Box::new(TextTransformInId::In)]
+    }
+
+    fn out_ids(&self) -> Vec<Box<dyn OutId>> {
+        vec![Box::new(TextTransformOutId::Out)]
+    }
+}
+
+#[derive(Debug, Default)]
+pub enum TextTestOperation {
+    #[default]
+    Contains,
+    StartsWith,
+    EndsWith,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum TextTestInId {
+    Text,
+    Pattern,
+}
+
+impl InId for TextTestInId {}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum TextTestOutId {
+    Out,
+}
+
+impl OutId for TextTestOutId {}
+
+/// Check whether a text contains, starts or ends with `Pattern`, as a `bool`
+#[derive(Debug, Default)]
+pub struct TextTest {
+    operation: TextTestOperation,
+}
+
+impl TextTest {
+    pub fn new(operation: TextTestOperation) -> Self {
+        Self { operation }
+    }
+}
+
+impl Node for TextTest {
+    fn initialize() -> Self {
+        Self::default()
+    }
+
+    fn title(&self) -> &str {
+        "Text Test"
+    }
+
+    fn fold(&self, _out_id: &dyn OutId, lasy_fold: LasyFold, meta: Meta) -> anyhow::Result<Data> {
+        let text = lasy_fold.get_in(&TextTestInId::Text, meta)?;
+        let text = text.as_text().context("invalid Text")?;
+        let pattern = get_text(&lasy_fold, &TextTestInId::Pattern, meta)?;
+
+        let result = match self.operation {
+            TextTestOperation::Contains => text.contains(&pattern),
+            TextTestOperation::StartsWith => text.starts_with(&pattern),
+            TextTestOperation::EndsWith => text.ends_with(&pattern),
+        };
+
+        Ok(Data::new(result))
+    }
+
+    fn node_in_id(&self, in_id: &dyn InId, node_id: NodeId) -> Option<NodeInId> {
+        in_id
+            .as_any()
+            .downcast_ref::<TextTestInId>()
+            .map(|in_id| NodeInId::new(node_id, in_id))
+    }
+
+    fn node_out_id(&self, out_id: &dyn OutId, node_id: NodeId) -> Option<NodeOutId> {
+        out_id
+            .as_any()
+            .downcast_ref::<TextTestOutId>()
+            .map(|out_id| NodeOutId::new(node_id, out_id))
+    }
+
+    fn in_ids(&self) -> Vec<Box<dyn InId>> {
+        vec![
+            Box::new(TextTestInId::Text),
+            Box::new(TextTestInId::Pattern),
+        ]
+    }
+
+    fn out_ids(&self) -> Vec<Box<dyn OutId>> {
+        vec![Box::new(TextTestOutId::Out)]
+    }
+}
+
+/// Repeat a text `Count` times, up to [`MAX_TEXT_LEN`] characters
+#[derive(Debug, Default)]
+pub struct TextRepeat;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum TextRepeatInId {
+    Text,
+    Count,
+}
+
+impl InId for TextRepeatInId {}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum TextRepeatOutId {
+    Out,
+}
+
+impl OutId for TextRepeatOutId {}
+
+impl Node for TextRepeat {
+    fn initialize() -> Self {
+        Self
+    }
+
+    fn title(&self) -> &str {
+        "Text Repeat"
+    }
+
+    fn fold(&self, _out_id: &dyn OutId, lasy_fold: LasyFold, meta: Meta) -> anyhow::Result<Data> {
+        let text = get_text(&lasy_fold, &TextRepeatInId::Text, meta)?;
+        let count = get_i64(&lasy_fold, &TextRepeatInId::Count, meta)?;
+
+        let count = usize::try_from(count)
+            .map_err(|_| anyhow!("the count {count} is negative"))
+            .context("invalid Count")?;
+
+        let len = text.chars().count().saturating_mul(count);
+        if len > MAX_TEXT_LEN {
+            return Err(anyhow!(
+                "the text would hold {len} characters, more than the maximum of {MAX_TEXT_LEN}"
+            ));
+        }
+
+        Ok(Data::new(text.repeat(count)))
+    }
+
+    fn node_in_id(&self, in_id: &dyn InId, node_id: NodeId) -> Option<NodeInId> {
+        in_id
+            .as_any()
+            .downcast_ref::<TextRepeatInId>()
+            .map(|in_id| NodeInId::new(node_id, in_id))
+    }
+
+    fn node_out_id(&self, out_id: &dyn OutId, node_id: NodeId) -> Option<NodeOutId> {
+        out_id
+            .as_any()
+            .downcast_ref::<TextRepeatOutId>()
+            .map(|out_id| NodeOutId::new(node_id, out_id))
+    }
+
+    fn in_ids(&self) -> Vec<Box<dyn InId>> {
+        vec![
+            Box::new(TextRepeatInId::Text),
+            Box::new(TextRepeatInId::Count),
+        ]
+    }
+
+    fn out_ids(&self) -> Vec<Box<dyn OutId>> {
+        vec![Box::new(TextRepeatOutId::Out)]
+    }
+}
+
+/// The side of the text a [`TextPad`] fills
+#[derive(Debug, Default)]
+pub enum PadSide {
+    /// Align the text to the right
+    #[default]
+    Start,
+    /// Align the text to the left
+    End,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum TextPadInId {
+    Text,
+    Width,
+    Fill,
+}
+
+impl InId for TextPadInId {}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum TextPadOutId {
+    Out,
+}
+
+impl OutId for TextPadOutId {}
+
+/// Fill a text up to `Width` characters, with the characters of `Fill` or spaces when it is not
+/// patched, texts already wider are left untouched
+#[derive(Debug, Default)]
+pub struct TextPad {
+    side: PadSide,
+}
+
+impl TextPad {
+    pub fn new(side: PadSide) -> Self {
+        Self { side }
+    }
+}
+
+impl Node for TextPad {
+    fn initialize() -> Self {
+        Self::default()
+    }
+
+    fn title(&self) -> &str {
+        "Text Pad"
+    }
+
+    fn fold(&self, _out_id: &dyn OutId, lasy_fold: LasyFold, meta: Meta) -> anyhow::Result<Data> {
+        let text = get_text(&lasy_fold, &TextPadInId::Text, meta)?;
+        let width = get_i64(&lasy_fold, &TextPadInId::Width, meta)?;
+        let fill = if lasy_fold.is_patched(&TextPadInId::Fill) {
+            get_text(&lasy_fold, &TextPadInId::Fill, meta)?
+        } else {
+            " ".to_string()
+        };
+
+        if fill.is_empty() {
+            return Err(anyhow!("the Fill is empty"));
+        }
+        if width > MAX_TEXT_LEN as i64 {
+            return Err(anyhow!(
+                "the width {width} is more than the maximum of {MAX_TEXT_LEN}"
+            ));
+        }
+
+        let missing = (width.max(0) as usize).saturating_sub(text.chars().count());
+        let padding: String = fill.chars().cycle().take(missing).collect();
+
+        let padded = match self.side {
+            PadSide::Start => padding + &text,
+            PadSide::End => text + &padding,
+        };
+
+        Ok(Data::new(padded))
+    }
+
+    fn node_in_id(&self, in_id: &dyn InId, node_id: NodeId) -> Option<NodeInId> {
+        in_id
+            .as_any()
+            .downcast_ref::<TextPadInId>()
+            .map(|in_id| NodeInId::new(node_id, in_id))
+    }
+
+    fn node_out_id(&self, out_id: &dyn OutId, node_id: NodeId) -> Option<NodeOutId> {
+        out_id
+            .as_any()
+            .downcast_ref::<TextPadOutId>()
+            .map(|out_id| NodeOutId::new(node_id, out_id))
+    }
+
+    fn in_ids(&self) -> Vec<Box<dyn InId>> {
+        vec![
+            Box::new(TextPadInId::Text),
+            Box::new(TextPadInId::Width),
+            Box::new(TextPadInId::Fill),
+        ]
+    }
+
+    fn out_ids(&self) -> Vec<Box<dyn OutId>> {
+        vec![Box::new(TextPadOutId::Out)]
+    }
+}
+
+/// Write a number as a text, with a fixed count of `Decimals` when patched
+#[derive(Debug, Default)]
+pub struct NumberToText;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum NumberToTextInId {
+    Number,
+    Decimals,
+}
+
+impl InId for NumberToTextInId {}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum NumberToTextOutId {
+    Out,
+}
+
+impl OutId for NumberToTextOutId {}
+
+impl Node for NumberToText {
+    fn initialize() -> Self {
+        Self
+    }
+
+    fn title(&self) -> &str {
+        "Number To Text"
+    }
+
+    fn fold(&self, _out_id: &dyn OutId, lasy_fold: LasyFold, meta: Meta) -> anyhow::Result<Data> {
+        let number = lasy_fold.get_in(&NumberToTextInId::Number, meta)?;
+
+        if !lasy_fold.is_patched(&NumberToTextInId::Decimals) {
+            // Integers are written as is, without going through a float
+            return Ok(Data::new(number.to_text().context("invalid Number")?));
+        }
+
+        let number = number.to_f64().context("invalid Number")?;
+        let decimals = get_i64(&lasy_fold, &NumberToTextInId::Decimals, meta)?;
+        let decimals = usize::try_from(decimals)
+            .map_err(|_| anyhow!("the count of decimals {decimals} is negative"))
+            .context("invalid Decimals")?;
+
+        Ok(Data::new(format_number(
+            number,
+            Some(decimals.min(MAX_DECIMALS)),
+        )))
+    }
+
+    fn node_in_id(&self, in_id: &dyn InId, node_id: NodeId) -> Option<NodeInId> {
+        in_id
+            .as_any()
+            .downcast_ref::<NumberToTextInId>()
+            .map(|in_id| NodeInId::new(node_id, in_id))
+    }
+
+    fn node_out_id(&self, out_id: &dyn OutId, node_id: NodeId) -> Option<NodeOutId> {
+        out_id
+            .as_any()
+            .downcast_ref::<NumberToTextOutId>()
+            .map(|out_id| NodeOutId::new(node_id, out_id))
+    }
+
+    fn in_ids(&self) -> Vec<Box<dyn InId>> {
+        vec![
+            Box::new(NumberToTextInId::Number),
+            Box::new(NumberToTextInId::Decimals),
+        ]
+    }
+
+    fn out_ids(&self) -> Vec<Box<dyn OutId>> {
+        vec![Box::new(NumberToTextOutId::Out)]
+    }
+}
+
+/// Parse a text as an `f32`, surrounding whitespace is ignored and anything else is an error
+#[derive(Debug, Default)]
+pub struct TextToNumber;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum TextToNumberInId {
+    Text,
+}
+
+impl InId for TextToNumberInId {}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum TextToNumberOutId {
+    Out,
+}
+
+impl OutId for TextToNumberOutId {}
+
+impl Node for TextToNumber {
+    fn initialize() -> Self {
+        Self
+    }
+
+    fn title(&self) -> &str {
+        "Text To Number"
+    }
+
+    fn fold(&self, _out_id: &dyn OutId, lasy_fold: LasyFold, meta: Meta) -> anyhow::Result<Data> {
+        let text = lasy_fold.get_in(&TextToNumberInId::Text, meta)?;
+        let text = text.as_text().context("invalid Text")?;
+
+        let number: f32 = text
+            .trim()
+            .parse()
+            .map_err(|_| anyhow!("`{text}` is not a number"))
+            .context("invalid Text")?;
+
+        Ok(Data::new(number))
+    }
+
+    fn node_in_id(&self, in_id: &dyn InId, node_id: NodeId) -> Option<NodeInId> {
+        in_id
+            .as_any()
+            .downcast_ref::<TextToNumberInId>()
+            .map(|in_id| NodeInId::new(node_id, in_id))
+    }
+
+    fn node_out_id(&self, out_id: &dyn OutId, node_id: NodeId) -> Option<NodeOutId> {
+        out_id
+            .as_any()
+            .downcast_ref::<TextToNumberOutId>()
+            .map(|out_id| NodeOutId::new(node_id, out_id))
+    }
+
+    fn in_ids(&self) -> Vec<Box<dyn InId>> {
+        vec![Box::new(TextToNumberInId::Text)]
+    }
+
+    fn out_ids(&self) -> Vec<Box<dyn OutId>> {
+        vec![Box::new(TextToNumberOutId::Out)]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::fold_node;
+
+    fn split_at(mode: TextSplitMode, text: &str, at: f32) -> anyhow::Result<(String, String)> {
+        let fold = |out_id| {
+            fold_node(
+                TextSplit::new(mode),
+                vec![
+                    (&TextSplitInId::Text, Data::new(text.to_string())),
+                    (&TextSplitInId::At, Data::new(at)),
+                ],
+                &out_id,
+            )
+            .map(|data| data.as_text().unwrap().to_string())
+        };
+
+        Ok((fold(TextSplitOutId::Start)?, fold(TextSplitOutId::End)?))
+    }
+
+    fn split_by(mode: TextSplitMode, text: &str, delimiter: &str) -> anyhow::Result<Vec<String>> {
+        let parts = fold_node(
+            TextSplit::new(mode),
+            vec![
+                (&TextSplitInId::Text, Data::new(text.to_string())),
+                (&TextSplitInId::Delimiter, Data::new(delimiter.to_string())),
+            ],
+            &TextSplitOutId::Parts,
+        )?;
+
+        Ok(parts
+            .as_list()?
+            .iter()
+            .map(|part| part.as_text().unwrap().to_string())
+            .collect())
+    }
+
+    #[test]
+    fn split_at_index_never_panics() {
+        let split = |text, at| split_at(TextSplitMode::Char, text, at).unwrap();
+
+        assert_eq!(split("Hello", 2.0), ("He".into(), "llo".into()));
+        assert_eq!(split("héllo", 2.0), ("hé".into(), "llo".into()));
+        assert_eq!(split("Hello", 10.0), ("Hello".into(), "".into()));
+        assert_eq!(split("Hello", -1.0), ("Hell".into(), "o".into()));
+        assert_eq!(split("Hello", -10.0), ("".into(), "Hello".into()));
+        assert_eq!(split("", 3.0), ("".into(), "".into()));
+        assert!(split_at(TextSplitMode::Char, "Hello", f32::NAN).is_err());
+    }
+
+    #[test]
+    fn split_at_grapheme() {
+        // An `e` followed by a combining acute accent
+        let text = "e\u{301}t\u{e9}";
+
+        assert_eq!(
+            split_at(TextSplitMode::Grapheme, text, 1.0).unwrap(),
+            ("e\u{301}".into(), "t\u{e9}".into())
+        );
+        assert_eq!(
+            split_at(TextSplitMode::Char, text, 1.0).unwrap(),
+            ("e".into(), "\u{301}t\u{e9}".into())
+        );
+    }
+
+    #[test]
+    fn split_by_delimiter() {
+        assert_eq!(
+            split_by(TextSplitMode::First, "a,b,c", ",").unwrap(),
+            ["a", "b,c"]
+        );
+        assert_eq!(
+            split_by(TextSplitMode::Last, "a,b,c", ",").unwrap(),
+            ["a,b", "c"]
+        );
+        assert_eq!(
+            split_by(TextSplitMode::All, "a,b,,c", ",").unwrap(),
+            ["a", "b", "", "c"]
+        );
+        assert_eq!(
+            split_by(TextSplitMode::First, "abc", ",").unwrap(),
+            ["abc", ""]
+        );
+        assert!(split_by(TextSplitMode::All, "abc", "").is_err());
+        assert!(
+            TextSplit::new(TextSplitMode::All)
+                .node_in_id(&TextSplitInId::At, NodeId::GraphIn)
+                .is_none()
+        );
+    }
+
+    fn text(value: &str) -> Data {
+        Data::new(value.to_string())
+    }
+
+    fn fold_text(
+        node: impl Node + 'static,
+        inputs: Vec<(&dyn InId, Data)>,
+        out_id: &dyn OutId,
+    ) -> anyhow::Result<String> {
+        fold_node(node, inputs, out_id).map(|data| data.as_text().unwrap().to_string())
+    }
+
+    #[test]
+    fn concat_join_and_length() {
+        let concat = fold_text(
+            TextConcat,
+            vec![
+                (&TextConcatInId::Term1, text("BPM ")),
+                (&TextConcatInId::Term2, Data::new(120i64)),
+            ],
+            &TextConcatOutId::Out,
+        );
+        assert_eq!(concat.unwrap(), "BPM 120");
+
+        let list = Data::new(vec![text("a"), Data::new(1i64), Data::new(true)]);
+        let join = |inputs| fold_text(TextJoin, inputs, &TextJoinOutId::Out).unwrap();
+        assert_eq!(join(vec![(&TextJoinInId::List, list.clone())]), "a1true");
+        assert_eq!(
+            join(vec![
+                (&TextJoinInId::List, list),
+                (&TextJoinInId::Separator, text(", "))
+            ]),
+            "a, 1, true"
+        );
+
+        let length = fold_node(
+            TextLength,
+            vec![(&TextLengthInId::Text, text("héllo"))],
+            &TextLengthOutId::Length,
+        );
+        assert_eq!(length.unwrap().as_i64().unwrap(), 5);
+    }
+
+    #[test]
+    fn substring_clamps() {
+        let substring = |start: i64, length: Option<i64>| {
+            let mut inputs: Vec<(&dyn InId, Data)> = vec![
+                (&TextSubstringInId::Text, text("héllo")),
+                (&TextSubstringInId::Start, Data::new(start)),
+            ];
+            if let Some(length) = length {
+                inputs.push((&TextSubstringInId::Length, Data::new(length)));
+            }
+
+            fold_text(TextSubstring, inputs, &TextSubstringOutId::Out)
+        };
+
+        assert_eq!(substring(1, Some(3)).unwrap(), "éll");
+        assert_eq!(substring(-2, None).unwrap(), "lo");
+        assert_eq!(substring(3, Some(10)).unwrap(), "lo");
+        assert_eq!(substring(10, None).unwrap(), "");
+        assert_eq!(substring(-10, Some(1)).unwrap(), "h");
+        assert!(substring(0, Some(-1)).is_err());
+    }
+
+    #[test]
+    fn replace_transform_and_test() {
+        let replace = |pattern| {
+            fold_text(
+                TextReplace,
+                vec![
+                    (&TextReplaceInId::Text, text("la la land")),
+                    (&TextReplaceInId::Pattern, text(pattern)),
+                    (&TextReplaceInId::Replacement, text("do")),
+                ],
+                &TextReplaceOutId::Out,
+            )
+        };
+        assert_eq!(replace("la").unwrap(), "do do dond");
+        assert!(replace("").is_err());
+
+        let transform = |transformation, value| {
+            fold_text(
+                TextTransform::new(transformation),
+                vec![(&TextTransformInId::In, text(value))],
+                &TextTransformOutId::Out,
+            )
+            .unwrap()
+        };
+        assert_eq!(transform(TextTransformation::Uppercase, "ça"), "ÇA");
+        assert_eq!(transform(TextTransformation::Lowercase, "ÇA"), "ça");
+        assert_eq!(transform(TextTransformation::Trim, "  a b  "), "a b");
+        assert_eq!(transform(TextTransformation::TrimEnd, "  a  "), "  a");
+        assert_eq!(
+            transform(TextTransformation::Reverse, "ae\u{301}"),
+            "e\u{301}a"
+        );
+
+        let test = |operation, pattern| {
+            fold_node(
+                TextTest::new(operation),
+                vec![
+                    (&TextTestInId::Text, text("hello")),
+                    (&TextTestInId::Pattern, text(pattern)),
+                ],
+                &TextTestOutId::Out,
+            )
+            .unwrap()
+            .as_bool()
+            .unwrap()
+        };
+        assert!(test(TextTestOperation::Contains, "ell"));
+        assert!(test(TextTestOperation::StartsWith, "he"));
+        assert!(!test(TextTestOperation::EndsWith, "he"));
+    }
+
+    #[test]
+    fn repeat_and_pad() {
+        let repeat = |count: i64| {
+            fold_text(
+                TextRepeat,
+                vec![
+                    (&TextRepeatInId::Text, text("ab")),
+                    (&TextRepeatInId::Count, Data::new(count)),
+                ],
+                &TextRepeatOutId::Out,
+            )
+        };
+        assert_eq!(repeat(3).unwrap(), "ababab");
+        assert_eq!(repeat(0).unwrap(), "");
+        assert!(repeat(-1).is_err());
+        assert!(repeat(i64::MAX).is_err());
+
+        let pad = |side, width: i64, fill: Option<&str>| {
+            let mut inputs: Vec<(&dyn InId, Data)> = vec![
+                (&TextPadInId::Text, Data::new(7i64)),
+                (&TextPadInId::Width, Data::new(width)),
+            ];
+            if let Some(fill) = fill {
+                inputs.push((&TextPadInId::Fill, text(fill)));
+            }
+
+            fold_text(TextPad::new(side), inputs, &TextPadOutId::Out)
+        };
+        assert_eq!(pad(PadSide::Start, 3, Some("0")).unwrap(), "007");
+        assert_eq!(pad(PadSide::End, 4, Some("-=")).unwrap(), "7-=-");
+        assert_eq!(pad(PadSide::Start, 2, None).unwrap(), " 7");
+        assert_eq!(pad(PadSide::Start, -2, None).unwrap(), "7");
+        assert!(pad(PadSide::Start, 3, Some("")).is_err());
+    }
+
+    #[test]
+    fn number_text_conversions() {
+        let to_text = |number: Data, decimals: Option<i64>| {
+            let mut inputs: Vec<(&dyn InId, Data)> = vec![(&NumberToTextInId::Number, number)];
+            if let Some(decimals) = decimals {
+                inputs.push((&NumberToTextInId::Decimals, Data::new(decimals)));
+            }
+
+            fold_text(NumberToText, inputs, &NumberToTextOutId::Out)
+        };
+        assert_eq!(to_text(Data::new(1.5f32), None).unwrap(), "1.5");
+        assert_eq!(
+            to_text(Data::new(i64::MAX), None).unwrap(),
+            i64::MAX.to_string()
+        );
+        assert_eq!(to_text(Data::new(2.0f64 / 3.0), Some(2)).unwrap(), "0.67");
+        assert!(to_text(Data::new(1.0f32), Some(-1)).is_err());
+        assert!(to_text(text("1"), None).is_ok());
 
-        // Ok(Data::new(text.split_at(at as usize)))
+        let to_number = |value| {
+            fold_node(
+                TextToNumber,
+                vec![(&TextToNumberInId::Text, text(value))],
+                &TextToNumberOutId::Out,
+            )
+            .map(|data| data.as_f32().unwrap())
+        };
+        assert_eq!(to_number(" -2.5 ").unwrap(), -2.5);
+        assert_eq!(to_number("1e3").unwrap(), 1000.0);
+        assert!(to_number("12abc").is_err());
+        assert!(to_number("").is_err());
     }
 }
@@ -0,0 +1,47 @@
+use std::fmt::{Debug, Display};
+
+/// An error found while parsing a textual input, like the formula of an
+/// [`Expression`](crate::expression::Expression)
+///
+/// The position is the index of the offending character (not byte) in the input
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    position: usize,
+    message: String,
+}
+
+impl ParseError {
+    pub fn new(position: usize, message: impl Into<String>) -> Self {
+        Self {
+            position,
+            message: message.into(),
+        }
+    }
+
+    /// The index of the character where the error was found
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// Show the input with a caret pointing to the error, on two lines
+    /// ```
+    /// # use quakk::ParseError;
+    /// let error = ParseError::new(4, "unexpected `)`");
+    /// assert_eq!(error.pointing("1 + )"), "1 + )\n    ^ unexpected `)`");
+    /// ```
+    pub fn pointing(&self, input: &str) -> String {
+        format!("{input}\n{}^ {}", " ".repeat(self.position), self.message)
+    }
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} at position {}", self.message, self.position)
+    }
+}
+
+impl std::error::Error for ParseError {}
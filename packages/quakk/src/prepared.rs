@@ -0,0 +1,421 @@
+//! Preparing an output of a graph for realtime evaluation, see [`Prepared`]
+
+use std::{
+    collections::HashMap,
+    rc::Rc,
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+use anyhow::{Context, anyhow};
+
+use crate::{
+    Data, Graph, GraphOutInId, GraphOutOutId, LasyFold, Meta, Node, SubgraphInId,
+    id::{InId, NodeId, NodeOutId, OutId},
+};
+
+/// Where an input of a planned node gets its value from
+#[derive(Debug)]
+enum Input {
+    /// An output of another planned node, by index
+    Node(usize, Box<dyn OutId>),
+    /// Nothing provides the value, folding the input is an error with this message
+    Missing(String),
+}
+
+#[derive(Debug)]
+struct PlannedNode {
+    node_id: NodeId,
+    node: Rc<Box<dyn Node>>,
+    inputs: Vec<(Box<dyn InId>, Input)>,
+}
+
+/// The nodes needed by an output, each inbound edge being resolved to another planned node
+#[derive(Debug, Default)]
+pub(crate) struct Plan {
+    nodes: Vec<PlannedNode>,
+}
+
+impl Plan {
+    pub(crate) fn node_id(&self, index: usize) -> NodeId {
+        self.nodes[index].node_id
+    }
+
+    fn input(&self, index: usize, in_id: &dyn InId) -> Option<&Input> {
+        self.nodes[index]
+            .inputs
+            .iter()
+            .find(|(planned, _)| &**planned == in_id)
+            .map(|(_, input)| input)
+    }
+
+    pub(crate) fn is_patched(&self, index: usize, in_id: &dyn InId) -> bool {
+        self.input(index, in_id).is_some()
+    }
+
+    /// Fold an input of the node at `index`
+    pub(crate) fn get_in(
+        self: &Rc<Self>,
+        index: usize,
+        in_id: &dyn InId,
+        meta: Meta,
+    ) -> anyhow::Result<Data> {
+        match self.input(index, in_id) {
+            Some(input) => self.fold_input(input, meta),
+            None => Err(anyhow!(
+                "The node does not have any inbound edge for InId `{in_id:?}`"
+            )),
+        }
+    }
+
+    fn fold_input(self: &Rc<Self>, input: &Input, meta: Meta) -> anyhow::Result<Data> {
+        match input {
+            Input::Node(source, out_id) => self.nodes[*source].node.fold(
+                &**out_id,
+                LasyFold::planned(self.clone(), *source),
+                meta,
+            ),
+            Input::Missing(message) => Err(anyhow!("{message}")),
+        }
+    }
+}
+
+/// A graph being planned, wrapped by a node of another context unless it is the root graph
+struct Scope {
+    graph: Arc<Mutex<Graph>>,
+    parent: Option<(usize, NodeId)>,
+}
+
+#[derive(Default)]
+struct Planner {
+    plan: Plan,
+    contexts: Vec<Scope>,
+    /// The index of each planned node, by context and id
+    planned: HashMap<(usize, NodeId), usize>,
+    /// The context of the inner graph of each inlined node, by context and id
+    inlined: HashMap<(usize, NodeId), usize>,
+}
+
+impl Planner {
+    fn graph(&self, context: usize) -> MutexGuard<'_, Graph> {
+        self.contexts[context]
+            .graph
+            .lock()
+            .expect("the graph has been poisoned, who was it!?")
+    }
+
+    /// The source of an input of a node
+    fn inbound(
+        &self,
+        context: usize,
+        node_id: NodeId,
+        in_id: &dyn InId,
+    ) -> anyhow::Result<Option<NodeOutId>> {
+        let graph = self.graph(context);
+        let vertex = graph
+            .vertex_for_id(node_id)
+            .context("Could not find a node of an edge")?;
+
+        Ok(vertex.inbound_for(in_id).cloned())
+    }
+
+    /// Resolve the output of the graph in `context` forwarding the graph output named `name`
+    fn resolve_graph_out(&mut self, context: usize, name: &str) -> anyhow::Result<Option<Input>> {
+        let in_id = match GraphOutOutId::from_name(name) {
+            GraphOutOutId::Numeric => GraphOutInId::Numeric,
+            GraphOutOutId::Named(name) => GraphOutInId::Named(name),
+        };
+
+        match self.inbound(context, NodeId::GraphOut, &in_id)? {
+            Some(source) => self.resolve(context, &source).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Resolve an output of a node of the graph in `context` to a planned node, going through
+    /// inlined nodes and graph inputs
+    fn resolve(&mut self, context: usize, source: &NodeOutId) -> anyhow::Result<Input> {
+        let node_id = source.node_id();
+        let name = source.out_id_ref().name();
+
+        if node_id == NodeId::GraphIn {
+            let Some((parent, wrapper)) = self.contexts[context].parent else {
+                return Ok(Input::Missing(format!(
+                    "The graph input `{name}` is not provided"
+                )));
+            };
+
+            return match self.inbound(parent, wrapper, &SubgraphInId::new(&name))? {
+                Some(source) => self.resolve(parent, &source),
+                None => Ok(Input::Missing(format!(
+                    "The subgraph input `{name}` is not patched"
+                ))),
+            };
+        }
+
+        let node = self
+            .graph(context)
+            .handle_for_id(node_id)
+            .context("Could not find the the `OutId` associated with this edge")?
+            .node();
+
+        if !node.forwards_inner_graph() {
+            let index = self.plan_node(context, node_id, node)?;
+            return Ok(Input::Node(
+                index,
+                dyn_clone::clone_box(source.out_id_ref()),
+            ));
+        }
+
+        let inner = match self.inlined.get(&(context, node_id)) {
+            Some(inner) => *inner,
+            None => {
+                let graph = node
+                    .inner_graph()
+                    .context("A node forwarding its inner graph should have one")?;
+                self.contexts.push(Scope {
+                    graph,
+                    parent: Some((context, node_id)),
+                });
+                let inner = self.contexts.len() - 1;
+                self.inlined.insert((context, node_id), inner);
+                inner
+            }
+        };
+
+        Ok(self.resolve_graph_out(inner, &name)?.unwrap_or_else(|| {
+            Input::Missing(format!("The subgraph output `{name}` is not patched"))
+        }))
+    }
+
+    /// Plan a node and the nodes it needs, once per context
+    fn plan_node(
+        &mut self,
+        context: usize,
+        node_id: NodeId,
+        node: Rc<Box<dyn Node>>,
+    ) -> anyhow::Result<usize> {
+        if let Some(index) = self.planned.get(&(context, node_id)) {
+            return Ok(*index);
+        }
+
+        // Planned before its inputs, so that feedback loops end
+        let index = self.plan.nodes.len();
+        self.plan.nodes.push(PlannedNode {
+            node_id,
+            node,
+            inputs: Vec::new(),
+        });
+        self.planned.insert((context, node_id), index);
+
+        let inbound: Vec<(Box<dyn InId>, NodeOutId)> = {
+            let graph = self.graph(context);
+            graph
+                .vertex_for_id(node_id)
+                .context("Could not find a planned node")?
+                .inbound()
+                .map(|(in_id, source)| (dyn_clone::clone_box(in_id), source.clone()))
+                .collect()
+        };
+
+        let mut inputs = Vec::with_capacity(inbound.len());
+        for (in_id, source) in inbound {
+            let input = self.resolve(context, &source)?;
+            inputs.push((in_id, input));
+        }
+        self.plan.nodes[index].inputs = inputs;
+
+        Ok(index)
+    }
+}
+
+/// An output of a graph prepared for realtime evaluation, e.g. on an audio thread
+///
+/// Preparing resolves every edge the output needs ahead of time, inlining subgraphs, so that
+/// folding a prepared output :
+/// - never locks the graph
+/// - does not allocate by itself, inputs being found without cloning any id
+/// - only visits the nodes reachable from the output, each input read costing a scan of the
+///   inputs of its node
+///
+/// Nodes must do their share : scalars are held inline in [`Data`], and nodes folding to buffers,
+/// like audio blocks, write over the buffers of their last value once every reader dropped it,
+/// see [`NodeState::step_reusing`](crate::NodeState::step_reusing). Nodes creating texts or lists
+/// still allocate, and nodes folding graphs of their own, like list nodes, lock them. Edits to
+/// the graph are only seen once the output is prepared again.
+///
+/// A `Prepared` shares the nodes of the graph through [`Rc`], so it is not [`Send`] : it must be
+/// prepared on the thread folding it. As preparing allocates, an audio thread should prepare
+/// again between two blocks when the graph changed, rather than in the middle of one.
+/// ```
+/// # use quakk::{GraphOutInId, GraphOutOutId, Quakk, numeric::*};
+/// let quakk = Quakk::new();
+/// {
+///     let mut graph = quakk.graph.lock().unwrap();
+///     let number = graph.insert(Box::new(NumericConstant::new(4.0)));
+///     let out = graph.graph_out_in_id(&GraphOutInId::Numeric).unwrap();
+///     graph
+///         .patch(number.node_out_id(&NumericConstantOutId::Out).unwrap(), out)
+///         .unwrap();
+/// }
+///
+/// let prepared = quakk.prepare(GraphOutOutId::Numeric).unwrap();
+/// let value = prepared.fold(quakk.base_meta).unwrap();
+/// assert_eq!(value.as_f32().unwrap(), 4.0);
+/// ```
+#[derive(Debug)]
+pub struct Prepared {
+    plan: Rc<Plan>,
+    output: Input,
+}
+
+impl Prepared {
+    pub(crate) fn new(
+        graph: Arc<Mutex<Graph>>,
+        graph_out_out_id: GraphOutOutId,
+    ) -> anyhow::Result<Self> {
+        let mut planner = Planner::default();
+        planner.contexts.push(Scope {
+            graph,
+            parent: None,
+        });
+
+        let name = graph_out_out_id.name();
+        let output = planner
+            .resolve_graph_out(0, &name)?
+            .ok_or(anyhow!("The graph output `{name}` is not patched"))?;
+
+        Ok(Self {
+            plan: Rc::new(planner.plan),
+            output,
+        })
+    }
+
+    /// Fold the output
+    pub fn fold(&self, meta: Meta) -> anyhow::Result<Data> {
+        self.plan
+            .fold_input(&self.output, meta)
+            .context("Could not evaluate the graph")
+    }
+
+    /// The count of nodes the output needs, inlined nodes not counting
+    pub fn node_count(&self) -> usize {
+        self.plan.nodes.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        GraphInOutId, Quakk, Subgraph, SubgraphOutId,
+        numeric::{
+            ArithmeticOperation, Arithmetics, ArithmeticsInId, ArithmeticsOutId, NumericConstant,
+            NumericConstantOutId,
+        },
+    };
+
+    /// A graph folding `(2 + 3) * 4`, the multiplication being in a subgraph, with an unused node
+    fn quakk() -> Quakk {
+        let subgraph = Subgraph::new();
+        {
+            let mut inner = subgraph.graph();
+            let mut inner = inner.lock().unwrap();
+            let multiply = inner.insert(Box::new(Arithmetics::new(
+                ArithmeticOperation::Multiplication,
+            )));
+            for (name, in_id) in [("a", ArithmeticsInId::Term1), ("b", ArithmeticsInId::Term2)] {
+                let graph_in = inner.graph_in_out_id(&GraphInOutId::named(name)).unwrap();
+                inner
+                    .patch(graph_in, multiply.node_in_id(&in_id).unwrap())
+                    .unwrap();
+            }
+            let product = inner
+                .graph_out_in_id(&GraphOutInId::named("product"))
+                .unwrap();
+            inner
+                .patch(
+                    multiply.node_out_id(&ArithmeticsOutId::Out).unwrap(),
+                    product,
+                )
+                .unwrap();
+        }
+
+        let quakk = Quakk::new();
+        {
+            let mut graph = quakk.graph.lock().unwrap();
+            let mut constant = |value: f32| {
+                graph
+                    .insert(Box::new(NumericConstant::new(value)))
+                    .node_out_id(&NumericConstantOutId::Out)
+                    .unwrap()
+            };
+            let (two, three, four) = (constant(2.0), constant(3.0), constant(4.0));
+            constant(5.0);
+
+            let add = graph.insert(Box::new(Arithmetics::new(ArithmeticOperation::Addition)));
+            let subgraph = graph.insert(Box::new(subgraph));
+            let edges = [
+                (two, add.node_in_id(&ArithmeticsInId::Term1)),
+                (three, add.node_in_id(&ArithmeticsInId::Term2)),
+                (
+                    add.node_out_id(&ArithmeticsOutId::Out).unwrap(),
+                    subgraph.node_in_id(&SubgraphInId::new("a")),
+                ),
+                (four, subgraph.node_in_id(&SubgraphInId::new("b"))),
+                (
+                    subgraph
+                        .node_out_id(&SubgraphOutId::new("product"))
+                        .unwrap(),
+                    graph.graph_out_in_id(&GraphOutInId::Numeric),
+                ),
+            ];
+            for (out, node_in_id) in edges {
+                graph.patch(out, node_in_id.unwrap()).unwrap();
+            }
+        }
+
+        quakk
+    }
+
+    #[test]
+    fn prepared_outputs_fold_like_the_graph() {
+        let quakk = quakk();
+        let prepared = quakk.prepare(GraphOutOutId::Numeric).unwrap();
+
+        assert_eq!(
+            quakk
+                .fold_for(GraphOutOutId::Numeric)
+                .unwrap()
+                .as_f32()
+                .unwrap(),
+            20.0
+        );
+        // The subgraph is inlined and the unused constant left out
+        assert_eq!(prepared.node_count(), 5);
+
+        // Folding never locks the graph
+        let _graph = quakk.graph.lock().unwrap();
+        let value = prepared.fold(quakk.base_meta).unwrap();
+        assert_eq!(value.as_f32().unwrap(), 20.0);
+    }
+
+    #[test]
+    fn missing_values_are_errors() {
+        let quakk = Quakk::new();
+        let error = quakk.prepare(GraphOutOutId::named("Left")).unwrap_err();
+        assert_eq!(error.to_string(), "The graph output `Left` is not patched");
+
+        {
+            let mut graph = quakk.graph.lock().unwrap();
+            let graph_in = graph.graph_in_out_id(&GraphInOutId::Numeric).unwrap();
+            let out = graph.graph_out_in_id(&GraphOutInId::Numeric).unwrap();
+            graph.patch(graph_in, out).unwrap();
+        }
+        let prepared = quakk.prepare(GraphOutOutId::Numeric).unwrap();
+        let error = prepared.fold(quakk.base_meta).unwrap_err();
+        assert_eq!(
+            format!("{error:#}"),
+            "Could not evaluate the graph: The graph input `Numeric` is not provided"
+        );
+    }
+}
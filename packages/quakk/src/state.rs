@@ -0,0 +1,208 @@
+//! [`NodeState`] lets a node keep a state from one tick to the next
+
+use std::cell::RefCell;
+
+use anyhow::anyhow;
+
+use crate::{Data, Meta};
+
+#[derive(Debug, Default)]
+struct Inner<S> {
+    state: S,
+    last: Option<(u64, Data)>,
+}
+
+/// A state kept by a node across ticks, like the phase of an oscillator
+///
+/// [`Node::fold`](crate::Node::fold) only gets `&self`, and a node may be folded several times a
+/// tick when many nodes read its outputs. `NodeState` steps the state once per tick and caches
+/// the result, so every reader sees the same value and the state advances at the right pace.
+/// ```
+/// # use quakk::{Data, NodeState, Quakk};
+/// let mut meta = Quakk::new().base_meta;
+/// let counter = NodeState::new(0i64);
+/// let step = |count: &mut i64| {
+///     *count += 1;
+///     Ok(Data::new(*count))
+/// };
+///
+/// assert_eq!(counter.step(meta, step).unwrap().as_i64().unwrap(), 1);
+/// assert_eq!(counter.step(meta, step).unwrap().as_i64().unwrap(), 1);
+///
+/// meta.tick += 1;
+/// assert_eq!(counter.step(meta, step).unwrap().as_i64().unwrap(), 2);
+/// ```
+#[derive(Debug, Default)]
+pub struct NodeState<S> {
+    inner: RefCell<Inner<S>>,
+}
+
+impl<S> NodeState<S> {
+    pub fn new(state: S) -> Self {
+        Self {
+            inner: RefCell::new(Inner { state, last: None }),
+        }
+    }
+
+    /// Step the state with `step` if it has not been stepped yet this tick, and return the value
+    /// computed for this tick
+    ///
+    /// Stepping a state from inside its own `step`, which happens when the output of a node is
+    /// patched back to one of its inputs, is an error rather than a panic
+    pub fn step(
+        &self,
+        meta: Meta,
+        step: impl FnOnce(&mut S) -> anyhow::Result<Data>,
+    ) -> anyhow::Result<Data> {
+        self.step_reusing(meta, |state, _| step(state))
+    }
+
+    /// Same as [`NodeState::step`], `step` being also given the value of the last tick, so that
+    /// it can write the value of this tick in the same buffers with [`Data::make_mut`]
+    ///
+    /// The buffers are only written in place once every reader of the last value dropped it,
+    /// which lets nodes fold to blocks of audio without allocating.
+    pub fn step_reusing(
+        &self,
+        meta: Meta,
+        step: impl FnOnce(&mut S, Option<Data>) -> anyhow::Result<Data>,
+    ) -> anyhow::Result<Data> {
+        let mut inner = self.inner.try_borrow_mut().map_err(|_| {
+            anyhow!(
+                "the node is folded while computing its own output, a feedback loop needs a delay"
+            )
+        })?;
+
+        if let Some((tick, data)) = &inner.last
+            && *tick == meta.tick
+        {
+            return Ok(data.clone());
+        }
+
+        let last = inner.last.take().map(|(_, data)| data);
+        let data = step(&mut inner.state, last)?;
+        inner.last = Some((meta.tick, data.clone()));
+
+        Ok(data)
+    }
+
+    /// Replace the state, the next call to [`NodeState::step`] steps it even on the same tick
+    pub fn reset(&self, state: S) {
+        if let Ok(mut inner) = self.inner.try_borrow_mut() {
+            *inner = Inner { state, last: None };
+        }
+    }
+
+    /// Read the state, `None` while it is being stepped
+    pub fn with<T>(&self, read: impl FnOnce(&S) -> T) -> Option<T> {
+        self.inner.try_borrow().ok().map(|inner| read(&inner.state))
+    }
+}
+
+/// A position moving forward with the ticks, like a count of beats, kept in a [`NodeState`]
+///
+/// The position moves by the ticks elapsed since the last one rather than by the count of folds,
+/// so a node left unfolded for a while, or folded at ticks far apart, does not fall behind. The
+/// rate is given on each move, so changing it keeps the position going from where it is.
+/// ```
+/// # use quakk::Progress;
+/// let mut beats = Progress::default();
+/// assert_eq!(beats.advance(4, 0.5), 2.0);
+/// // Twice as fast from tick 4
+/// assert_eq!(beats.advance(6, 1.0), 4.0);
+/// ```
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Progress {
+    position: f64,
+    /// The tick of `position`
+    tick: u64,
+}
+
+impl Progress {
+    /// Move forward to `tick` by `rate` per tick since the last tick, returning the position
+    ///
+    /// Ticks before the last one do not move the position, nor does a NaN or infinite rate,
+    /// which would leave it NaN for good.
+    pub fn advance(&mut self, tick: u64, rate: f64) -> f64 {
+        let step = tick.saturating_sub(self.tick) as f64 * rate;
+        if step.is_finite() {
+            self.position += step;
+        }
+        self.tick = self.tick.max(tick);
+
+        self.position
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::meta_at;
+
+    #[test]
+    fn steps_once_per_tick() {
+        let state = NodeState::new(0i64);
+        let step = |count: &mut i64| {
+            *count += 1;
+            Ok(Data::new(*count))
+        };
+
+        assert_eq!(state.step(meta_at(0), step).unwrap().as_i64().unwrap(), 1);
+        assert_eq!(state.step(meta_at(0), step).unwrap().as_i64().unwrap(), 1);
+        assert_eq!(state.step(meta_at(1), step).unwrap().as_i64().unwrap(), 2);
+        assert_eq!(state.with(|count| *count), Some(2));
+
+        state.reset(10);
+        assert_eq!(state.step(meta_at(1), step).unwrap().as_i64().unwrap(), 11);
+    }
+
+    #[test]
+    fn steps_reuse_the_last_value() {
+        let state = NodeState::new(());
+        let step = |_: &mut (), last: Option<Data>| {
+            let mut data = last.unwrap_or_else(|| Data::new(vec![0i64]));
+            data.make_mut::<Vec<i64>>().unwrap()[0] += 1;
+            Ok(data)
+        };
+
+        let first = state.step_reusing(meta_at(0), step).unwrap();
+        let first_ptr = first.downcast_ref::<Vec<i64>>().unwrap().as_ptr();
+        drop(first);
+
+        let second = state.step_reusing(meta_at(1), step).unwrap();
+        assert_eq!(second.downcast_ref::<Vec<i64>>().unwrap(), &[2]);
+        assert_eq!(
+            second.downcast_ref::<Vec<i64>>().unwrap().as_ptr(),
+            first_ptr
+        );
+
+        // A value still read is not written over
+        let third = state.step_reusing(meta_at(2), step).unwrap();
+        assert_eq!(second.downcast_ref::<Vec<i64>>().unwrap(), &[2]);
+        assert_eq!(third.downcast_ref::<Vec<i64>>().unwrap(), &[3]);
+    }
+
+    #[test]
+    fn progress_follows_the_ticks() {
+        let mut progress = Progress::default();
+
+        assert_eq!(progress.advance(0, 0.5), 0.0);
+        assert_eq!(progress.advance(4, 0.5), 2.0);
+        assert_eq!(progress.advance(5, 2.0), 4.0);
+        assert_eq!(progress.advance(3, 2.0), 4.0);
+
+        assert_eq!(progress.advance(6, f64::NAN), 4.0);
+        assert_eq!(progress.advance(7, f64::INFINITY), 4.0);
+        assert_eq!(progress.advance(8, 1.0), 5.0);
+    }
+
+    #[test]
+    fn reentrant_step_is_an_error() {
+        let state = NodeState::new(());
+
+        let result = state.step(meta_at(0), |_| {
+            state.step(meta_at(0), |_| Ok(Data::new(())))
+        });
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,207 @@
+//! Helpers to fold a single node in isolation, feeding its inputs with constant values
+
+use std::sync::{Arc, Mutex};
+
+use anyhow::anyhow;
+
+use crate::{
+    Data, Graph, LasyFold, Meta, Node, NodeHandle, Quality,
+    id::{InId, NodeId, NodeInId, NodeOutId, OutId},
+};
+
+/// A node always folding to the same [`Data`]
+#[derive(Debug)]
+pub(crate) struct Constant {
+    value: Data,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) enum ConstantOutId {
+    Out,
+}
+
+impl OutId for ConstantOutId {}
+
+impl Constant {
+    pub(crate) fn new(value: Data) -> Self {
+        Self { value }
+    }
+}
+
+impl Node for Constant {
+    fn initialize() -> Self {
+        Self {
+            value: Data::new(0.0f32),
+        }
+    }
+
+    fn title(&self) -> &str {
+        "Constant"
+    }
+
+    fn fold(&self, _out_id: &dyn OutId, _lasy_fold: LasyFold, _meta: Meta) -> anyhow::Result<Data> {
+        Ok(self.value.clone())
+    }
+
+    fn node_in_id(&self, _in_id: &dyn InId, _node_id: NodeId) -> Option<NodeInId> {
+        None
+    }
+
+    fn node_out_id(&self, out_id: &dyn OutId, node_id: NodeId) -> Option<NodeOutId> {
+        out_id
+            .as_any()
+            .downcast_ref::<ConstantOutId>()
+            .map(|out_id| NodeOutId::new(node_id, out_id))
+    }
+
+    fn in_ids(&self) -> Vec<Box<dyn InId>> {
+        Vec::new()
+    }
+
+    fn out_ids(&self) -> Vec<Box<dyn OutId>> {
+        vec![Box::new(ConstantOutId::Out)]
+    }
+}
+
+/// A node always failing to fold, to check an input is not folded
+#[derive(Debug)]
+pub(crate) struct Failing;
+
+impl Node for Failing {
+    fn initialize() -> Self {
+        Self
+    }
+
+    fn title(&self) -> &str {
+        "Failing"
+    }
+
+    fn fold(&self, _out_id: &dyn OutId, _lasy_fold: LasyFold, _meta: Meta) -> anyhow::Result<Data> {
+        Err(anyhow!("this node should not have been folded"))
+    }
+
+    fn node_in_id(&self, _in_id: &dyn InId, _node_id: NodeId) -> Option<NodeInId> {
+        None
+    }
+
+    fn node_out_id(&self, out_id: &dyn OutId, node_id: NodeId) -> Option<NodeOutId> {
+        out_id
+            .as_any()
+            .downcast_ref::<ConstantOutId>()
+            .map(|out_id| NodeOutId::new(node_id, out_id))
+    }
+
+    fn in_ids(&self) -> Vec<Box<dyn InId>> {
+        Vec::new()
+    }
+
+    fn out_ids(&self) -> Vec<Box<dyn OutId>> {
+        vec![Box::new(ConstantOutId::Out)]
+    }
+}
+
+pub(crate) fn meta_at(tick: u64) -> Meta {
+    Meta {
+        tick,
+        quality: Quality::Balanced,
+        tick_rate: 60.0,
+        sample_rate: 48_000,
+        block_size: 800,
+    }
+}
+
+/// Fold `out_id` of `node`, each of the given inputs being patched to a constant value
+pub(crate) fn fold_node(
+    node: impl Node + 'static,
+    inputs: Vec<(&dyn InId, Data)>,
+    out_id: &dyn OutId,
+) -> anyhow::Result<Data> {
+    fold_node_at(node, inputs, out_id, meta_at(0))
+}
+
+/// Same as [`fold_node`], with a given [`Meta`]
+pub(crate) fn fold_node_at(
+    node: impl Node + 'static,
+    inputs: Vec<(&dyn InId, Data)>,
+    out_id: &dyn OutId,
+    meta: Meta,
+) -> anyhow::Result<Data> {
+    let inputs = inputs
+        .into_iter()
+        .map(|(in_id, value)| (in_id, Box::new(Constant::new(value)) as Box<dyn Node>))
+        .collect();
+
+    fold_node_with(node, inputs, out_id, meta)
+}
+
+/// Fold `out_id` of `node`, each of the given inputs being patched to the [`ConstantOutId::Out`]
+/// output of the given node
+pub(crate) fn fold_node_with(
+    node: impl Node + 'static,
+    inputs: Vec<(&dyn InId, Box<dyn Node>)>,
+    out_id: &dyn OutId,
+    meta: Meta,
+) -> anyhow::Result<Data> {
+    let (graph, node_handle) = graph_with(node, inputs);
+
+    node_handle.node().fold(
+        out_id,
+        LasyFold::new(node_handle.node_id(), graph.clone()),
+        meta,
+    )
+}
+
+/// Fold `out_id` of the same `node` at each of `ticks`, for nodes keeping a state across ticks
+pub(crate) fn fold_node_over(
+    node: impl Node + 'static,
+    inputs: Vec<(&dyn InId, Data)>,
+    out_id: &dyn OutId,
+    ticks: impl IntoIterator<Item = u64>,
+) -> anyhow::Result<Vec<Data>> {
+    let inputs = inputs
+        .into_iter()
+        .map(|(in_id, value)| (in_id, Box::new(Constant::new(value)) as Box<dyn Node>))
+        .collect();
+    let (graph, node_handle) = graph_with(node, inputs);
+
+    ticks
+        .into_iter()
+        .map(|tick| {
+            node_handle.node().fold(
+                out_id,
+                LasyFold::new(node_handle.node_id(), graph.clone()),
+                meta_at(tick),
+            )
+        })
+        .collect()
+}
+
+/// A graph holding `node`, each of the given inputs being patched to the
+/// [`ConstantOutId::Out`] output of the given node
+fn graph_with(
+    node: impl Node + 'static,
+    inputs: Vec<(&dyn InId, Box<dyn Node>)>,
+) -> (Arc<Mutex<Graph>>, NodeHandle) {
+    let graph = Arc::new(Mutex::new(Graph::new()));
+
+    let node_handle = {
+        let mut graph = graph.lock().unwrap();
+        let node_handle = graph.insert(Box::new(node));
+
+        for (in_id, input_node) in inputs {
+            let input_handle = graph.insert(input_node);
+            graph
+                .patch(
+                    input_handle.node_out_id(&ConstantOutId::Out).unwrap(),
+                    node_handle
+                        .node_in_id(in_id)
+                        .expect("the node should accept this input"),
+                )
+                .unwrap();
+        }
+
+        node_handle
+    };
+
+    (graph, node_handle)
+}
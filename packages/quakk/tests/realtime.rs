@@ -0,0 +1,137 @@
+//! Folding a prepared output must not allocate, checked with an instrumented global allocator
+//!
+//! The allocator counts allocations of every thread, so this file holds a single test.
+
+use std::alloc::System;
+
+use quakk::{
+    GraphInOutId, GraphOutInId, GraphOutOutId, Meta, Quakk, Subgraph, SubgraphInId, SubgraphOutId,
+    clock::{Clock, ClockInId, ClockOutId, Division},
+    numeric::{
+        ArithmeticOperation, Arithmetics, ArithmeticsInId, ArithmeticsOutId, NumericConstant,
+        NumericConstantOutId,
+    },
+    sequence::{Step, StepSequencer, StepSequencerInId, StepSequencerOutId},
+};
+use stats_alloc::{INSTRUMENTED_SYSTEM, Region, StatsAlloc};
+
+#[global_allocator]
+static GLOBAL: &StatsAlloc<System> = &INSTRUMENTED_SYSTEM;
+
+/// Run `f`, panicking if it allocated
+fn assert_no_allocation<T>(f: impl FnOnce() -> T) -> T {
+    let region = Region::new(GLOBAL);
+    let value = f();
+    let stats = region.change();
+
+    assert_eq!(
+        (stats.allocations, stats.reallocations),
+        (0, 0),
+        "the fold allocated"
+    );
+
+    value
+}
+
+/// A clock driving a sequencer, the value of each step being doubled in a subgraph
+fn quakk() -> Quakk {
+    let subgraph = Subgraph::new();
+    {
+        let inner = subgraph.graph();
+        let mut inner = inner.lock().unwrap();
+        let two = inner.insert(Box::new(NumericConstant::new(2.0)));
+        let double = inner.insert(Box::new(Arithmetics::new(
+            ArithmeticOperation::Multiplication,
+        )));
+        let value = inner
+            .graph_in_out_id(&GraphInOutId::named("value"))
+            .unwrap();
+        let doubled = inner
+            .graph_out_in_id(&GraphOutInId::named("doubled"))
+            .unwrap();
+
+        let edges = [
+            (value, double.node_in_id(&ArithmeticsInId::Term1).unwrap()),
+            (
+                two.node_out_id(&NumericConstantOutId::Out).unwrap(),
+                double.node_in_id(&ArithmeticsInId::Term2).unwrap(),
+            ),
+            (double.node_out_id(&ArithmeticsOutId::Out).unwrap(), doubled),
+        ];
+        for (out, node_in_id) in edges {
+            inner.patch(out, node_in_id).unwrap();
+        }
+    }
+
+    let quakk = Quakk::new();
+    {
+        let mut graph = quakk.graph.lock().unwrap();
+        let bpm = graph.insert(Box::new(NumericConstant::new(140.0)));
+        let clock = graph.insert(Box::new(Clock::new(Division::new(1, 4))));
+        let steps = [1.0, 2.0, 3.0, 4.0].into_iter().map(Step::new).collect();
+        let sequencer = graph.insert(Box::new(StepSequencer::new(steps, 7)));
+        let subgraph = graph.insert(Box::new(subgraph));
+        let out = graph.graph_out_in_id(&GraphOutInId::Numeric).unwrap();
+
+        let edges = [
+            (
+                bpm.node_out_id(&NumericConstantOutId::Out).unwrap(),
+                clock.node_in_id(&ClockInId::Bpm).unwrap(),
+            ),
+            (
+                clock.node_out_id(&ClockOutId::Beat).unwrap(),
+                sequencer.node_in_id(&StepSequencerInId::Beat).unwrap(),
+            ),
+            (
+                sequencer.node_out_id(&StepSequencerOutId::Value).unwrap(),
+                subgraph.node_in_id(&SubgraphInId::new("value")).unwrap(),
+            ),
+            (
+                subgraph
+                    .node_out_id(&SubgraphOutId::new("doubled"))
+                    .unwrap(),
+                out,
+            ),
+        ];
+        for (out, node_in_id) in edges {
+            graph.patch(out, node_in_id).unwrap();
+        }
+    }
+
+    quakk
+}
+
+#[test]
+fn prepared_fold_does_not_allocate() {
+    let quakk = quakk();
+    let prepared = quakk.prepare(GraphOutOutId::Numeric).unwrap();
+    let meta = |tick| Meta {
+        tick,
+        ..quakk.base_meta
+    };
+
+    // Folds the same value as the locked path, which allocates on each fold
+    assert_eq!(
+        prepared.fold(meta(0)).unwrap().as_f32().unwrap(),
+        quakk
+            .fold_with(GraphOutOutId::Numeric, meta(0))
+            .unwrap()
+            .as_f32()
+            .unwrap()
+    );
+
+    let mut values = [0.0; 120];
+    assert_no_allocation(|| {
+        for (tick, value) in values.iter_mut().enumerate() {
+            *value = prepared
+                .fold(meta(tick as u64 + 1))
+                .unwrap()
+                .as_f32()
+                .unwrap();
+        }
+    });
+
+    // A beat at 140 BPM lasts about 26 ticks, each step being a quarter of it
+    assert_eq!(values[0], 2.0);
+    assert!(values.contains(&8.0));
+}
@@ -0,0 +1,254 @@
+use std::cell::RefCell;
+
+use anyhow::anyhow;
+use quakk::Data;
+
+/// A block of audio samples, one buffer per channel
+///
+/// Audio nodes fold to an `AudioBlock` of [`Meta::block_size`](quakk::Meta::block_size) frames,
+/// carried in a [`Data`](quakk::Data) like any other value. Samples are nominally between `-1.0`
+/// and `1.0`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AudioBlock {
+    channels: Vec<Vec<f32>>,
+    sample_rate: u32,
+}
+
+impl AudioBlock {
+    /// A silent block
+    pub fn new(channel_count: usize, frames: usize, sample_rate: u32) -> Self {
+        Self {
+            channels: vec![vec![0.0; frames]; channel_count],
+            sample_rate,
+        }
+    }
+
+    /// A block computing each sample with `sample(channel, frame)`
+    pub fn from_fn(
+        channel_count: usize,
+        frames: usize,
+        sample_rate: u32,
+        mut sample: impl FnMut(usize, usize) -> f32,
+    ) -> Self {
+        let channels = (0..channel_count)
+            .map(|channel| (0..frames).map(|frame| sample(channel, frame)).collect())
+            .collect();
+
+        Self {
+            channels,
+            sample_rate,
+        }
+    }
+
+    /// A block in a [`Data`], written by `write` in the buffers of `last` when it holds a block
+    /// that nothing else holds anymore, see [`NodeState::step_reusing`]
+    ///
+    /// Writing with [`AudioBlock::fill_with`] then only allocates when the block grows.
+    ///
+    /// [`NodeState::step_reusing`]: quakk::NodeState::step_reusing
+    pub fn reuse(last: Option<Data>, write: impl FnOnce(&mut AudioBlock)) -> Data {
+        let mut data = last
+            .filter(|last| last.downcast_ref::<AudioBlock>().is_some())
+            .unwrap_or_else(|| Data::new(AudioBlock::new(0, 0, 0)));
+
+        write(
+            data.make_mut::<AudioBlock>()
+                .expect("the data should hold an AudioBlock"),
+        );
+
+        data
+    }
+
+    /// Compute each sample with `sample(channel, frame)` like [`AudioBlock::from_fn`], keeping
+    /// the buffers of the block
+    pub fn fill_with(
+        &mut self,
+        channel_count: usize,
+        frames: usize,
+        sample_rate: u32,
+        mut sample: impl FnMut(usize, usize) -> f32,
+    ) {
+        self.channels.resize_with(channel_count, Vec::new);
+        for (channel, samples) in self.channels.iter_mut().enumerate() {
+            samples.clear();
+            samples.extend((0..frames).map(|frame| sample(channel, frame)));
+        }
+        self.sample_rate = sample_rate;
+    }
+
+    /// A block from the buffers of its channels, which must all hold the same count of frames
+    pub fn from_channels(channels: Vec<Vec<f32>>, sample_rate: u32) -> anyhow::Result<Self> {
+        if let Some(first) = channels.first()
+            && let Some(other) = channels.iter().find(|channel| channel.len() != first.len())
+        {
+            return Err(anyhow!(
+                "all channels should hold the same count of frames, found {} and {}",
+                first.len(),
+                other.len()
+            ));
+        }
+
+        Ok(Self {
+            channels,
+            sample_rate,
+        })
+    }
+
+    pub fn channel_count(&self) -> usize {
+        self.channels.len()
+    }
+
+    /// The count of samples in each channel
+    pub fn frames(&self) -> usize {
+        self.channels.first().map(Vec::len).unwrap_or(0)
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    pub fn channel(&self, channel: usize) -> Option<&[f32]> {
+        self.channels.get(channel).map(Vec::as_slice)
+    }
+
+    pub fn channel_mut(&mut self, channel: usize) -> Option<&mut [f32]> {
+        self.channels.get_mut(channel).map(Vec::as_mut_slice)
+    }
+
+    pub fn channels(&self) -> &[Vec<f32>] {
+        &self.channels
+    }
+
+    pub fn into_channels(self) -> Vec<Vec<f32>> {
+        self.channels
+    }
+
+    /// A sample of the block, channels past the last one wrap around, so a mono block reads the
+    /// same on every channel
+    pub fn sample(&self, channel: usize, frame: usize) -> f32 {
+        if self.channels.is_empty() {
+            return 0.0;
+        }
+
+        self.channels[channel % self.channels.len()]
+            .get(frame)
+            .copied()
+            .unwrap_or(0.0)
+    }
+
+    /// Append the frames of `other` at the end of the block, `other` must have the same channel
+    /// count and sample rate
+    pub fn append(&mut self, other: &AudioBlock) -> anyhow::Result<()> {
+        if self.channel_count() != other.channel_count() {
+            return Err(anyhow!(
+                "cannot append a block of {} channels to a block of {} channels",
+                other.channel_count(),
+                self.channel_count()
+            ));
+        }
+        if self.sample_rate != other.sample_rate {
+            return Err(anyhow!(
+                "cannot append a block at {} Hz to a block at {} Hz",
+                other.sample_rate,
+                self.sample_rate
+            ));
+        }
+
+        for (channel, other) in self.channels.iter_mut().zip(&other.channels) {
+            channel.extend_from_slice(other);
+        }
+
+        Ok(())
+    }
+
+    /// Keep only the first `frames` frames
+    pub fn truncate(&mut self, frames: usize) {
+        for channel in &mut self.channels {
+            channel.truncate(frames);
+        }
+    }
+}
+
+/// The last block folded by a node without a [`NodeState`](quakk::NodeState), written over by
+/// the next one once nothing else holds it
+///
+/// A node may be folded several times a tick, each fold writing in a new block while the last
+/// one is still read.
+#[derive(Debug, Default)]
+pub struct BlockBuffer {
+    last: RefCell<Option<Data>>,
+}
+
+impl BlockBuffer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The next block, written by `write`, see [`AudioBlock::reuse`]
+    pub fn write(&self, write: impl FnOnce(&mut AudioBlock)) -> Data {
+        let last = self.last.borrow_mut().take();
+        let data = AudioBlock::reuse(last, write);
+        *self.last.borrow_mut() = Some(data.clone());
+
+        data
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn channels_must_match() {
+        assert!(AudioBlock::from_channels(vec![vec![0.0; 4], vec![0.0; 3]], 48_000).is_err());
+
+        let block = AudioBlock::from_channels(vec![vec![0.5; 4], vec![0.0; 4]], 48_000).unwrap();
+        assert_eq!(block.channel_count(), 2);
+        assert_eq!(block.frames(), 4);
+    }
+
+    #[test]
+    fn mono_wraps_to_every_channel() {
+        let block = AudioBlock::from_fn(1, 4, 48_000, |_, frame| frame as f32);
+
+        assert_eq!(block.sample(0, 2), 2.0);
+        assert_eq!(block.sample(1, 2), 2.0);
+        assert_eq!(block.sample(1, 10), 0.0);
+    }
+
+    #[test]
+    fn buffers_are_written_over_once_dropped() {
+        let buffer = BlockBuffer::new();
+        let write = |value: f32| {
+            buffer.write(|block| block.fill_with(2, 4, 48_000, |channel, _| channel as f32 + value))
+        };
+        let right = |data: &Data| data.downcast_ref::<AudioBlock>().unwrap().channels()[1].clone();
+        let right_ptr =
+            |data: &Data| data.downcast_ref::<AudioBlock>().unwrap().channels()[1].as_ptr();
+
+        // The first block is still read, the second one is written in new buffers
+        let first = write(0.0);
+        let second = write(1.0);
+        assert_eq!(right(&first), [1.0; 4]);
+        assert_eq!(right(&second), [2.0; 4]);
+
+        let second_ptr = right_ptr(&second);
+        drop((first, second));
+        let third = write(2.0);
+        assert_eq!(right(&third), [3.0; 4]);
+        assert_eq!(right_ptr(&third), second_ptr);
+    }
+
+    #[test]
+    fn append_and_truncate() {
+        let mut block = AudioBlock::from_fn(2, 2, 48_000, |channel, _| channel as f32);
+        block.append(&AudioBlock::new(2, 3, 48_000)).unwrap();
+        assert_eq!(block.channel(1).unwrap(), [1.0, 1.0, 0.0, 0.0, 0.0]);
+
+        block.truncate(3);
+        assert_eq!(block.frames(), 3);
+
+        assert!(block.append(&AudioBlock::new(1, 3, 48_000)).is_err());
+        assert!(block.append(&AudioBlock::new(2, 3, 44_100)).is_err());
+    }
+}
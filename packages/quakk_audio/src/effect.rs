@@ -0,0 +1,757 @@
+//! Time based effects : delays, chorus and reverb
+//!
+//! The buffers of an effect are kept in its [`NodeState`], allocated on the first block and again
+//! when the sample rate or the count of channels change.
+
+use std::f64::consts::TAU;
+
+use quakk::{
+    Data, LasyFold, Meta, Node, NodeState,
+    id::{InId, NodeId, NodeInId, NodeOutId, OutId},
+};
+
+use crate::{AudioBlock, Signal};
+
+/// A ring buffer read at a fractional delay
+#[derive(Debug, Clone)]
+struct DelayLine {
+    buffer: Vec<f32>,
+    write: usize,
+}
+
+impl DelayLine {
+    /// A delay line holding up to `length - 1` samples of delay
+    fn new(length: usize) -> Self {
+        Self {
+            buffer: vec![0.0; length.max(2)],
+            write: 0,
+        }
+    }
+
+    /// The sample written `delay` samples ago, interpolated between samples, at least `1`
+    fn read(&self, delay: f64) -> f32 {
+        let length = self.buffer.len();
+        let delay = delay.clamp(1.0, (length - 1) as f64);
+        let whole = delay.floor();
+        let fraction = (delay - whole) as f32;
+
+        let at = |delay: usize| self.buffer[(self.write + length - delay) % length];
+        let newer = at(whole as usize);
+        let older = at((whole as usize + 1).min(length - 1));
+
+        newer + (older - newer) * fraction
+    }
+
+    fn write(&mut self, sample: f32) {
+        self.buffer[self.write] = sample;
+        self.write = (self.write + 1) % self.buffer.len();
+    }
+}
+
+/// A feedback coefficient keeping the loop stable
+fn feedback(signal: &Signal, channel: usize, frame: usize) -> f32 {
+    let feedback = signal.sample(channel, frame);
+    if feedback.is_nan() {
+        0.0
+    } else {
+        feedback.clamp(-0.99, 0.99)
+    }
+}
+
+/// A dry and wet balance, from `0` for the dry signal only to `1` for the wet signal only
+fn mix(signal: &Signal, channel: usize, frame: usize, dry: f32, wet: f32) -> f32 {
+    let mix = signal.sample(channel, frame);
+    let mix = if mix.is_nan() {
+        0.0
+    } else {
+        mix.clamp(0.0, 1.0)
+    };
+
+    dry * (1.0 - mix) + wet * mix
+}
+
+/// Delay lines, one per channel, reallocated when the layout of the audio changes
+#[derive(Debug)]
+struct Lines<C> {
+    sample_rate: u32,
+    channels: Vec<C>,
+}
+
+impl<C> Default for Lines<C> {
+    fn default() -> Self {
+        Self {
+            sample_rate: 0,
+            channels: Vec::new(),
+        }
+    }
+}
+
+impl<C> Lines<C> {
+    fn prepare(&mut self, sample_rate: u32, channel_count: usize, new: impl Fn(usize) -> C) {
+        if self.sample_rate != sample_rate || self.channels.len() != channel_count {
+            self.sample_rate = sample_rate;
+            self.channels = (0..channel_count).map(new).collect();
+        }
+    }
+}
+
+/// The longest time of a [`Delay`], in seconds
+pub const MAX_DELAY: f64 = 10.0;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum DelayInId {
+    In,
+    /// The time between repeats, in seconds, or in beats when `Bpm` is patched, `0.25` when not
+    /// patched
+    Time,
+    /// The tempo the time is synced to, in beats per minute, optional
+    Bpm,
+    /// How much of each repeat is fed back, negative values inverting it, `0.3` when not patched
+    Feedback,
+    /// The balance from the dry signal at `0` to the repeats at `1`, `0.5` when not patched
+    Mix,
+}
+
+impl InId for DelayInId {}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum DelayOutId {
+    Out,
+}
+
+impl OutId for DelayOutId {}
+
+/// A delay line with feedback, each channel of the input being delayed separately
+#[derive(Debug, Default)]
+pub struct Delay {
+    lines: NodeState<Lines<DelayLine>>,
+}
+
+impl Node for Delay {
+    fn initialize() -> Self {
+        Self::default()
+    }
+
+    fn title(&self) -> &str {
+        "Delay"
+    }
+
+    fn fold(&self, _out_id: &dyn OutId, lasy_fold: LasyFold, meta: Meta) -> anyhow::Result<Data> {
+        self.lines.step_reusing(meta, |lines, last| {
+            let input = Signal::get(&lasy_fold, &DelayInId::In, meta)?;
+            let time = Signal::get_or(&lasy_fold, &DelayInId::Time, meta, 0.25)?;
+            let bpm = if lasy_fold.is_patched(&DelayInId::Bpm) {
+                Some(Signal::get(&lasy_fold, &DelayInId::Bpm, meta)?)
+            } else {
+                None
+            };
+            let feedback_signal = Signal::get_or(&lasy_fold, &DelayInId::Feedback, meta, 0.3)?;
+            let mix_signal = Signal::get_or(&lasy_fold, &DelayInId::Mix, meta, 0.5)?;
+
+            let sample_rate = meta.sample_rate as f64;
+            let length = (MAX_DELAY * sample_rate) as usize + 2;
+            lines.prepare(meta.sample_rate, input.channel_count(), |_| {
+                DelayLine::new(length)
+            });
+
+            Ok(AudioBlock::reuse(last, |block| {
+                block.fill_with(
+                    input.channel_count(),
+                    meta.block_size,
+                    meta.sample_rate,
+                    |channel, frame| {
+                        let mut seconds = time.sample(channel, frame) as f64;
+                        if let Some(bpm) = &bpm {
+                            seconds *= 60.0 / bpm.sample(channel, frame) as f64;
+                        }
+                        let seconds = if seconds.is_finite() {
+                            seconds.clamp(0.0, MAX_DELAY)
+                        } else {
+                            0.0
+                        };
+
+                        let line = &mut lines.channels[channel];
+                        let dry = input.sample(channel, frame);
+                        let wet = line.read(seconds * sample_rate);
+                        line.write(dry + wet * feedback(&feedback_signal, channel, frame));
+
+                        mix(&mix_signal, channel, frame, dry, wet)
+                    },
+                )
+            }))
+        })
+    }
+
+    fn node_in_id(&self, in_id: &dyn InId, node_id: NodeId) -> Option<NodeInId> {
+        in_id
+            .as_any()
+            .downcast_ref::<DelayInId>()
+            .map(|in_id| NodeInId::new(node_id, in_id))
+    }
+
+    fn node_out_id(&self, out_id: &dyn OutId, node_id: NodeId) -> Option<NodeOutId> {
+        out_id
+            .as_any()
+            .downcast_ref::<DelayOutId>()
+            .map(|out_id| NodeOutId::new(node_id, out_id))
+    }
+
+    fn in_ids(&self) -> Vec<Box<dyn InId>> {
+        vec![
+            Box::new(DelayInId::In),
+            Box::new(DelayInId::Time),
+            Box::new(DelayInId::Bpm),
+            Box::new(DelayInId::Feedback),
+            Box::new(DelayInId::Mix),
+        ]
+    }
+
+    fn out_ids(&self) -> Vec<Box<dyn OutId>> {
+        vec![Box::new(DelayOutId::Out)]
+    }
+}
+
+/// The sound of a [`Chorus`], setting the range of its modulated delay
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ModulationKind {
+    /// A delay sweeping between 10 and 30 ms, thickening the sound like several voices
+    #[default]
+    Chorus,
+    /// A delay sweeping between 0.5 and 5.5 ms, with a comb filter sweeping the spectrum
+    Flanger,
+}
+
+impl ModulationKind {
+    /// The shortest delay and the width of the sweep, in seconds
+    fn range(&self) -> (f64, f64) {
+        match self {
+            ModulationKind::Chorus => (0.010, 0.020),
+            ModulationKind::Flanger => (0.0005, 0.005),
+        }
+    }
+
+    /// The rate of the sweep and the feedback when they are not patched
+    fn defaults(&self) -> (f32, f32) {
+        match self {
+            ModulationKind::Chorus => (0.8, 0.0),
+            ModulationKind::Flanger => (0.25, 0.7),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ChorusInId {
+    In,
+    /// The rate of the sweep in Hz, `0.8` for a chorus and `0.25` for a flanger when not patched
+    Rate,
+    /// How much of the sweep is used, between `0` and `1`, `0.5` when not patched
+    Depth,
+    /// How much of the delayed signal is fed back, `0` for a chorus and `0.7` for a flanger when
+    /// not patched
+    Feedback,
+    /// The balance from the dry signal at `0` to the delayed signal at `1`, `0.5` when not
+    /// patched
+    Mix,
+}
+
+impl InId for ChorusInId {}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ChorusOutId {
+    Out,
+}
+
+impl OutId for ChorusOutId {}
+
+#[derive(Debug, Clone)]
+struct ChorusChannel {
+    line: DelayLine,
+    /// The phase of the sweep, between `0` and `1`
+    phase: f64,
+}
+
+/// A chorus or a flanger, mixing the signal with a copy delayed by a sweeping time
+///
+/// The sweep of each channel is a quarter of a cycle after the previous one, widening stereo
+/// signals.
+#[derive(Debug, Default)]
+pub struct Chorus {
+    kind: ModulationKind,
+    channels: NodeState<Lines<ChorusChannel>>,
+}
+
+impl Chorus {
+    pub fn new(kind: ModulationKind) -> Self {
+        Self {
+            kind,
+            channels: NodeState::default(),
+        }
+    }
+
+    pub fn kind(&self) -> ModulationKind {
+        self.kind
+    }
+}
+
+impl Node for Chorus {
+    fn initialize() -> Self {
+        Self::default()
+    }
+
+    fn title(&self) -> &str {
+        match self.kind {
+            ModulationKind::Chorus => "Chorus",
+            ModulationKind::Flanger => "Flanger",
+        }
+    }
+
+    fn fold(&self, _out_id: &dyn OutId, lasy_fold: LasyFold, meta: Meta) -> anyhow::Result<Data> {
+        self.channels.step_reusing(meta, |channels, last| {
+            let (rate, feedback_default) = self.kind.defaults();
+            let input = Signal::get(&lasy_fold, &ChorusInId::In, meta)?;
+            let rate = Signal::get_or(&lasy_fold, &ChorusInId::Rate, meta, rate)?;
+            let depth = Signal::get_or(&lasy_fold, &ChorusInId::Depth, meta, 0.5)?;
+            let feedback_signal =
+                Signal::get_or(&lasy_fold, &ChorusInId::Feedback, meta, feedback_default)?;
+            let mix_signal = Signal::get_or(&lasy_fold, &ChorusInId::Mix, meta, 0.5)?;
+
+            let sample_rate = meta.sample_rate as f64;
+            let (shortest, sweep) = self.kind.range();
+            let length = ((shortest + sweep) * sample_rate) as usize + 2;
+            channels.prepare(meta.sample_rate, input.channel_count(), |channel| {
+                ChorusChannel {
+                    line: DelayLine::new(length),
+                    phase: (channel as f64 * 0.25).fract(),
+                }
+            });
+
+            Ok(AudioBlock::reuse(last, |block| {
+                block.fill_with(
+                    input.channel_count(),
+                    meta.block_size,
+                    meta.sample_rate,
+                    |channel, frame| {
+                        let state = &mut channels.channels[channel];
+                        let depth = (depth.sample(channel, frame) as f64).clamp(0.0, 1.0);
+                        let modulation = 0.5 + 0.5 * (state.phase * TAU).sin();
+                        let delay = (shortest + sweep * depth * modulation) * sample_rate;
+
+                        let rate = rate.sample(channel, frame) as f64;
+                        if rate.is_finite() {
+                            state.phase = (state.phase + rate / sample_rate).rem_euclid(1.0);
+                        }
+
+                        let dry = input.sample(channel, frame);
+                        let wet = state.line.read(delay);
+                        state
+                            .line
+                            .write(dry + wet * feedback(&feedback_signal, channel, frame));
+
+                        mix(&mix_signal, channel, frame, dry, wet)
+                    },
+                )
+            }))
+        })
+    }
+
+    fn node_in_id(&self, in_id: &dyn InId, node_id: NodeId) -> Option<NodeInId> {
+        in_id
+            .as_any()
+            .downcast_ref::<ChorusInId>()
+            .map(|in_id| NodeInId::new(node_id, in_id))
+    }
+
+    fn node_out_id(&self, out_id: &dyn OutId, node_id: NodeId) -> Option<NodeOutId> {
+        out_id
+            .as_any()
+            .downcast_ref::<ChorusOutId>()
+            .map(|out_id| NodeOutId::new(node_id, out_id))
+    }
+
+    fn in_ids(&self) -> Vec<Box<dyn InId>> {
+        vec![
+            Box::new(ChorusInId::In),
+            Box::new(ChorusInId::Rate),
+            Box::new(ChorusInId::Depth),
+            Box::new(ChorusInId::Feedback),
+            Box::new(ChorusInId::Mix),
+        ]
+    }
+
+    fn out_ids(&self) -> Vec<Box<dyn OutId>> {
+        vec![Box::new(ChorusOutId::Out)]
+    }
+}
+
+/// The tunings of Freeverb, in samples at 44.1 kHz
+const COMB_TUNINGS: [usize; 8] = [1116, 1188, 1277, 1356, 1422, 1491, 1557, 1617];
+const ALLPASS_TUNINGS: [usize; 4] = [556, 441, 341, 225];
+/// How many samples longer the lines of each channel are than the previous channel's
+const STEREO_SPREAD: usize = 23;
+const INPUT_GAIN: f32 = 0.015;
+const WET_GAIN: f32 = 3.0;
+
+/// A low-pass filtered feedback comb filter
+#[derive(Debug, Clone)]
+struct Comb {
+    buffer: Vec<f32>,
+    index: usize,
+    filtered: f32,
+}
+
+impl Comb {
+    fn new(length: usize) -> Self {
+        Self {
+            buffer: vec![0.0; length.max(1)],
+            index: 0,
+            filtered: 0.0,
+        }
+    }
+
+    fn next(&mut self, input: f32, feedback: f32, damping: f32) -> f32 {
+        let output = self.buffer[self.index];
+        self.filtered = output * (1.0 - damping) + self.filtered * damping;
+        self.buffer[self.index] = input + self.filtered * feedback;
+        self.index = (self.index + 1) % self.buffer.len();
+
+        output
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Allpass {
+    buffer: Vec<f32>,
+    index: usize,
+}
+
+impl Allpass {
+    fn new(length: usize) -> Self {
+        Self {
+            buffer: vec![0.0; length.max(1)],
+            index: 0,
+        }
+    }
+
+    fn next(&mut self, input: f32) -> f32 {
+        let delayed = self.buffer[self.index];
+        self.buffer[self.index] = input + delayed * 0.5;
+        self.index = (self.index + 1) % self.buffer.len();
+
+        delayed - input
+    }
+}
+
+/// The combs and allpasses of one output channel of a [`Reverb`]
+#[derive(Debug, Clone)]
+struct ReverbChannel {
+    combs: Vec<Comb>,
+    allpasses: Vec<Allpass>,
+}
+
+impl ReverbChannel {
+    fn new(channel: usize, sample_rate: u32) -> Self {
+        let scale = |tuning: usize| {
+            ((tuning + channel * STEREO_SPREAD) as f64 * sample_rate as f64 / 44_100.0) as usize
+        };
+
+        Self {
+            combs: COMB_TUNINGS.map(|tuning| Comb::new(scale(tuning))).to_vec(),
+            allpasses: ALLPASS_TUNINGS
+                .map(|tuning| Allpass::new(scale(tuning)))
+                .to_vec(),
+        }
+    }
+
+    /// The reverberation of a mono input, `room_size` and `damping` being between `0` and `1`
+    fn next(&mut self, input: f32, room_size: f32, damping: f32) -> f32 {
+        let input = input * INPUT_GAIN;
+        let feedback = room_size * 0.28 + 0.7;
+        let damping = damping * 0.4;
+
+        let combs: f32 = self
+            .combs
+            .iter_mut()
+            .map(|comb| comb.next(input, feedback, damping))
+            .sum();
+
+        self.allpasses
+            .iter_mut()
+            .fold(combs, |signal, allpass| allpass.next(signal))
+            * WET_GAIN
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ReverbInId {
+    In,
+    /// The size of the room, setting the length of the tail, between `0` and `1`, `0.5` when not
+    /// patched
+    RoomSize,
+    /// How fast high frequencies fade in the tail, between `0` and `1`, `0.5` when not patched
+    Damping,
+    /// The balance from the dry signal at `0` to the reverberation at `1`, `0.3` when not patched
+    Mix,
+}
+
+impl InId for ReverbInId {}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ReverbOutId {
+    Out,
+}
+
+impl OutId for ReverbOutId {}
+
+/// An algorithmic reverb, after Jezar's Freeverb
+///
+/// The channels of the input are summed, and reverberated by slightly different lines on each
+/// output channel, folding to at least a stereo block.
+#[derive(Debug, Default)]
+pub struct Reverb {
+    channels: NodeState<Lines<ReverbChannel>>,
+}
+
+impl Node for Reverb {
+    fn initialize() -> Self {
+        Self::default()
+    }
+
+    fn title(&self) -> &str {
+        "Reverb"
+    }
+
+    fn fold(&self, _out_id: &dyn OutId, lasy_fold: LasyFold, meta: Meta) -> anyhow::Result<Data> {
+        self.channels.step_reusing(meta, |channels, last| {
+            let input = Signal::get(&lasy_fold, &ReverbInId::In, meta)?;
+            let room_size = Signal::get_or(&lasy_fold, &ReverbInId::RoomSize, meta, 0.5)?;
+            let damping = Signal::get_or(&lasy_fold, &ReverbInId::Damping, meta, 0.5)?;
+            let mix_signal = Signal::get_or(&lasy_fold, &ReverbInId::Mix, meta, 0.3)?;
+
+            let channel_count = input.channel_count().max(2);
+            channels.prepare(meta.sample_rate, channel_count, |channel| {
+                ReverbChannel::new(channel, meta.sample_rate)
+            });
+
+            let input_count = input.channel_count();
+            Ok(AudioBlock::reuse(last, |block| {
+                block.fill_with(
+                    channel_count,
+                    meta.block_size,
+                    meta.sample_rate,
+                    |channel, frame| {
+                        let mono = (0..input_count)
+                            .map(|channel| input.sample(channel, frame))
+                            .sum::<f32>()
+                            / input_count as f32;
+                        let unit = |signal: &Signal| {
+                            let value = signal.sample(channel, frame);
+                            if value.is_nan() {
+                                0.0
+                            } else {
+                                value.clamp(0.0, 1.0)
+                            }
+                        };
+
+                        let wet =
+                            channels.channels[channel].next(mono, unit(&room_size), unit(&damping));
+
+                        mix(
+                            &mix_signal,
+                            channel,
+                            frame,
+                            input.sample(channel, frame),
+                            wet,
+                        )
+                    },
+                )
+            }))
+        })
+    }
+
+    fn node_in_id(&self, in_id: &dyn InId, node_id: NodeId) -> Option<NodeInId> {
+        in_id
+            .as_any()
+            .downcast_ref::<ReverbInId>()
+            .map(|in_id| NodeInId::new(node_id, in_id))
+    }
+
+    fn node_out_id(&self, out_id: &dyn OutId, node_id: NodeId) -> Option<NodeOutId> {
+        out_id
+            .as_any()
+            .downcast_ref::<ReverbOutId>()
+            .map(|out_id| NodeOutId::new(node_id, out_id))
+    }
+
+    fn in_ids(&self) -> Vec<Box<dyn InId>> {
+        vec![
+            Box::new(ReverbInId::In),
+            Box::new(ReverbInId::RoomSize),
+            Box::new(ReverbInId::Damping),
+            Box::new(ReverbInId::Mix),
+        ]
+    }
+
+    fn out_ids(&self) -> Vec<Box<dyn OutId>> {
+        vec![Box::new(ReverbOutId::Out)]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use quakk::GraphOutOutId;
+
+    use super::*;
+    use crate::{
+        Transport,
+        testing::{patch_node_to_formulas, render_node},
+    };
+
+    #[test]
+    fn delay_line_interpolates() {
+        let mut line = DelayLine::new(8);
+        for sample in [1.0, 2.0, 3.0, 4.0] {
+            line.write(sample);
+        }
+
+        assert_eq!(line.read(1.0), 4.0);
+        assert_eq!(line.read(2.5), 2.5);
+        // Delays are clamped to what the line holds
+        assert_eq!(line.read(0.0), 4.0);
+        assert_eq!(line.read(100.0), 0.0);
+    }
+
+    #[test]
+    fn delay_repeats_with_feedback() {
+        let inputs: [(&dyn InId, f32); 4] = [
+            (&DelayInId::In, 1.0),
+            (&DelayInId::Time, 0.0078125),
+            (&DelayInId::Feedback, 0.5),
+            (&DelayInId::Mix, 1.0),
+        ];
+        let block = render_node(
+            Box::new(Delay::default()),
+            &DelayOutId::Out,
+            &inputs,
+            1_024,
+            8,
+            24,
+        );
+
+        // Repeats every 8 samples
+        let samples = block.channel(0).unwrap();
+        assert_eq!(samples[..8], [0.0; 8]);
+        assert_eq!(samples[8..16], [1.0; 8]);
+        assert_eq!(samples[16..24], [1.5; 8]);
+    }
+
+    #[test]
+    fn delay_syncs_to_the_tempo() {
+        // An eighth of a beat at 960 BPM is 8 samples at 1024 Hz
+        let inputs: [(&dyn InId, f32); 5] = [
+            (&DelayInId::In, 1.0),
+            (&DelayInId::Time, 0.125),
+            (&DelayInId::Bpm, 960.0),
+            (&DelayInId::Feedback, 0.0),
+            (&DelayInId::Mix, 1.0),
+        ];
+        let block = render_node(
+            Box::new(Delay::default()),
+            &DelayOutId::Out,
+            &inputs,
+            1_024,
+            8,
+            12,
+        );
+
+        let samples = block.channel(0).unwrap();
+        assert_eq!(samples[7], 0.0);
+        assert_eq!(samples[8], 1.0);
+    }
+
+    #[test]
+    fn chorus_without_mix_is_dry() {
+        for kind in [ModulationKind::Chorus, ModulationKind::Flanger] {
+            let block = render_node(
+                Box::new(Chorus::new(kind)),
+                &ChorusOutId::Out,
+                &[(&ChorusInId::In, 0.5), (&ChorusInId::Mix, 0.0)],
+                1_024,
+                8,
+                64,
+            );
+            assert!(
+                block
+                    .channel(0)
+                    .unwrap()
+                    .iter()
+                    .all(|sample| *sample == 0.5)
+            );
+        }
+    }
+
+    #[test]
+    fn chorus_sweeps_its_delay() {
+        let sample_rate = 48_000;
+        for kind in [ModulationKind::Chorus, ModulationKind::Flanger] {
+            // An impulse every 2400 samples, while the delay sweeps twice a second
+            let quakk = patch_node_to_formulas(
+                Box::new(Chorus::new(kind)),
+                &ChorusOutId::Out,
+                &[
+                    (&ChorusInId::In, "1 - min(tick % 2400, 1)"),
+                    (&ChorusInId::Rate, "2"),
+                    (&ChorusInId::Depth, "1"),
+                    (&ChorusInId::Feedback, "0"),
+                    (&ChorusInId::Mix, "1"),
+                ],
+            );
+            let block = Transport::new(sample_rate, 1)
+                .unwrap()
+                .render(&quakk, GraphOutOutId::Numeric, 24_000)
+                .unwrap();
+
+            let delays: Vec<usize> = block
+                .channel(0)
+                .unwrap()
+                .chunks(2400)
+                .map(|echo| echo.iter().position(|sample| *sample != 0.0).unwrap())
+                .collect();
+
+            // Each echo lands within the range of the sweep, give or take the interpolation
+            let (shortest, sweep) = kind.range();
+            let shortest = shortest * sample_rate as f64;
+            let longest = shortest + sweep * sample_rate as f64;
+            for delay in &delays {
+                let delay = *delay as f64;
+                assert!(
+                    delay >= shortest - 1.0 && delay <= longest + 1.0,
+                    "{kind:?} echoes after {delay} samples"
+                );
+            }
+
+            // And the delay moves over the sweep
+            let (first, last) = (delays.iter().min().unwrap(), delays.iter().max().unwrap());
+            assert!(
+                (last - first) as f64 > sweep * sample_rate as f64 / 2.0,
+                "{kind:?} sweeps from {first} to {last} samples"
+            );
+        }
+    }
+
+    #[test]
+    fn reverb_tail_decays() {
+        let mut channels = [ReverbChannel::new(0, 48_000), ReverbChannel::new(1, 48_000)];
+        let tails = channels.each_mut().map(|channel| {
+            (0..96_000)
+                .map(|sample| channel.next(if sample == 0 { 1.0 } else { 0.0 }, 0.5, 0.5))
+                .collect::<Vec<f32>>()
+        });
+
+        let energy = |samples: &[f32]| samples.iter().map(|sample| sample * sample).sum::<f32>();
+        let [left, right] = &tails;
+        assert!(energy(&left[..24_000]) > 0.0);
+        assert!(energy(&left[72_000..]) < energy(&left[..24_000]) * 1e-3);
+        // The spread between channels decorrelates them
+        assert_ne!(left[..4_800], right[..4_800]);
+    }
+}
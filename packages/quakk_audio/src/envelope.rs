@@ -0,0 +1,315 @@
+//! Envelopes, shaping the level of a sound over the time of a note
+
+use quakk::{
+    Data, LasyFold, Meta, Node, NodeState,
+    id::{InId, NodeId, NodeInId, NodeOutId, OutId},
+};
+
+use crate::{AudioBlock, Signal};
+
+/// How the level of an [`Adsr`] moves from one stage to the next
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum EnvelopeCurve {
+    /// A straight line, the stage lasts exactly its time
+    Linear,
+    /// An exponential approach, like analog envelopes, that sounds more natural
+    #[default]
+    Exponential,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+enum Stage {
+    #[default]
+    Idle,
+    Attack,
+    Decay,
+    Sustain,
+    Release,
+}
+
+#[derive(Debug, Default)]
+struct AdsrState {
+    stage: Stage,
+    level: f64,
+    /// The level when the release started, linear releases fall from it
+    release_from: f64,
+    gate: bool,
+    trigger: bool,
+}
+
+/// How much an exponential stage overshoots its target, so it reaches it in a finite time
+const ATTACK_RATIO: f64 = 0.3;
+const DECAY_RATIO: f64 = 0.0001;
+
+/// The coefficient of a one-pole filter reaching `ratio` away from its target in `samples`
+fn exponential_coefficient(samples: f64, ratio: f64) -> f64 {
+    (-((1.0 + ratio) / ratio).ln() / samples).exp()
+}
+
+impl AdsrState {
+    /// Advance the envelope by one sample, times being given in samples
+    fn next(
+        &mut self,
+        curve: EnvelopeCurve,
+        gate: bool,
+        trigger: bool,
+        [attack, decay, sustain, release]: [f64; 4],
+    ) -> f64 {
+        let rising_gate = gate && !self.gate;
+        let rising_trigger = trigger && !self.trigger;
+        self.gate = gate;
+        self.trigger = trigger;
+
+        if rising_gate || (gate && rising_trigger) {
+            self.stage = Stage::Attack;
+        } else if !gate && self.stage != Stage::Idle && self.stage != Stage::Release {
+            self.stage = Stage::Release;
+            self.release_from = self.level;
+        }
+
+        // A stage of zero samples ends at once
+        let approach = |level: f64, target: f64, samples: f64, ratio: f64| match curve {
+            _ if samples < 1.0 => target,
+            EnvelopeCurve::Linear => {
+                let step = 1.0 / samples;
+                // Rounding errors should not add a sample to the stage
+                if (target - level).abs() <= step * (1.0 + 1e-9) {
+                    target
+                } else if level < target {
+                    (level + step).min(target)
+                } else {
+                    (level - step).max(target)
+                }
+            }
+            EnvelopeCurve::Exponential => {
+                let coefficient = exponential_coefficient(samples, ratio);
+                let overshoot = if level < target {
+                    target + ratio
+                } else {
+                    target - ratio
+                };
+                let next = overshoot + (level - overshoot) * coefficient;
+
+                if (level < target) == (next < target) {
+                    next
+                } else {
+                    target
+                }
+            }
+        };
+
+        match self.stage {
+            Stage::Idle => self.level = 0.0,
+            Stage::Attack => {
+                self.level = approach(self.level, 1.0, attack, ATTACK_RATIO);
+                if self.level >= 1.0 {
+                    self.stage = Stage::Decay;
+                }
+            }
+            Stage::Decay => {
+                // Linear stages move by `1 / samples` each sample, scale it to the distance
+                let samples = match curve {
+                    EnvelopeCurve::Linear => decay / (1.0 - sustain).max(f64::EPSILON),
+                    EnvelopeCurve::Exponential => decay,
+                };
+                self.level = approach(self.level, sustain, samples, DECAY_RATIO);
+                if self.level <= sustain {
+                    self.stage = Stage::Sustain;
+                }
+            }
+            Stage::Sustain => self.level = sustain,
+            Stage::Release => {
+                let samples = match curve {
+                    EnvelopeCurve::Linear => release / self.release_from.max(f64::EPSILON),
+                    EnvelopeCurve::Exponential => release,
+                };
+                self.level = approach(self.level, 0.0, samples, DECAY_RATIO);
+                if self.level <= 0.0 {
+                    self.stage = Stage::Idle;
+                }
+            }
+        }
+
+        self.level
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum AdsrInId {
+    /// The note is held while the gate is above `0.5`
+    Gate,
+    /// Restart the attack on a rising edge while the gate is held, optional
+    Retrigger,
+    /// The time to rise from `0` to `1`, in seconds
+    Attack,
+    /// The time to fall from `1` to the sustain level, in seconds
+    Decay,
+    /// The level held while the gate is held, between `0` and `1`
+    Sustain,
+    /// The time to fall to `0` once the gate is released, in seconds
+    Release,
+}
+
+impl InId for AdsrInId {}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum AdsrOutId {
+    Out,
+}
+
+impl OutId for AdsrOutId {}
+
+/// An attack, decay, sustain and release envelope, folding to a mono [`AudioBlock`] between `0`
+/// and `1`
+///
+/// Every input can be a number or a block, the gate and retrigger being read sample by sample
+#[derive(Debug, Default)]
+pub struct Adsr {
+    curve: EnvelopeCurve,
+    state: NodeState<AdsrState>,
+}
+
+impl Adsr {
+    pub fn new(curve: EnvelopeCurve) -> Self {
+        Self {
+            curve,
+            state: NodeState::default(),
+        }
+    }
+
+    pub fn curve(&self) -> EnvelopeCurve {
+        self.curve
+    }
+}
+
+impl Node for Adsr {
+    fn initialize() -> Self {
+        Self::default()
+    }
+
+    fn title(&self) -> &str {
+        "ADSR"
+    }
+
+    fn fold(&self, _out_id: &dyn OutId, lasy_fold: LasyFold, meta: Meta) -> anyhow::Result<Data> {
+        self.state.step_reusing(meta, |state, last| {
+            let gate = Signal::get(&lasy_fold, &AdsrInId::Gate, meta)?;
+            let retrigger = Signal::get_or(&lasy_fold, &AdsrInId::Retrigger, meta, 0.0)?;
+            let attack = Signal::get(&lasy_fold, &AdsrInId::Attack, meta)?;
+            let decay = Signal::get(&lasy_fold, &AdsrInId::Decay, meta)?;
+            let sustain = Signal::get(&lasy_fold, &AdsrInId::Sustain, meta)?;
+            let release = Signal::get(&lasy_fold, &AdsrInId::Release, meta)?;
+
+            let sample_rate = meta.sample_rate as f64;
+            Ok(AudioBlock::reuse(last, |block| {
+                block.fill_with(1, meta.block_size, meta.sample_rate, |_, frame| {
+                    let positive = |signal: &Signal| (signal.sample(0, frame) as f64).max(0.0);
+                    let parameters = [
+                        positive(&attack) * sample_rate,
+                        positive(&decay) * sample_rate,
+                        positive(&sustain).min(1.0),
+                        positive(&release) * sample_rate,
+                    ];
+
+                    state.next(
+                        self.curve,
+                        gate.sample(0, frame) > 0.5,
+                        retrigger.sample(0, frame) > 0.5,
+                        parameters,
+                    ) as f32
+                })
+            }))
+        })
+    }
+
+    fn node_in_id(&self, in_id: &dyn InId, node_id: NodeId) -> Option<NodeInId> {
+        in_id
+            .as_any()
+            .downcast_ref::<AdsrInId>()
+            .map(|in_id| NodeInId::new(node_id, in_id))
+    }
+
+    fn node_out_id(&self, out_id: &dyn OutId, node_id: NodeId) -> Option<NodeOutId> {
+        out_id
+            .as_any()
+            .downcast_ref::<AdsrOutId>()
+            .map(|out_id| NodeOutId::new(node_id, out_id))
+    }
+
+    fn in_ids(&self) -> Vec<Box<dyn InId>> {
+        vec![
+            Box::new(AdsrInId::Gate),
+            Box::new(AdsrInId::Retrigger),
+            Box::new(AdsrInId::Attack),
+            Box::new(AdsrInId::Decay),
+            Box::new(AdsrInId::Sustain),
+            Box::new(AdsrInId::Release),
+        ]
+    }
+
+    fn out_ids(&self) -> Vec<Box<dyn OutId>> {
+        vec![Box::new(AdsrOutId::Out)]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Run an envelope with times in samples, the gate being held for `held` samples
+    fn run(curve: EnvelopeCurve, held: usize, total: usize) -> Vec<f64> {
+        let mut state = AdsrState::default();
+        (0..total)
+            .map(|sample| state.next(curve, sample < held, false, [10.0, 10.0, 0.5, 20.0]))
+            .collect()
+    }
+
+    #[test]
+    fn linear_stages() {
+        let levels = run(EnvelopeCurve::Linear, 40, 70);
+
+        assert!((levels[4] - 0.5).abs() < 1e-9);
+        assert!((levels[9] - 1.0).abs() < 1e-9);
+        assert!((levels[14] - 0.75).abs() < 1e-9);
+        assert_eq!(levels[30], 0.5);
+        assert!((levels[49] - 0.25).abs() < 1e-9);
+        assert_eq!(levels[60], 0.0);
+    }
+
+    #[test]
+    fn exponential_stages_reach_their_targets() {
+        let levels = run(EnvelopeCurve::Exponential, 60, 200);
+
+        assert!(levels[..10].windows(2).all(|pair| pair[0] < pair[1]));
+        assert!(levels[..11].contains(&1.0));
+        assert_eq!(levels[50], 0.5);
+        assert!(levels[59 + 20] < 0.01);
+        assert_eq!(levels[199], 0.0);
+    }
+
+    #[test]
+    fn released_before_the_end_of_the_attack() {
+        let levels = run(EnvelopeCurve::Linear, 5, 30);
+
+        assert!((levels[4] - 0.5).abs() < 1e-9);
+        assert!(levels[5] < levels[4]);
+        assert!((levels[24] - 0.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn retrigger_restarts_the_attack() {
+        let mut state = AdsrState::default();
+        let parameters = [10.0, 10.0, 0.5, 20.0];
+
+        for _ in 0..40 {
+            state.next(EnvelopeCurve::Linear, true, false, parameters);
+        }
+        assert_eq!(state.stage, Stage::Sustain);
+
+        state.next(EnvelopeCurve::Linear, true, true, parameters);
+        assert_eq!(state.stage, Stage::Attack);
+        // Holding the retrigger high does not restart the attack again
+        let level = state.next(EnvelopeCurve::Linear, true, true, parameters);
+        assert!((level - 0.7).abs() < 1e-9);
+    }
+}
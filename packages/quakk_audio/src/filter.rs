@@ -0,0 +1,581 @@
+//! Filters, shaping the spectrum of a signal
+//!
+//! [`Svf`] is a state-variable filter (the trapezoidal integrated SVF described by Andrew Simper)
+//! that stays stable and smooth when its frequency or resonance move at audio rate, it is the
+//! filter to modulate. [`Biquad`] uses the classic coefficients of Robert Bristow-Johnson's
+//! cookbook, it is cheaper when its parameters hold still, like in an equalizer.
+
+use std::f64::consts::PI;
+
+use quakk::{
+    Data, LasyFold, Meta, Node, NodeState,
+    id::{InId, NodeId, NodeInId, NodeOutId, OutId},
+};
+
+use crate::{AudioBlock, Signal};
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum FilterKind {
+    #[default]
+    LowPass,
+    HighPass,
+    /// A band-pass with a gain of 0 dB at its center frequency
+    BandPass,
+    Notch,
+    /// Boost or cut the frequencies below the cutoff by `Gain` decibels
+    LowShelf,
+    /// Boost or cut the frequencies above the cutoff by `Gain` decibels
+    HighShelf,
+    /// Boost or cut the frequencies around the cutoff by `Gain` decibels
+    Peak,
+}
+
+impl FilterKind {
+    fn uses_gain(&self) -> bool {
+        matches!(self, Self::LowShelf | Self::HighShelf | Self::Peak)
+    }
+}
+
+/// The parameters of a filter for one sample, clamped to values keeping it stable
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+struct Parameters {
+    frequency: f64,
+    resonance: f64,
+    gain: f64,
+}
+
+impl Parameters {
+    fn new(frequency: f32, resonance: f32, gain: f32, sample_rate: f64) -> Self {
+        Self {
+            // Not `clamp`, which panics when the sample rate is under 3 Hz
+            frequency: (frequency as f64).min(sample_rate * 0.49).max(1.0),
+            resonance: (resonance as f64).max(0.01),
+            gain: gain as f64,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum FilterInId {
+    In,
+    /// The cutoff or center frequency in Hz, `1000` when not patched
+    Frequency,
+    /// The resonance, as a Q factor, `0.707` when not patched
+    Resonance,
+    /// The gain of shelf and peak filters, in decibels, `0` when not patched
+    Gain,
+}
+
+impl InId for FilterInId {}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum FilterOutId {
+    Out,
+}
+
+impl OutId for FilterOutId {}
+
+/// The signals read by a filter for one block
+struct FilterInputs {
+    input: Signal,
+    frequency: Signal,
+    resonance: Signal,
+    gain: Signal,
+}
+
+impl FilterInputs {
+    fn get(kind: FilterKind, lasy_fold: &LasyFold, meta: Meta) -> anyhow::Result<Self> {
+        Ok(Self {
+            input: Signal::get(lasy_fold, &FilterInId::In, meta)?,
+            frequency: Signal::get_or(lasy_fold, &FilterInId::Frequency, meta, 1000.0)?,
+            resonance: Signal::get_or(lasy_fold, &FilterInId::Resonance, meta, 0.707)?,
+            gain: if kind.uses_gain() {
+                Signal::get_or(lasy_fold, &FilterInId::Gain, meta, 0.0)?
+            } else {
+                Signal::Constant(0.0)
+            },
+        })
+    }
+
+    /// Filter every channel of the input in the buffers of the `last` block, `filter` being
+    /// called for each sample with the channel, the sample and its parameters
+    fn process(
+        &self,
+        meta: Meta,
+        last: Option<Data>,
+        mut filter: impl FnMut(usize, f64, Parameters) -> f64,
+    ) -> Data {
+        let sample_rate = meta.sample_rate as f64;
+
+        AudioBlock::reuse(last, |block| {
+            block.fill_with(
+                self.input.channel_count(),
+                meta.block_size,
+                meta.sample_rate,
+                |channel, frame| {
+                    let parameters = Parameters::new(
+                        self.frequency.sample(channel, frame),
+                        self.resonance.sample(channel, frame),
+                        self.gain.sample(channel, frame),
+                        sample_rate,
+                    );
+
+                    filter(
+                        channel,
+                        self.input.sample(channel, frame) as f64,
+                        parameters,
+                    ) as f32
+                },
+            )
+        })
+    }
+}
+
+fn filter_in_id(kind: FilterKind, in_id: &dyn InId, node_id: NodeId) -> Option<NodeInId> {
+    in_id
+        .as_any()
+        .downcast_ref::<FilterInId>()
+        .filter(|in_id| **in_id != FilterInId::Gain || kind.uses_gain())
+        .map(|in_id| NodeInId::new(node_id, in_id))
+}
+
+fn filter_in_ids(kind: FilterKind) -> Vec<Box<dyn InId>> {
+    let mut in_ids: Vec<Box<dyn InId>> = vec![
+        Box::new(FilterInId::In),
+        Box::new(FilterInId::Frequency),
+        Box::new(FilterInId::Resonance),
+    ];
+    if kind.uses_gain() {
+        in_ids.push(Box::new(FilterInId::Gain));
+    }
+
+    in_ids
+}
+
+/// The coefficients of a state-variable filter
+#[derive(Debug, Default, Clone, Copy)]
+struct SvfCoefficients {
+    a1: f64,
+    a2: f64,
+    a3: f64,
+    m0: f64,
+    m1: f64,
+    m2: f64,
+}
+
+impl SvfCoefficients {
+    fn new(kind: FilterKind, parameters: Parameters, sample_rate: f64) -> Self {
+        let g = (PI * parameters.frequency / sample_rate).tan();
+        let k = 1.0 / parameters.resonance;
+        let a = 10f64.powf(parameters.gain / 40.0);
+
+        let (g, k, m0, m1, m2) = match kind {
+            FilterKind::LowPass => (g, k, 0.0, 0.0, 1.0),
+            FilterKind::HighPass => (g, k, 1.0, -k, -1.0),
+            FilterKind::BandPass => (g, k, 0.0, k, 0.0),
+            FilterKind::Notch => (g, k, 1.0, -k, 0.0),
+            FilterKind::LowShelf => (g / a.sqrt(), k, 1.0, k * (a - 1.0), a * a - 1.0),
+            FilterKind::HighShelf => (g * a.sqrt(), k, a * a, k * (1.0 - a) * a, 1.0 - a * a),
+            FilterKind::Peak => {
+                let k = k / a;
+                (g, k, 1.0, k * (a * a - 1.0), 0.0)
+            }
+        };
+
+        let a1 = 1.0 / (1.0 + g * (g + k));
+        let a2 = g * a1;
+        let a3 = g * a2;
+
+        Self {
+            a1,
+            a2,
+            a3,
+            m0,
+            m1,
+            m2,
+        }
+    }
+}
+
+/// The state of a state-variable filter for one channel, with the last coefficients computed
+#[derive(Debug, Default, Clone)]
+struct SvfChannel {
+    ic1: f64,
+    ic2: f64,
+    parameters: Option<Parameters>,
+    coefficients: SvfCoefficients,
+}
+
+impl SvfChannel {
+    fn next(
+        &mut self,
+        kind: FilterKind,
+        input: f64,
+        parameters: Parameters,
+        sample_rate: f64,
+    ) -> f64 {
+        if self.parameters != Some(parameters) {
+            self.coefficients = SvfCoefficients::new(kind, parameters, sample_rate);
+            self.parameters = Some(parameters);
+        }
+        let SvfCoefficients {
+            a1,
+            a2,
+            a3,
+            m0,
+            m1,
+            m2,
+        } = self.coefficients;
+
+        let v3 = input - self.ic2;
+        let v1 = a1 * self.ic1 + a2 * v3;
+        let v2 = self.ic2 + a2 * self.ic1 + a3 * v3;
+        self.ic1 = 2.0 * v1 - self.ic1;
+        self.ic2 = 2.0 * v2 - self.ic2;
+
+        m0 * input + m1 * v1 + m2 * v2
+    }
+}
+
+/// A state-variable filter, each channel of the input being filtered separately
+#[derive(Debug, Default)]
+pub struct Svf {
+    kind: FilterKind,
+    channels: NodeState<Vec<SvfChannel>>,
+}
+
+impl Svf {
+    pub fn new(kind: FilterKind) -> Self {
+        Self {
+            kind,
+            channels: NodeState::default(),
+        }
+    }
+
+    pub fn kind(&self) -> FilterKind {
+        self.kind
+    }
+}
+
+impl Node for Svf {
+    fn initialize() -> Self {
+        Self::default()
+    }
+
+    fn title(&self) -> &str {
+        "SVF"
+    }
+
+    fn fold(&self, _out_id: &dyn OutId, lasy_fold: LasyFold, meta: Meta) -> anyhow::Result<Data> {
+        self.channels.step_reusing(meta, |channels, last| {
+            let inputs = FilterInputs::get(self.kind, &lasy_fold, meta)?;
+            channels.resize(inputs.input.channel_count(), SvfChannel::default());
+
+            Ok(inputs.process(meta, last, |channel, input, parameters| {
+                channels[channel].next(self.kind, input, parameters, meta.sample_rate as f64)
+            }))
+        })
+    }
+
+    fn node_in_id(&self, in_id: &dyn InId, node_id: NodeId) -> Option<NodeInId> {
+        filter_in_id(self.kind, in_id, node_id)
+    }
+
+    fn node_out_id(&self, out_id: &dyn OutId, node_id: NodeId) -> Option<NodeOutId> {
+        out_id
+            .as_any()
+            .downcast_ref::<FilterOutId>()
+            .map(|out_id| NodeOutId::new(node_id, out_id))
+    }
+
+    fn in_ids(&self) -> Vec<Box<dyn InId>> {
+        filter_in_ids(self.kind)
+    }
+
+    fn out_ids(&self) -> Vec<Box<dyn OutId>> {
+        vec![Box::new(FilterOutId::Out)]
+    }
+}
+
+/// The normalized coefficients of a biquad filter
+#[derive(Debug, Default, Clone, Copy)]
+struct BiquadCoefficients {
+    b0: f64,
+    b1: f64,
+    b2: f64,
+    a1: f64,
+    a2: f64,
+}
+
+impl BiquadCoefficients {
+    fn new(kind: FilterKind, parameters: Parameters, sample_rate: f64) -> Self {
+        let w0 = 2.0 * PI * parameters.frequency / sample_rate;
+        let (sin, cos) = w0.sin_cos();
+        let alpha = sin / (2.0 * parameters.resonance);
+        let a = 10f64.powf(parameters.gain / 40.0);
+
+        let [b0, b1, b2, a0, a1, a2] = match kind {
+            FilterKind::LowPass => [
+                (1.0 - cos) / 2.0,
+                1.0 - cos,
+                (1.0 - cos) / 2.0,
+                1.0 + alpha,
+                -2.0 * cos,
+                1.0 - alpha,
+            ],
+            FilterKind::HighPass => [
+                (1.0 + cos) / 2.0,
+                -(1.0 + cos),
+                (1.0 + cos) / 2.0,
+                1.0 + alpha,
+                -2.0 * cos,
+                1.0 - alpha,
+            ],
+            FilterKind::BandPass => [alpha, 0.0, -alpha, 1.0 + alpha, -2.0 * cos, 1.0 - alpha],
+            FilterKind::Notch => [1.0, -2.0 * cos, 1.0, 1.0 + alpha, -2.0 * cos, 1.0 - alpha],
+            FilterKind::Peak => [
+                1.0 + alpha * a,
+                -2.0 * cos,
+                1.0 - alpha * a,
+                1.0 + alpha / a,
+                -2.0 * cos,
+                1.0 - alpha / a,
+            ],
+            FilterKind::LowShelf => {
+                let beta = 2.0 * a.sqrt() * alpha;
+                [
+                    a * ((a + 1.0) - (a - 1.0) * cos + beta),
+                    2.0 * a * ((a - 1.0) - (a + 1.0) * cos),
+                    a * ((a + 1.0) - (a - 1.0) * cos - beta),
+                    (a + 1.0) + (a - 1.0) * cos + beta,
+                    -2.0 * ((a - 1.0) + (a + 1.0) * cos),
+                    (a + 1.0) + (a - 1.0) * cos - beta,
+                ]
+            }
+            FilterKind::HighShelf => {
+                let beta = 2.0 * a.sqrt() * alpha;
+                [
+                    a * ((a + 1.0) + (a - 1.0) * cos + beta),
+                    -2.0 * a * ((a - 1.0) + (a + 1.0) * cos),
+                    a * ((a + 1.0) + (a - 1.0) * cos - beta),
+                    (a + 1.0) - (a - 1.0) * cos + beta,
+                    2.0 * ((a - 1.0) - (a + 1.0) * cos),
+                    (a + 1.0) - (a - 1.0) * cos - beta,
+                ]
+            }
+        };
+
+        Self {
+            b0: b0 / a0,
+            b1: b1 / a0,
+            b2: b2 / a0,
+            a1: a1 / a0,
+            a2: a2 / a0,
+        }
+    }
+}
+
+/// The state of a biquad filter for one channel (transposed direct form II)
+#[derive(Debug, Default, Clone)]
+struct BiquadChannel {
+    s1: f64,
+    s2: f64,
+    parameters: Option<Parameters>,
+    coefficients: BiquadCoefficients,
+}
+
+impl BiquadChannel {
+    fn next(
+        &mut self,
+        kind: FilterKind,
+        input: f64,
+        parameters: Parameters,
+        sample_rate: f64,
+    ) -> f64 {
+        if self.parameters != Some(parameters) {
+            self.coefficients = BiquadCoefficients::new(kind, parameters, sample_rate);
+            self.parameters = Some(parameters);
+        }
+        let BiquadCoefficients { b0, b1, b2, a1, a2 } = self.coefficients;
+
+        let output = b0 * input + self.s1;
+        self.s1 = b1 * input - a1 * output + self.s2;
+        self.s2 = b2 * input - a2 * output;
+
+        output
+    }
+}
+
+/// A biquad filter, each channel of the input being filtered separately
+#[derive(Debug, Default)]
+pub struct Biquad {
+    kind: FilterKind,
+    channels: NodeState<Vec<BiquadChannel>>,
+}
+
+impl Biquad {
+    pub fn new(kind: FilterKind) -> Self {
+        Self {
+            kind,
+            channels: NodeState::default(),
+        }
+    }
+
+    pub fn kind(&self) -> FilterKind {
+        self.kind
+    }
+}
+
+impl Node for Biquad {
+    fn initialize() -> Self {
+        Self::default()
+    }
+
+    fn title(&self) -> &str {
+        "Biquad"
+    }
+
+    fn fold(&self, _out_id: &dyn OutId, lasy_fold: LasyFold, meta: Meta) -> anyhow::Result<Data> {
+        self.channels.step_reusing(meta, |channels, last| {
+            let inputs = FilterInputs::get(self.kind, &lasy_fold, meta)?;
+            channels.resize(inputs.input.channel_count(), BiquadChannel::default());
+
+            Ok(inputs.process(meta, last, |channel, input, parameters| {
+                channels[channel].next(self.kind, input, parameters, meta.sample_rate as f64)
+            }))
+        })
+    }
+
+    fn node_in_id(&self, in_id: &dyn InId, node_id: NodeId) -> Option<NodeInId> {
+        filter_in_id(self.kind, in_id, node_id)
+    }
+
+    fn node_out_id(&self, out_id: &dyn OutId, node_id: NodeId) -> Option<NodeOutId> {
+        out_id
+            .as_any()
+            .downcast_ref::<FilterOutId>()
+            .map(|out_id| NodeOutId::new(node_id, out_id))
+    }
+
+    fn in_ids(&self) -> Vec<Box<dyn InId>> {
+        filter_in_ids(self.kind)
+    }
+
+    fn out_ids(&self) -> Vec<Box<dyn OutId>> {
+        vec![Box::new(FilterOutId::Out)]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: f64 = 48_000.0;
+
+    /// The RMS level of a sine at `frequency` once filtered, after the filter settled
+    fn response(
+        mut filter: impl FnMut(f64, Parameters) -> f64,
+        frequency: f64,
+        parameters: Parameters,
+    ) -> f64 {
+        let samples: Vec<f64> = (0..9600)
+            .map(|i| {
+                let input = (2.0 * PI * frequency * i as f64 / SAMPLE_RATE).sin();
+                filter(input, parameters)
+            })
+            .collect();
+
+        let tail = &samples[4800..];
+        (tail.iter().map(|sample| sample * sample).sum::<f64>() / tail.len() as f64).sqrt()
+            * 2f64.sqrt()
+    }
+
+    fn svf(kind: FilterKind, frequency: f64, parameters: Parameters) -> f64 {
+        let mut channel = SvfChannel::default();
+        response(
+            |input, parameters| channel.next(kind, input, parameters, SAMPLE_RATE),
+            frequency,
+            parameters,
+        )
+    }
+
+    fn biquad(kind: FilterKind, frequency: f64, parameters: Parameters) -> f64 {
+        let mut channel = BiquadChannel::default();
+        response(
+            |input, parameters| channel.next(kind, input, parameters, SAMPLE_RATE),
+            frequency,
+            parameters,
+        )
+    }
+
+    #[test]
+    fn both_filters_agree() {
+        let parameters = Parameters::new(1000.0, 0.707, 6.0, SAMPLE_RATE);
+
+        for kind in [
+            FilterKind::LowPass,
+            FilterKind::HighPass,
+            FilterKind::BandPass,
+            FilterKind::Notch,
+            FilterKind::LowShelf,
+            FilterKind::HighShelf,
+            FilterKind::Peak,
+        ] {
+            for frequency in [100.0, 1000.0, 8000.0] {
+                let svf = svf(kind, frequency, parameters);
+                let biquad = biquad(kind, frequency, parameters);
+                assert!(
+                    (svf - biquad).abs() < 0.02,
+                    "{kind:?} at {frequency} Hz, {svf} against {biquad}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn pass_and_stop_bands() {
+        let parameters = Parameters::new(1000.0, 0.707, 12.0, SAMPLE_RATE);
+        let gain = 10f64.powf(12.0 / 20.0);
+
+        assert!(svf(FilterKind::LowPass, 100.0, parameters) > 0.99);
+        assert!(svf(FilterKind::LowPass, 10_000.0, parameters) < 0.02);
+        assert!(svf(FilterKind::HighPass, 100.0, parameters) < 0.02);
+        assert!(svf(FilterKind::Notch, 1000.0, parameters) < 0.01);
+        assert!((svf(FilterKind::Peak, 1000.0, parameters) - gain).abs() < 0.05);
+        assert!((svf(FilterKind::LowShelf, 20.0, parameters) - gain).abs() < 0.05);
+        assert!((svf(FilterKind::HighShelf, 20.0, parameters) - 1.0).abs() < 0.05);
+    }
+
+    #[test]
+    fn audio_rate_modulation_stays_bounded() {
+        let mut channel = SvfChannel::default();
+
+        for i in 0..48_000 {
+            let time = i as f64 / SAMPLE_RATE;
+            let frequency = 2000.0 + 1900.0 * (2.0 * PI * 300.0 * time).sin();
+            let parameters = Parameters::new(frequency as f32, 8.0, 0.0, SAMPLE_RATE);
+            let input = if i % 100 < 50 { 1.0 } else { -1.0 };
+
+            let output = channel.next(FilterKind::LowPass, input, parameters, SAMPLE_RATE);
+            assert!(output.is_finite() && output.abs() < 20.0);
+        }
+    }
+
+    #[test]
+    fn parameters_at_tiny_sample_rates() {
+        assert_eq!(Parameters::new(1000.0, 1.0, 0.0, 0.0).frequency, 1.0);
+        assert_eq!(Parameters::new(1000.0, 1.0, 0.0, 2.0).frequency, 1.0);
+        assert_eq!(Parameters::new(1000.0, 1.0, 0.0, 100.0).frequency, 49.0);
+    }
+
+    #[test]
+    fn gain_is_only_a_shelf_and_peak_input() {
+        assert_eq!(Svf::new(FilterKind::LowPass).in_ids().len(), 3);
+        assert_eq!(Biquad::new(FilterKind::Peak).in_ids().len(), 4);
+        assert!(
+            Svf::new(FilterKind::Notch)
+                .node_in_id(&FilterInId::Gain, NodeId::GraphIn)
+                .is_none()
+        );
+    }
+}
@@ -1,42 +1,31 @@
-use std::ops::{Add as opsAdd, Mul as opsMul};
-
-use quakk::{
-    Data, LasyFold, Meta, Node,
-    id::{InId, InoutId, NodeId, NodeInId, NodeOutId, OutId},
-};
-
-#[derive(Debug, Default)]
-pub struct LFO {
-    frequency: f32,
-    phase: f32,
-}
-
-impl LFO {
-    fn new() -> Self {
-        Self::default()
-    }
-}
-
-impl Node for LFO {
-    fn initialize() -> Self {
-        Self::default()
-    }
-
-    fn title(&self) -> &str {
-        "LFO"
-    }
-
-    fn fold(&self, out_id: &dyn OutId, lasy_fold: LasyFold, meta: Meta) -> anyhow::Result<Data> {
-        Ok(Data::new(
-            (meta.tick as f32).mul(self.frequency).add(self.phase),
-        ))
-    }
-
-    fn node_in_id(&self, in_id: &dyn InId, node_id: NodeId) -> Option<NodeInId> {
-        None
-    }
-
-    fn node_out_id(&self, out_id: &dyn OutId, node_id: NodeId) -> Option<NodeOutId> {
-        None
-    }
-}
+//! Audio nodes for quakk
+//!
+//! Audio is evaluated by blocks : each tick, an audio node folds to an [`AudioBlock`] holding
+//! [`Meta::block_size`] frames at [`Meta::sample_rate`], and a [`Transport`] drives the graph one
+//! block after the other.
+//!
+//! [`Meta::block_size`]: quakk::Meta::block_size
+//! [`Meta::sample_rate`]: quakk::Meta::sample_rate
+
+mod block;
+pub use block::{AudioBlock, BlockBuffer};
+
+pub mod effect;
+pub mod envelope;
+pub mod filter;
+pub mod mix;
+pub mod oscillator;
+pub use oscillator::LFO;
+pub mod sampler;
+pub mod sink;
+
+mod signal;
+pub use signal::Signal;
+
+mod transport;
+pub use transport::Transport;
+
+#[cfg(test)]
+mod testing;
+
+pub mod wav;
@@ -0,0 +1,687 @@
+//! Combining and routing signals : levels, panning, mixing and channels
+//!
+//! Numbered ports are counted from `1` in their names, like `In1` and `Level1`, and from `0` in
+//! their ids, like [`MixerInId::In(0)`](MixerInId::In).
+
+use std::{
+    cell::RefCell,
+    f32::consts::{FRAC_PI_4, SQRT_2},
+};
+
+use anyhow::anyhow;
+use quakk::{
+    Data, LasyFold, Meta, Node,
+    id::{InId, NodeId, NodeInId, NodeOutId, OutId},
+};
+
+use crate::{AudioBlock, BlockBuffer, Signal};
+
+/// How the gain of a [`Gain`] is given
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum GainUnit {
+    /// A factor, `1` keeping the level
+    #[default]
+    Linear,
+    /// Decibels, `0` keeping the level and `-6` about halving it
+    Decibels,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum GainInId {
+    In,
+    /// The gain, in the [`GainUnit`] of the node, keeping the level when not patched
+    Gain,
+}
+
+impl InId for GainInId {}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum GainOutId {
+    Out,
+}
+
+impl OutId for GainOutId {}
+
+/// Scale the level of a signal, keeping its channels
+#[derive(Debug, Default)]
+pub struct Gain {
+    unit: GainUnit,
+    output: BlockBuffer,
+}
+
+impl Gain {
+    pub fn new(unit: GainUnit) -> Self {
+        Self {
+            unit,
+            output: BlockBuffer::new(),
+        }
+    }
+
+    pub fn unit(&self) -> GainUnit {
+        self.unit
+    }
+}
+
+impl Node for Gain {
+    fn initialize() -> Self {
+        Self::default()
+    }
+
+    fn title(&self) -> &str {
+        "Gain"
+    }
+
+    fn fold(&self, _out_id: &dyn OutId, lasy_fold: LasyFold, meta: Meta) -> anyhow::Result<Data> {
+        let input = Signal::get(&lasy_fold, &GainInId::In, meta)?;
+        let gain = match self.unit {
+            GainUnit::Linear => Signal::get_or(&lasy_fold, &GainInId::Gain, meta, 1.0)?,
+            GainUnit::Decibels => Signal::get_or(&lasy_fold, &GainInId::Gain, meta, 0.0)?,
+        };
+
+        let channel_count = input.channel_count().max(gain.channel_count());
+        Ok(self.output.write(|block| {
+            block.fill_with(
+                channel_count,
+                meta.block_size,
+                meta.sample_rate,
+                |channel, frame| {
+                    let gain = gain.sample(channel, frame);
+                    let gain = match self.unit {
+                        GainUnit::Linear => gain,
+                        GainUnit::Decibels => 10f32.powf(gain / 20.0),
+                    };
+
+                    input.sample(channel, frame) * gain
+                },
+            )
+        }))
+    }
+
+    fn node_in_id(&self, in_id: &dyn InId, node_id: NodeId) -> Option<NodeInId> {
+        in_id
+            .as_any()
+            .downcast_ref::<GainInId>()
+            .map(|in_id| NodeInId::new(node_id, in_id))
+    }
+
+    fn node_out_id(&self, out_id: &dyn OutId, node_id: NodeId) -> Option<NodeOutId> {
+        out_id
+            .as_any()
+            .downcast_ref::<GainOutId>()
+            .map(|out_id| NodeOutId::new(node_id, out_id))
+    }
+
+    fn in_ids(&self) -> Vec<Box<dyn InId>> {
+        vec![Box::new(GainInId::In), Box::new(GainInId::Gain)]
+    }
+
+    fn out_ids(&self) -> Vec<Box<dyn OutId>> {
+        vec![Box::new(GainOutId::Out)]
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum PanInId {
+    In,
+    /// From `-1` for left to `1` for right, centered when not patched
+    Position,
+}
+
+impl InId for PanInId {}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum PanOutId {
+    Out,
+}
+
+impl OutId for PanOutId {}
+
+/// Place a signal in a stereo field, folding to a stereo block
+///
+/// A mono signal is panned with an equal-power law, each side being 3 dB down at the center so
+/// the loudness holds while it moves. A stereo signal is balanced instead : the center keeps both
+/// sides as they are, and moving toward a side fades the other one out.
+#[derive(Debug, Default)]
+pub struct Pan {
+    output: BlockBuffer,
+}
+
+impl Pan {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Node for Pan {
+    fn initialize() -> Self {
+        Self::default()
+    }
+
+    fn title(&self) -> &str {
+        "Pan"
+    }
+
+    fn fold(&self, _out_id: &dyn OutId, lasy_fold: LasyFold, meta: Meta) -> anyhow::Result<Data> {
+        let input = Signal::get(&lasy_fold, &PanInId::In, meta)?;
+        let position = Signal::get_or(&lasy_fold, &PanInId::Position, meta, 0.0)?;
+        let stereo = input.channel_count() >= 2;
+
+        Ok(self.output.write(|block| {
+            block.fill_with(2, meta.block_size, meta.sample_rate, |channel, frame| {
+                let position = position.sample(0, frame);
+                let position = if position.is_nan() {
+                    0.0
+                } else {
+                    position.clamp(-1.0, 1.0)
+                };
+
+                let angle = (position + 1.0) * FRAC_PI_4;
+                let gain = if channel == 0 {
+                    angle.cos()
+                } else {
+                    angle.sin()
+                };
+                let gain = if stereo {
+                    (gain * SQRT_2).min(1.0)
+                } else {
+                    gain
+                };
+
+                input.sample(channel, frame) * gain
+            })
+        }))
+    }
+
+    fn node_in_id(&self, in_id: &dyn InId, node_id: NodeId) -> Option<NodeInId> {
+        in_id
+            .as_any()
+            .downcast_ref::<PanInId>()
+            .map(|in_id| NodeInId::new(node_id, in_id))
+    }
+
+    fn node_out_id(&self, out_id: &dyn OutId, node_id: NodeId) -> Option<NodeOutId> {
+        out_id
+            .as_any()
+            .downcast_ref::<PanOutId>()
+            .map(|out_id| NodeOutId::new(node_id, out_id))
+    }
+
+    fn in_ids(&self) -> Vec<Box<dyn InId>> {
+        vec![Box::new(PanInId::In), Box::new(PanInId::Position)]
+    }
+
+    fn out_ids(&self) -> Vec<Box<dyn OutId>> {
+        vec![Box::new(PanOutId::Out)]
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum MixerInId {
+    /// A signal to mix, silent when not patched
+    In(usize),
+    /// The linear level of the input with the same index, `1` when not patched
+    Level(usize),
+}
+
+impl InId for MixerInId {
+    fn name(&self) -> String {
+        match self {
+            Self::In(index) => format!("In{}", index + 1),
+            Self::Level(index) => format!("Level{}", index + 1),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum MixerOutId {
+    Out,
+}
+
+impl OutId for MixerOutId {}
+
+/// Sum signals, each with its own level
+///
+/// The mix has as many channels as the widest input, mono inputs being heard on every channel.
+#[derive(Debug)]
+pub struct Mixer {
+    inputs: usize,
+    /// The signals of the patched inputs with their levels, kept to reuse the buffer
+    tracks: RefCell<Vec<(Signal, Signal)>>,
+    output: BlockBuffer,
+}
+
+impl Mixer {
+    pub fn new(inputs: usize) -> Self {
+        Self {
+            inputs,
+            tracks: RefCell::new(Vec::with_capacity(inputs)),
+            output: BlockBuffer::new(),
+        }
+    }
+
+    pub fn inputs(&self) -> usize {
+        self.inputs
+    }
+}
+
+impl Default for Mixer {
+    fn default() -> Self {
+        Self::new(2)
+    }
+}
+
+impl Node for Mixer {
+    fn initialize() -> Self {
+        Self::default()
+    }
+
+    fn title(&self) -> &str {
+        "Mixer"
+    }
+
+    fn fold(&self, _out_id: &dyn OutId, lasy_fold: LasyFold, meta: Meta) -> anyhow::Result<Data> {
+        let mut tracks = self.tracks.try_borrow_mut().map_err(|_| {
+            anyhow!("the mixer is folded while mixing, a feedback loop needs a delay")
+        })?;
+        tracks.clear();
+
+        for index in 0..self.inputs {
+            if !lasy_fold.is_patched(&MixerInId::In(index)) {
+                continue;
+            }
+
+            let input = Signal::get(&lasy_fold, &MixerInId::In(index), meta)?;
+            let level = Signal::get_or(&lasy_fold, &MixerInId::Level(index), meta, 1.0)?;
+            tracks.push((input, level));
+        }
+
+        let channel_count = tracks
+            .iter()
+            .map(|(input, _)| input.channel_count())
+            .max()
+            .unwrap_or(1);
+
+        let output = self.output.write(|block| {
+            block.fill_with(
+                channel_count,
+                meta.block_size,
+                meta.sample_rate,
+                |channel, frame| {
+                    tracks
+                        .iter()
+                        .map(|(input, level)| {
+                            input.sample(channel, frame) * level.sample(channel, frame)
+                        })
+                        .sum()
+                },
+            )
+        });
+        // Let the inputs write over their blocks on the next tick
+        tracks.clear();
+
+        Ok(output)
+    }
+
+    fn node_in_id(&self, in_id: &dyn InId, node_id: NodeId) -> Option<NodeInId> {
+        in_id
+            .as_any()
+            .downcast_ref::<MixerInId>()
+            .filter(|in_id| match in_id {
+                MixerInId::In(index) | MixerInId::Level(index) => *index < self.inputs,
+            })
+            .map(|in_id| NodeInId::new(node_id, in_id))
+    }
+
+    fn node_out_id(&self, out_id: &dyn OutId, node_id: NodeId) -> Option<NodeOutId> {
+        out_id
+            .as_any()
+            .downcast_ref::<MixerOutId>()
+            .map(|out_id| NodeOutId::new(node_id, out_id))
+    }
+
+    fn in_ids(&self) -> Vec<Box<dyn InId>> {
+        (0..self.inputs)
+            .flat_map(|index| -> [Box<dyn InId>; 2] {
+                [
+                    Box::new(MixerInId::In(index)),
+                    Box::new(MixerInId::Level(index)),
+                ]
+            })
+            .collect()
+    }
+
+    fn out_ids(&self) -> Vec<Box<dyn OutId>> {
+        vec![Box::new(MixerOutId::Out)]
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ChannelSplitInId {
+    In,
+}
+
+impl InId for ChannelSplitInId {}
+
+/// A channel of the input, as a mono signal
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ChannelSplitOutId(pub usize);
+
+impl OutId for ChannelSplitOutId {
+    fn name(&self) -> String {
+        format!("Out{}", self.0 + 1)
+    }
+}
+
+/// Split a signal in mono signals, one per channel
+///
+/// Reading past the channels of the input wraps around, so a mono input is copied on every output.
+#[derive(Debug)]
+pub struct ChannelSplit {
+    channels: usize,
+    /// A buffer per output
+    outputs: Vec<BlockBuffer>,
+}
+
+impl ChannelSplit {
+    pub fn new(channels: usize) -> Self {
+        Self {
+            channels,
+            outputs: (0..channels).map(|_| BlockBuffer::new()).collect(),
+        }
+    }
+
+    pub fn channels(&self) -> usize {
+        self.channels
+    }
+}
+
+impl Default for ChannelSplit {
+    fn default() -> Self {
+        Self::new(2)
+    }
+}
+
+impl Node for ChannelSplit {
+    fn initialize() -> Self {
+        Self::default()
+    }
+
+    fn title(&self) -> &str {
+        "Channel split"
+    }
+
+    fn fold(&self, out_id: &dyn OutId, lasy_fold: LasyFold, meta: Meta) -> anyhow::Result<Data> {
+        let Some(ChannelSplitOutId(channel)) = out_id.as_any().downcast_ref::<ChannelSplitOutId>()
+        else {
+            return Err(anyhow!("not a valid out_id"));
+        };
+        let Some(output) = self.outputs.get(*channel) else {
+            return Err(anyhow!("not a valid out_id"));
+        };
+
+        let input = Signal::get(&lasy_fold, &ChannelSplitInId::In, meta)?;
+        Ok(output.write(|block| {
+            block.fill_with(1, meta.block_size, meta.sample_rate, |_, frame| {
+                input.sample(*channel, frame)
+            })
+        }))
+    }
+
+    fn node_in_id(&self, in_id: &dyn InId, node_id: NodeId) -> Option<NodeInId> {
+        in_id
+            .as_any()
+            .downcast_ref::<ChannelSplitInId>()
+            .map(|in_id| NodeInId::new(node_id, in_id))
+    }
+
+    fn node_out_id(&self, out_id: &dyn OutId, node_id: NodeId) -> Option<NodeOutId> {
+        out_id
+            .as_any()
+            .downcast_ref::<ChannelSplitOutId>()
+            .filter(|out_id| out_id.0 < self.channels)
+            .map(|out_id| NodeOutId::new(node_id, out_id))
+    }
+
+    fn in_ids(&self) -> Vec<Box<dyn InId>> {
+        vec![Box::new(ChannelSplitInId::In)]
+    }
+
+    fn out_ids(&self) -> Vec<Box<dyn OutId>> {
+        (0..self.channels)
+            .map(|channel| Box::new(ChannelSplitOutId(channel)) as Box<dyn OutId>)
+            .collect()
+    }
+}
+
+/// A channel of the output, the first channel of the signal being used, silent when not patched
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ChannelMergeInId(pub usize);
+
+impl InId for ChannelMergeInId {
+    fn name(&self) -> String {
+        format!("In{}", self.0 + 1)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ChannelMergeOutId {
+    Out,
+}
+
+impl OutId for ChannelMergeOutId {}
+
+/// Merge mono signals in one signal, one channel per input
+#[derive(Debug)]
+pub struct ChannelMerge {
+    channels: usize,
+    /// The signals of the inputs, kept to reuse the buffer
+    inputs: RefCell<Vec<Signal>>,
+    output: BlockBuffer,
+}
+
+impl ChannelMerge {
+    pub fn new(channels: usize) -> Self {
+        Self {
+            channels,
+            inputs: RefCell::new(Vec::with_capacity(channels)),
+            output: BlockBuffer::new(),
+        }
+    }
+
+    pub fn channels(&self) -> usize {
+        self.channels
+    }
+}
+
+impl Default for ChannelMerge {
+    fn default() -> Self {
+        Self::new(2)
+    }
+}
+
+impl Node for ChannelMerge {
+    fn initialize() -> Self {
+        Self::default()
+    }
+
+    fn title(&self) -> &str {
+        "Channel merge"
+    }
+
+    fn fold(&self, _out_id: &dyn OutId, lasy_fold: LasyFold, meta: Meta) -> anyhow::Result<Data> {
+        let mut inputs = self.inputs.try_borrow_mut().map_err(|_| {
+            anyhow!("the merge is folded while merging, a feedback loop needs a delay")
+        })?;
+        inputs.clear();
+
+        for channel in 0..self.channels {
+            inputs.push(Signal::get_or(
+                &lasy_fold,
+                &ChannelMergeInId(channel),
+                meta,
+                0.0,
+            )?);
+        }
+
+        let output = self.output.write(|block| {
+            block.fill_with(
+                self.channels,
+                meta.block_size,
+                meta.sample_rate,
+                |channel, frame| inputs[channel].sample(0, frame),
+            )
+        });
+        // Let the inputs write over their blocks on the next tick
+        inputs.clear();
+
+        Ok(output)
+    }
+
+    fn node_in_id(&self, in_id: &dyn InId, node_id: NodeId) -> Option<NodeInId> {
+        in_id
+            .as_any()
+            .downcast_ref::<ChannelMergeInId>()
+            .filter(|in_id| in_id.0 < self.channels)
+            .map(|in_id| NodeInId::new(node_id, in_id))
+    }
+
+    fn node_out_id(&self, out_id: &dyn OutId, node_id: NodeId) -> Option<NodeOutId> {
+        out_id
+            .as_any()
+            .downcast_ref::<ChannelMergeOutId>()
+            .map(|out_id| NodeOutId::new(node_id, out_id))
+    }
+
+    fn in_ids(&self) -> Vec<Box<dyn InId>> {
+        (0..self.channels)
+            .map(|channel| Box::new(ChannelMergeInId(channel)) as Box<dyn InId>)
+            .collect()
+    }
+
+    fn out_ids(&self) -> Vec<Box<dyn OutId>> {
+        vec![Box::new(ChannelMergeOutId::Out)]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use quakk::{GraphOutInId, GraphOutOutId, Quakk, numeric::*};
+
+    use super::*;
+    use crate::{Transport, testing::render_node};
+
+    #[test]
+    fn gain_in_decibels() {
+        let block = render_node(
+            Box::new(Gain::new(GainUnit::Decibels)),
+            &GainOutId::Out,
+            &[(&GainInId::In, 0.5), (&GainInId::Gain, 20.0)],
+            48_000,
+            4,
+            4,
+        );
+        assert!((block.sample(0, 0) - 5.0).abs() < 1e-5);
+
+        let block = render_node(
+            Box::new(Gain::new(GainUnit::Linear)),
+            &GainOutId::Out,
+            &[(&GainInId::In, 0.5)],
+            48_000,
+            4,
+            4,
+        );
+        assert_eq!(block.sample(0, 0), 0.5);
+    }
+
+    #[test]
+    fn pan_keeps_the_power() {
+        for position in [-1.0, -0.3, 0.0, 0.6, 1.0] {
+            let block = render_node(
+                Box::new(Pan::new()),
+                &PanOutId::Out,
+                &[(&PanInId::In, 1.0), (&PanInId::Position, position)],
+                48_000,
+                4,
+                4,
+            );
+            let (left, right) = (block.sample(0, 0), block.sample(1, 0));
+
+            assert_eq!(block.channel_count(), 2);
+            assert!((left * left + right * right - 1.0).abs() < 1e-5);
+        }
+
+        let block = render_node(
+            Box::new(Pan::new()),
+            &PanOutId::Out,
+            &[(&PanInId::In, 1.0), (&PanInId::Position, -1.0)],
+            48_000,
+            4,
+            4,
+        );
+        assert!((block.sample(0, 0) - 1.0).abs() < 1e-6);
+        assert!(block.sample(1, 0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn mixer_sums_its_inputs() {
+        let block = render_node(
+            Box::new(Mixer::new(3)),
+            &MixerOutId::Out,
+            &[
+                (&MixerInId::In(0), 0.5),
+                (&MixerInId::In(2), 0.25),
+                (&MixerInId::Level(2), 2.0),
+                // The level of an unpatched input is ignored
+                (&MixerInId::Level(1), 4.0),
+            ],
+            48_000,
+            4,
+            4,
+        );
+        assert_eq!(block.channels(), [vec![1.0; 4]]);
+
+        assert_eq!(MixerInId::Level(2).name(), "Level3");
+        assert!(
+            Mixer::new(3)
+                .node_in_id(&MixerInId::In(3), NodeId::new_node())
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn splits_and_merges_channels() {
+        let quakk = Quakk::new();
+        {
+            let mut graph = quakk.graph.lock().unwrap();
+            let left = graph.insert(Box::new(NumericConstant::new(1.0)));
+            let right = graph.insert(Box::new(NumericConstant::new(2.0)));
+            let merge = graph.insert(Box::new(ChannelMerge::new(2)));
+            let split = graph.insert(Box::new(ChannelSplit::new(2)));
+            let out = graph.graph_out_in_id(&GraphOutInId::Numeric).unwrap();
+
+            for (constant, channel) in [(left, 0), (right, 1)] {
+                graph
+                    .patch(
+                        constant.node_out_id(&NumericConstantOutId::Out).unwrap(),
+                        merge.node_in_id(&ChannelMergeInId(channel)).unwrap(),
+                    )
+                    .unwrap();
+            }
+            graph
+                .patch(
+                    merge.node_out_id(&ChannelMergeOutId::Out).unwrap(),
+                    split.node_in_id(&ChannelSplitInId::In).unwrap(),
+                )
+                .unwrap();
+            graph
+                .patch(split.node_out_id(&ChannelSplitOutId(1)).unwrap(), out)
+                .unwrap();
+        }
+
+        let block = Transport::new(48_000, 4)
+            .unwrap()
+            .next_block(&quakk, GraphOutOutId::Numeric)
+            .unwrap();
+        assert_eq!(block.channels(), [vec![2.0; 4]]);
+    }
+}
@@ -0,0 +1,484 @@
+//! Band-limited oscillators
+//!
+//! Saw and pulse waves have discontinuities that alias badly when sampled naively, they are
+//! smoothed with PolyBLEP (polynomial band-limited step) around each discontinuity.
+
+use anyhow::{Context, anyhow};
+use quakk::{
+    Data, LasyFold, Meta, Node, NodeState, Progress,
+    id::{InId, NodeId, NodeInId, NodeOutId, OutId},
+};
+
+use crate::{AudioBlock, Signal};
+
+/// The PolyBLEP residual to add around a unit step, `t` being the phase in `[0, 1)` and `dt` the
+/// phase increment per sample
+fn poly_blep(t: f64, dt: f64) -> f64 {
+    if t < dt {
+        let t = t / dt;
+        t + t - t * t - 1.0
+    } else if t > 1.0 - dt {
+        let t = (t - 1.0) / dt;
+        t * t + t + t + 1.0
+    } else {
+        0.0
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Waveform {
+    #[default]
+    Sine,
+    Saw,
+    /// A pulse wave whose high part lasts `PulseWidth` of the cycle, a square wave at `0.5`
+    Pulse,
+    /// A triangle wave, its harmonics fall fast enough that it is computed without correction
+    Triangle,
+}
+
+impl Waveform {
+    /// The value of the wave at phase `t` in `[0, 1)`, `dt` being the phase increment per sample
+    pub(crate) fn sample(&self, t: f64, dt: f64, pulse_width: f64) -> f64 {
+        use std::f64::consts::TAU;
+        match self {
+            Waveform::Sine => (t * TAU).sin(),
+            Waveform::Saw => 2.0 * t - 1.0 - poly_blep(t, dt),
+            Waveform::Pulse => {
+                let naive = if t < pulse_width { 1.0 } else { -1.0 };
+                naive + poly_blep(t, dt) - poly_blep((t - pulse_width).rem_euclid(1.0), dt)
+            }
+            Waveform::Triangle => 1.0 - 4.0 * (t - 0.5).abs(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum OscillatorInId {
+    /// The frequency in Hz, `440` when not patched
+    Frequency,
+    /// An offset of the phase, in cycles, `0` when not patched
+    Phase,
+    /// The width of the pulse wave between `0` and `1`, `0.5` when not patched
+    PulseWidth,
+}
+
+impl InId for OscillatorInId {}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum OscillatorOutId {
+    Out,
+}
+
+impl OutId for OscillatorOutId {}
+
+/// An oscillator folding to a mono [`AudioBlock`] each tick
+///
+/// The phase carries over from one block to the next, so consecutive blocks join without clicks
+#[derive(Debug, Default)]
+pub struct Oscillator {
+    waveform: Waveform,
+    /// The phase at the start of the next block, in cycles
+    phase: NodeState<f64>,
+}
+
+impl Oscillator {
+    pub fn new(waveform: Waveform) -> Self {
+        Self {
+            waveform,
+            phase: NodeState::default(),
+        }
+    }
+
+    pub fn waveform(&self) -> Waveform {
+        self.waveform
+    }
+}
+
+impl Node for Oscillator {
+    fn initialize() -> Self {
+        Self::default()
+    }
+
+    fn title(&self) -> &str {
+        "Oscillator"
+    }
+
+    fn fold(&self, _out_id: &dyn OutId, lasy_fold: LasyFold, meta: Meta) -> anyhow::Result<Data> {
+        self.phase.step_reusing(meta, |phase, last| {
+            let frequency = Signal::get_or(&lasy_fold, &OscillatorInId::Frequency, meta, 440.0)?;
+            let offset = Signal::get_or(&lasy_fold, &OscillatorInId::Phase, meta, 0.0)?;
+            let pulse_width = if self.waveform == Waveform::Pulse {
+                Signal::get_or(&lasy_fold, &OscillatorInId::PulseWidth, meta, 0.5)?
+            } else {
+                Signal::Constant(0.5)
+            };
+
+            if meta.sample_rate == 0 {
+                return Err(anyhow!("the sample rate is 0"));
+            }
+            let sample_rate = meta.sample_rate as f64;
+
+            Ok(AudioBlock::reuse(last, |block| {
+                block.fill_with(1, meta.block_size, meta.sample_rate, |_, frame| {
+                    let dt = frequency.sample(0, frame) as f64 / sample_rate;
+                    let t = (*phase + offset.sample(0, frame) as f64).rem_euclid(1.0);
+                    let pulse_width = (pulse_width.sample(0, frame) as f64).clamp(0.0, 1.0);
+
+                    // A single NaN or infinite frequency would leave the phase NaN for good
+                    if dt.is_finite() {
+                        *phase = (*phase + dt).rem_euclid(1.0);
+                    }
+                    self.waveform.sample(t, dt.abs().min(0.5), pulse_width) as f32
+                })
+            }))
+        })
+    }
+
+    fn node_in_id(&self, in_id: &dyn InId, node_id: NodeId) -> Option<NodeInId> {
+        in_id
+            .as_any()
+            .downcast_ref::<OscillatorInId>()
+            .filter(|in_id| {
+                **in_id != OscillatorInId::PulseWidth || self.waveform == Waveform::Pulse
+            })
+            .map(|in_id| NodeInId::new(node_id, in_id))
+    }
+
+    fn node_out_id(&self, out_id: &dyn OutId, node_id: NodeId) -> Option<NodeOutId> {
+        out_id
+            .as_any()
+            .downcast_ref::<OscillatorOutId>()
+            .map(|out_id| NodeOutId::new(node_id, out_id))
+    }
+
+    fn in_ids(&self) -> Vec<Box<dyn InId>> {
+        let mut in_ids: Vec<Box<dyn InId>> = vec![
+            Box::new(OscillatorInId::Frequency),
+            Box::new(OscillatorInId::Phase),
+        ];
+        if self.waveform == Waveform::Pulse {
+            in_ids.push(Box::new(OscillatorInId::PulseWidth));
+        }
+
+        in_ids
+    }
+
+    fn out_ids(&self) -> Vec<Box<dyn OutId>> {
+        vec![Box::new(OscillatorOutId::Out)]
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum LFOInId {
+    /// The rate in Hz, or in cycles per beat when `Beat` is patched, `1` when not patched
+    Rate,
+    /// The count of beats, like the `Beat` output of a `Clock`, optional
+    Beat,
+    /// An offset of the phase, in cycles, `0` when not patched
+    Phase,
+    /// The width of the pulse wave between `0` and `1`, `0.5` when not patched
+    PulseWidth,
+}
+
+impl InId for LFOInId {}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum LFOOutId {
+    Out,
+}
+
+impl OutId for LFOOutId {}
+
+/// A low frequency oscillator, folding to a number between `-1` and `1` each tick
+///
+/// Left alone, the phase is a [`Progress`] moving forward by the ticks elapsed. Once `Beat` is
+/// patched, the phase is read from the beat instead, keeping the LFO locked to the tempo, even
+/// when the tempo changes. The inputs are read once per tick, the first frame of a block
+/// standing for the whole block.
+#[derive(Debug, Default)]
+pub struct LFO {
+    waveform: Waveform,
+    /// The phase when free running, in cycles
+    phase: NodeState<Progress>,
+}
+
+impl LFO {
+    pub fn new(waveform: Waveform) -> Self {
+        Self {
+            waveform,
+            phase: NodeState::default(),
+        }
+    }
+
+    pub fn waveform(&self) -> Waveform {
+        self.waveform
+    }
+}
+
+impl Node for LFO {
+    fn initialize() -> Self {
+        Self::default()
+    }
+
+    fn title(&self) -> &str {
+        "LFO"
+    }
+
+    fn fold(&self, _out_id: &dyn OutId, lasy_fold: LasyFold, meta: Meta) -> anyhow::Result<Data> {
+        self.phase.step(meta, |phase| {
+            let rate = Signal::get_or(&lasy_fold, &LFOInId::Rate, meta, 1.0)?.sample(0, 0) as f64;
+            let offset = Signal::get_or(&lasy_fold, &LFOInId::Phase, meta, 0.0)?.sample(0, 0);
+            let pulse_width = if self.waveform == Waveform::Pulse {
+                Signal::get_or(&lasy_fold, &LFOInId::PulseWidth, meta, 0.5)?.sample(0, 0)
+            } else {
+                0.5
+            };
+
+            let current = if lasy_fold.is_patched(&LFOInId::Beat) {
+                let beat = lasy_fold
+                    .get_in(&LFOInId::Beat, meta)?
+                    .to_f64()
+                    .context("invalid Beat")?;
+                (beat * rate).rem_euclid(1.0)
+            } else {
+                phase
+                    .advance(meta.tick, rate / meta.tick_rate)
+                    .rem_euclid(1.0)
+            };
+
+            let t = (current + offset as f64).rem_euclid(1.0);
+            let pulse_width = (pulse_width as f64).clamp(0.0, 1.0);
+
+            Ok(Data::new(self.waveform.sample(t, 0.0, pulse_width) as f32))
+        })
+    }
+
+    fn node_in_id(&self, in_id: &dyn InId, node_id: NodeId) -> Option<NodeInId> {
+        in_id
+            .as_any()
+            .downcast_ref::<LFOInId>()
+            .filter(|in_id| **in_id != LFOInId::PulseWidth || self.waveform == Waveform::Pulse)
+            .map(|in_id| NodeInId::new(node_id, in_id))
+    }
+
+    fn node_out_id(&self, out_id: &dyn OutId, node_id: NodeId) -> Option<NodeOutId> {
+        out_id
+            .as_any()
+            .downcast_ref::<LFOOutId>()
+            .map(|out_id| NodeOutId::new(node_id, out_id))
+    }
+
+    fn in_ids(&self) -> Vec<Box<dyn InId>> {
+        let mut in_ids: Vec<Box<dyn InId>> = vec![
+            Box::new(LFOInId::Rate),
+            Box::new(LFOInId::Beat),
+            Box::new(LFOInId::Phase),
+        ];
+        if self.waveform == Waveform::Pulse {
+            in_ids.push(Box::new(LFOInId::PulseWidth));
+        }
+
+        in_ids
+    }
+
+    fn out_ids(&self) -> Vec<Box<dyn OutId>> {
+        vec![Box::new(LFOOutId::Out)]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use quakk::GraphOutOutId;
+
+    use super::*;
+    use crate::testing::{patch_node, patch_node_to_formulas, render_node};
+
+    /// Fold an LFO at the given ticks, at 4 ticks per second, each input patched to a constant
+    fn lfo(
+        waveform: Waveform,
+        inputs: &[(LFOInId, f32)],
+        ticks: impl IntoIterator<Item = u64>,
+    ) -> Vec<f32> {
+        let inputs: Vec<(&dyn InId, f32)> = inputs
+            .iter()
+            .map(|(in_id, value)| (in_id as &dyn InId, *value))
+            .collect();
+        let quakk = patch_node(Box::new(LFO::new(waveform)), &LFOOutId::Out, &inputs);
+
+        ticks
+            .into_iter()
+            .map(|tick| {
+                let meta = Meta {
+                    tick,
+                    tick_rate: 4.0,
+                    ..quakk.base_meta
+                };
+                quakk
+                    .fold_with(GraphOutOutId::Numeric, meta)
+                    .unwrap()
+                    .as_f32()
+                    .unwrap()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn lfo_runs_free_or_locked_to_a_beat() {
+        // At 1 Hz and 4 ticks per second, a saw moves a quarter of a cycle each tick
+        let free = lfo(Waveform::Saw, &[], 0..5);
+        assert_eq!(free, [-1.0, -0.5, 0.0, 0.5, -1.0]);
+        // Ticks left unfolded still count
+        let free = lfo(Waveform::Saw, &[], [0, 3, 5]);
+        assert_eq!(free, [-1.0, 0.5, -0.5]);
+
+        // Half a cycle per beat, at beat 1.5
+        let locked = lfo(
+            Waveform::Triangle,
+            &[(LFOInId::Beat, 1.5), (LFOInId::Rate, 0.5)],
+            0..2,
+        );
+        assert_eq!(locked, [0.0, 0.0]);
+        let locked = lfo(
+            Waveform::Pulse,
+            &[(LFOInId::Beat, 1.5), (LFOInId::PulseWidth, 0.8)],
+            0..1,
+        );
+        assert_eq!(locked, [1.0]);
+    }
+
+    /// Fold `out_id` of `node` at ticks 0 and 1 of blocks of 16 frames, `in_id` being NaN on
+    /// tick 0 and `value` from tick 1
+    fn fold_after_nan(
+        node: Box<dyn Node>,
+        in_id: &dyn InId,
+        out_id: &dyn OutId,
+        value: f32,
+    ) -> Vec<Data> {
+        // `ln(0)` is infinite, and `0` times infinity is NaN
+        let formula = format!("{value} + 0 * ln(tick)");
+        let quakk = patch_node_to_formulas(node, out_id, &[(in_id, &formula)]);
+
+        (0..2)
+            .map(|tick| {
+                let meta = Meta {
+                    tick,
+                    tick_rate: 4.0,
+                    ..quakk.base_meta.with_audio(48_000, 16)
+                };
+                quakk.fold_with(GraphOutOutId::Numeric, meta).unwrap()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn phase_recovers_from_nan() {
+        // The phase holds still on the NaN block, the next one starting like a fresh oscillator
+        let blocks = fold_after_nan(
+            Box::new(Oscillator::new(Waveform::Saw)),
+            &OscillatorInId::Frequency,
+            &OscillatorOutId::Out,
+            1000.0,
+        );
+        let block = blocks[1].downcast_ref::<AudioBlock>().unwrap();
+        assert_eq!(
+            block.channels()[0],
+            render_node(
+                Box::new(Oscillator::new(Waveform::Saw)),
+                &OscillatorOutId::Out,
+                &[(&OscillatorInId::Frequency, 1000.0)],
+                48_000,
+                16,
+                16
+            )
+            .into_channels()
+            .remove(0)
+        );
+
+        let values = fold_after_nan(
+            Box::new(LFO::new(Waveform::Saw)),
+            &LFOInId::Rate,
+            &LFOOutId::Out,
+            1.0,
+        );
+        assert_eq!(values[1].as_f32().unwrap(), -0.5);
+    }
+
+    #[test]
+    fn phase_carries_over_blocks() {
+        let whole = render_node(
+            Box::new(Oscillator::new(Waveform::Sine)),
+            &OscillatorOutId::Out,
+            &[(&OscillatorInId::Frequency, 1000.0)],
+            48_000,
+            96,
+            96,
+        )
+        .into_channels()
+        .remove(0);
+        let split = render_node(
+            Box::new(Oscillator::new(Waveform::Sine)),
+            &OscillatorOutId::Out,
+            &[(&OscillatorInId::Frequency, 1000.0)],
+            48_000,
+            7,
+            96,
+        )
+        .into_channels()
+        .remove(0);
+
+        for (whole, split) in whole.iter().zip(&split) {
+            assert!((whole - split).abs() < 1e-5);
+        }
+        // A 1000 Hz sine at 48 kHz is back to 0 after 48 samples
+        assert!(split[48].abs() < 1e-4);
+        assert!((split[12] - 1.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn waveforms_stay_in_range() {
+        for waveform in [
+            Waveform::Sine,
+            Waveform::Saw,
+            Waveform::Pulse,
+            Waveform::Triangle,
+        ] {
+            let samples = render_node(
+                Box::new(Oscillator::new(waveform)),
+                &OscillatorOutId::Out,
+                &[(&OscillatorInId::Frequency, 3_000.0)],
+                48_000,
+                64,
+                480,
+            )
+            .into_channels()
+            .remove(0);
+            assert!(samples.iter().all(|sample| sample.abs() <= 1.0 + 1e-4));
+
+            // The waveforms have no DC offset
+            let mean = samples.iter().sum::<f32>() / samples.len() as f32;
+            assert!(mean.abs() < 0.05, "{waveform:?} has a mean of {mean}");
+        }
+    }
+
+    #[test]
+    fn poly_blep_smooths_the_saw_step() {
+        // Right after the wrap, the naive saw would jump from 1 to -1
+        let dt = 0.1;
+        let before = Waveform::Saw.sample(0.95, dt, 0.5);
+        let after = Waveform::Saw.sample(0.05, dt, 0.5);
+
+        let naive = (2.0 * 0.95 - 1.0) - (2.0 * 0.05 - 1.0);
+        assert!((before - after).abs() < naive * 0.75);
+    }
+
+    #[test]
+    fn pulse_width_is_only_a_pulse_input() {
+        let oscillator = Oscillator::new(Waveform::Saw);
+        assert!(
+            oscillator
+                .node_in_id(&OscillatorInId::PulseWidth, NodeId::GraphIn)
+                .is_none()
+        );
+        assert_eq!(Oscillator::new(Waveform::Pulse).in_ids().len(), 3);
+    }
+}
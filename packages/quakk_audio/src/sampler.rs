@@ -0,0 +1,408 @@
+//! Playing back recorded audio
+
+use std::{fmt, fs::File, io::BufReader, path::Path, sync::Arc};
+
+use anyhow::Context;
+use quakk::{
+    Data, LasyFold, Meta, Node, NodeState,
+    id::{InId, NodeId, NodeInId, NodeOutId, OutId},
+};
+
+use crate::{AudioBlock, Signal, wav::read_wav};
+
+#[derive(Debug, Default)]
+struct SamplerState {
+    /// The read position in frames of the sample, `None` when stopped
+    position: Option<f64>,
+    gate: bool,
+    /// The positions read during the current block, kept to reuse the buffer
+    positions: Vec<Option<f64>>,
+}
+
+/// The region of the sample being played, in frames of the sample
+#[derive(Debug, Clone, Copy)]
+struct Region {
+    start: f64,
+    stop: f64,
+    looping: bool,
+    loop_start: f64,
+    loop_end: f64,
+}
+
+impl SamplerState {
+    /// Advance by one frame, returning the position to read
+    fn next(&mut self, gate: bool, region: Region, increment: f64) -> Option<f64> {
+        let rising_gate = gate && !self.gate;
+        self.gate = gate;
+
+        if rising_gate {
+            // Backwards from the last frame before the stop, the stop itself not being played
+            self.position = Some(if increment < 0.0 {
+                (region.stop - 1.0).max(region.start)
+            } else {
+                region.start
+            });
+        } else if !gate {
+            self.position = None;
+        }
+
+        let position = self.position?;
+        let mut next = position + increment;
+
+        let loop_length = region.loop_end - region.loop_start;
+        if region.looping && loop_length > 0.0 {
+            let wraps = if increment < 0.0 {
+                next < region.loop_start
+            } else {
+                next >= region.loop_end
+            };
+            if wraps {
+                next = region.loop_start + (next - region.loop_start).rem_euclid(loop_length);
+            }
+            self.position = Some(next);
+        } else if (region.start..region.stop).contains(&next) {
+            self.position = Some(next);
+        } else {
+            self.position = None;
+        }
+
+        (region.start..region.stop)
+            .contains(&position)
+            .then_some(position)
+    }
+}
+
+/// A cubic Hermite interpolation between `b` and `c`, `a` and `d` being their neighbours
+fn hermite(a: f32, b: f32, c: f32, d: f32, fraction: f32) -> f32 {
+    let c1 = 0.5 * (c - a);
+    let c2 = a - 2.5 * b + 2.0 * c - 0.5 * d;
+    let c3 = 0.5 * (d - a) + 1.5 * (b - c);
+
+    ((c3 * fraction + c2) * fraction + c1) * fraction + b
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum SamplerInId {
+    /// The sample plays while the gate is above `0.5`, from the start on each rising edge,
+    /// continuously when not patched
+    Gate,
+    /// Where playing starts, in seconds of the sample
+    Start,
+    /// Where playing stops, in seconds of the sample, the end of the sample when not patched
+    Stop,
+    /// Loop between the loop points while above `0.5`
+    Loop,
+    /// The start of the loop, in seconds of the sample
+    LoopStart,
+    /// The end of the loop, in seconds of the sample, the stop when not patched
+    LoopEnd,
+    /// The playback rate, `2` playing an octave higher and negative rates playing backwards
+    Rate,
+}
+
+impl InId for SamplerInId {}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum SamplerOutId {
+    Out,
+}
+
+impl OutId for SamplerOutId {}
+
+/// Plays an [`AudioBlock`] loaded in memory, folding to a block with the channels of the sample
+///
+/// The sample is resampled to [`Meta::sample_rate`] when its own rate differs. Positions are
+/// read once per frame, so they can be modulated by blocks.
+pub struct Sampler {
+    sample: Arc<AudioBlock>,
+    state: NodeState<SamplerState>,
+}
+
+impl Sampler {
+    pub fn new(sample: AudioBlock) -> Self {
+        Self {
+            sample: Arc::new(sample),
+            state: NodeState::default(),
+        }
+    }
+
+    /// A sampler playing a WAV file
+    pub fn load(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let file =
+            File::open(path).with_context(|| format!("could not open {}", path.display()))?;
+        let sample = read_wav(BufReader::new(file))
+            .with_context(|| format!("invalid {}", path.display()))?;
+
+        Ok(Self::new(sample))
+    }
+
+    pub fn sample(&self) -> &AudioBlock {
+        &self.sample
+    }
+
+    /// The sample at a frame, silent outside of the sample
+    fn frame(&self, channel: usize, frame: i64) -> f32 {
+        usize::try_from(frame)
+            .map(|frame| self.sample.sample(channel, frame))
+            .unwrap_or(0.0)
+    }
+
+    fn interpolate(&self, channel: usize, position: f64) -> f32 {
+        let index = position.floor() as i64;
+        let fraction = (position - position.floor()) as f32;
+
+        hermite(
+            self.frame(channel, index - 1),
+            self.frame(channel, index),
+            self.frame(channel, index + 1),
+            self.frame(channel, index + 2),
+            fraction,
+        )
+    }
+}
+
+impl Default for Sampler {
+    fn default() -> Self {
+        Self::new(AudioBlock::new(1, 0, 48_000))
+    }
+}
+
+impl fmt::Debug for Sampler {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Sampler")
+            .field("channels", &self.sample.channel_count())
+            .field("frames", &self.sample.frames())
+            .field("sample_rate", &self.sample.sample_rate())
+            .finish_non_exhaustive()
+    }
+}
+
+impl Node for Sampler {
+    fn initialize() -> Self {
+        Self::default()
+    }
+
+    fn title(&self) -> &str {
+        "Sampler"
+    }
+
+    fn fold(&self, _out_id: &dyn OutId, lasy_fold: LasyFold, meta: Meta) -> anyhow::Result<Data> {
+        self.state.step_reusing(meta, |state, last| {
+            let gate = Signal::get_or(&lasy_fold, &SamplerInId::Gate, meta, 1.0)?;
+            let start = Signal::get_or(&lasy_fold, &SamplerInId::Start, meta, 0.0)?;
+            let stop = Signal::get_or(&lasy_fold, &SamplerInId::Stop, meta, f32::INFINITY)?;
+            let looping = Signal::get_or(&lasy_fold, &SamplerInId::Loop, meta, 0.0)?;
+            let loop_start = Signal::get_or(&lasy_fold, &SamplerInId::LoopStart, meta, 0.0)?;
+            let loop_end = Signal::get_or(&lasy_fold, &SamplerInId::LoopEnd, meta, f32::INFINITY)?;
+            let rate = Signal::get_or(&lasy_fold, &SamplerInId::Rate, meta, 1.0)?;
+
+            let sample_rate = self.sample.sample_rate() as f64;
+            let length = self.sample.frames() as f64;
+            let resampling = sample_rate / meta.sample_rate as f64;
+
+            let mut positions = std::mem::take(&mut state.positions);
+            positions.clear();
+            positions.extend((0..meta.block_size).map(|frame| {
+                let frames = |signal: &Signal| {
+                    let seconds = signal.sample(0, frame) as f64;
+                    if seconds.is_nan() {
+                        0.0
+                    } else {
+                        (seconds * sample_rate).clamp(0.0, length)
+                    }
+                };
+                let start = frames(&start);
+                let stop = frames(&stop).max(start);
+                let region = Region {
+                    start,
+                    stop,
+                    looping: looping.sample(0, frame) > 0.5,
+                    loop_start: frames(&loop_start).clamp(start, stop),
+                    loop_end: frames(&loop_end).clamp(start, stop),
+                };
+
+                let rate = rate.sample(0, frame) as f64;
+                let increment = if rate.is_finite() {
+                    rate * resampling
+                } else {
+                    0.0
+                };
+
+                state.next(gate.sample(0, frame) > 0.5, region, increment)
+            }));
+
+            let data = AudioBlock::reuse(last, |block| {
+                block.fill_with(
+                    self.sample.channel_count().max(1),
+                    meta.block_size,
+                    meta.sample_rate,
+                    |channel, frame| {
+                        positions[frame]
+                            .map(|position| self.interpolate(channel, position))
+                            .unwrap_or(0.0)
+                    },
+                )
+            });
+            state.positions = positions;
+
+            Ok(data)
+        })
+    }
+
+    fn node_in_id(&self, in_id: &dyn InId, node_id: NodeId) -> Option<NodeInId> {
+        in_id
+            .as_any()
+            .downcast_ref::<SamplerInId>()
+            .map(|in_id| NodeInId::new(node_id, in_id))
+    }
+
+    fn node_out_id(&self, out_id: &dyn OutId, node_id: NodeId) -> Option<NodeOutId> {
+        out_id
+            .as_any()
+            .downcast_ref::<SamplerOutId>()
+            .map(|out_id| NodeOutId::new(node_id, out_id))
+    }
+
+    fn in_ids(&self) -> Vec<Box<dyn InId>> {
+        vec![
+            Box::new(SamplerInId::Gate),
+            Box::new(SamplerInId::Start),
+            Box::new(SamplerInId::Stop),
+            Box::new(SamplerInId::Loop),
+            Box::new(SamplerInId::LoopStart),
+            Box::new(SamplerInId::LoopEnd),
+            Box::new(SamplerInId::Rate),
+        ]
+    }
+
+    fn out_ids(&self) -> Vec<Box<dyn OutId>> {
+        vec![Box::new(SamplerOutId::Out)]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::render_node;
+
+    /// A ramp from `0` to `frames - 1`, easy to check positions with
+    fn ramp(frames: usize, sample_rate: u32) -> AudioBlock {
+        AudioBlock::from_fn(1, frames, sample_rate, |_, frame| frame as f32)
+    }
+
+    #[test]
+    fn plays_once_between_start_and_stop() {
+        // From frame 8 to frame 16
+        let sampler = Sampler::new(ramp(100, 1_024));
+        let samples = render_node(
+            Box::new(sampler),
+            &SamplerOutId::Out,
+            &[
+                (&SamplerInId::Start, 0.0078125),
+                (&SamplerInId::Stop, 0.015625),
+            ],
+            1_024,
+            16,
+            40,
+        )
+        .into_channels()
+        .remove(0);
+
+        assert_eq!(samples[..8], [8., 9., 10., 11., 12., 13., 14., 15.]);
+        assert!(samples[8..].iter().all(|sample| *sample == 0.0));
+    }
+
+    #[test]
+    fn loops_and_plays_backwards() {
+        let sampler = Sampler::new(ramp(100, 1_024));
+        let samples = render_node(
+            Box::new(sampler),
+            &SamplerOutId::Out,
+            &[
+                (&SamplerInId::Loop, 1.0),
+                (&SamplerInId::LoopStart, 0.00390625),
+                (&SamplerInId::LoopEnd, 0.0078125),
+            ],
+            1_024,
+            16,
+            12,
+        )
+        .into_channels()
+        .remove(0);
+        assert_eq!(samples, [0., 1., 2., 3., 4., 5., 6., 7., 4., 5., 6., 7.]);
+
+        let sampler = Sampler::new(ramp(10, 1_024));
+        let samples = render_node(
+            Box::new(sampler),
+            &SamplerOutId::Out,
+            &[(&SamplerInId::Rate, -1.0)],
+            1_024,
+            16,
+            12,
+        )
+        .into_channels()
+        .remove(0);
+        assert_eq!(samples, [9., 8., 7., 6., 5., 4., 3., 2., 1., 0., 0., 0.]);
+
+        // From frame 7 down to frame 2, the stop at frame 8 not being played
+        let sampler = Sampler::new(ramp(10, 1_024));
+        let samples = render_node(
+            Box::new(sampler),
+            &SamplerOutId::Out,
+            &[
+                (&SamplerInId::Rate, -1.0),
+                (&SamplerInId::Start, 0.001953125),
+                (&SamplerInId::Stop, 0.0078125),
+            ],
+            1_024,
+            16,
+            8,
+        )
+        .into_channels()
+        .remove(0);
+        assert_eq!(samples, [7., 6., 5., 4., 3., 2., 0., 0.]);
+    }
+
+    #[test]
+    fn resamples_to_the_session_rate() {
+        // A sample at twice the session rate plays two of its frames per frame
+        let sampler = Sampler::new(ramp(100, 2_048));
+        let samples = render_node(Box::new(sampler), &SamplerOutId::Out, &[], 1_024, 16, 4)
+            .into_channels()
+            .remove(0);
+        assert_eq!(samples, [0., 2., 4., 6.]);
+
+        // Half speed interpolates between frames, exactly on a ramp
+        let sampler = Sampler::new(ramp(100, 1_024));
+        let samples = render_node(
+            Box::new(sampler),
+            &SamplerOutId::Out,
+            &[(&SamplerInId::Start, 0.0078125), (&SamplerInId::Rate, 0.5)],
+            1_024,
+            16,
+            5,
+        )
+        .into_channels()
+        .remove(0);
+        assert_eq!(samples, [8., 8.5, 9., 9.5, 10.]);
+    }
+
+    #[test]
+    fn gate_restarts_from_the_start() {
+        let mut state = SamplerState::default();
+        let region = Region {
+            start: 2.0,
+            stop: 10.0,
+            looping: false,
+            loop_start: 2.0,
+            loop_end: 10.0,
+        };
+
+        assert_eq!(state.next(false, region, 1.0), None);
+        assert_eq!(state.next(true, region, 1.0), Some(2.0));
+        assert_eq!(state.next(true, region, 1.0), Some(3.0));
+        assert_eq!(state.next(false, region, 1.0), None);
+        assert_eq!(state.next(true, region, 1.0), Some(2.0));
+    }
+}
@@ -0,0 +1,125 @@
+use anyhow::{Context, anyhow};
+use quakk::{Data, LasyFold, Meta, id::InId};
+
+use crate::AudioBlock;
+
+/// An audio input of a node, either a number held for the whole block or an [`AudioBlock`]
+///
+/// Letting numbers in makes any numeric node usable as an audio rate parameter
+#[derive(Debug, Clone)]
+pub enum Signal {
+    Constant(f32),
+    Block(Data),
+}
+
+impl Signal {
+    /// Fold an input of a node as a signal, a block must hold [`Meta::block_size`] frames
+    pub fn get(lasy_fold: &LasyFold, in_id: &dyn InId, meta: Meta) -> anyhow::Result<Self> {
+        let data = lasy_fold.get_in(in_id, meta)?;
+
+        Self::from_data(data, meta).with_context(|| format!("invalid {}", in_id.name()))
+    }
+
+    /// Same as [`Signal::get`], with a constant `default` when the input is not patched
+    pub fn get_or(
+        lasy_fold: &LasyFold,
+        in_id: &dyn InId,
+        meta: Meta,
+        default: f32,
+    ) -> anyhow::Result<Self> {
+        if lasy_fold.is_patched(in_id) {
+            Self::get(lasy_fold, in_id, meta)
+        } else {
+            Ok(Self::Constant(default))
+        }
+    }
+
+    pub fn from_data(data: Data, meta: Meta) -> anyhow::Result<Self> {
+        if let Some(block) = data.downcast_ref::<AudioBlock>() {
+            if block.frames() != meta.block_size {
+                return Err(anyhow!(
+                    "the block holds {} frames, but the block size is {}",
+                    block.frames(),
+                    meta.block_size
+                ));
+            }
+
+            Ok(Self::Block(data))
+        } else {
+            Ok(Self::Constant(data.to_f32()?))
+        }
+    }
+
+    pub fn block(&self) -> Option<&AudioBlock> {
+        match self {
+            Self::Constant(_) => None,
+            Self::Block(data) => data.downcast_ref(),
+        }
+    }
+
+    /// The count of channels, a constant being mono
+    pub fn channel_count(&self) -> usize {
+        self.block().map(AudioBlock::channel_count).unwrap_or(1)
+    }
+
+    /// A sample of the signal, see [`AudioBlock::sample`]
+    pub fn sample(&self, channel: usize, frame: usize) -> f32 {
+        match self {
+            Self::Constant(value) => *value,
+            Self::Block(_) => self
+                .block()
+                .map(|block| block.sample(channel, frame))
+                .unwrap_or(0.0),
+        }
+    }
+
+    /// The signal as a block of [`Meta::block_size`] frames
+    pub fn into_block(self, meta: Meta) -> AudioBlock {
+        match self {
+            Self::Constant(value) => {
+                AudioBlock::from_fn(1, meta.block_size, meta.sample_rate, |_, _| value)
+            }
+            Self::Block(data) => data
+                .downcast::<AudioBlock>()
+                .expect("a signal block should hold an AudioBlock"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use quakk::Quality;
+
+    use super::*;
+
+    fn meta() -> Meta {
+        Meta {
+            tick: 0,
+            quality: Quality::Balanced,
+            tick_rate: 60.0,
+            sample_rate: 48_000,
+            block_size: 800,
+        }
+        .with_audio(48_000, 4)
+    }
+
+    #[test]
+    fn numbers_are_constant_signals() {
+        let signal = Signal::from_data(Data::new(2i64), meta()).unwrap();
+
+        assert_eq!(signal.sample(1, 3), 2.0);
+        assert_eq!(signal.into_block(meta()).channel(0).unwrap(), [2.0; 4]);
+    }
+
+    #[test]
+    fn blocks_must_match_the_block_size() {
+        let block = AudioBlock::from_fn(2, 4, 48_000, |channel, frame| (channel * frame) as f32);
+        let signal = Signal::from_data(Data::new(block), meta()).unwrap();
+        assert_eq!(signal.channel_count(), 2);
+        assert_eq!(signal.sample(1, 3), 3.0);
+
+        let block = AudioBlock::new(1, 5, 48_000);
+        assert!(Signal::from_data(Data::new(block), meta()).is_err());
+        assert!(Signal::from_data(Data::new("a".to_string()), meta()).is_err());
+    }
+}
@@ -0,0 +1,245 @@
+//! Where rendered audio goes, one block after the other
+//!
+//! A [`Transport`](crate::Transport) streams the blocks of a graph output into an [`AudioSink`].
+//! The sinks here need no audio hardware, sound devices being backends of their own.
+
+use std::{
+    fs::File,
+    io::{self, BufWriter, Seek, SeekFrom, Stdout, Write},
+    path::Path,
+};
+
+use anyhow::{Context, anyhow};
+
+use crate::{
+    AudioBlock,
+    wav::{WavFormat, data_len, write_header},
+};
+
+/// A destination for blocks of audio
+pub trait AudioSink {
+    /// Take the next block, blocks being given in order
+    fn write(&mut self, block: &AudioBlock) -> anyhow::Result<()>;
+
+    /// Complete the output once the last block is written
+    fn finish(&mut self) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+/// A sink dropping every block, to measure how fast a graph renders
+#[derive(Debug, Default)]
+pub struct NullSink {
+    frames: u64,
+}
+
+impl NullSink {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The count of frames written
+    pub fn frames(&self) -> u64 {
+        self.frames
+    }
+}
+
+impl AudioSink for NullSink {
+    fn write(&mut self, block: &AudioBlock) -> anyhow::Result<()> {
+        self.frames += block.frames() as u64;
+        Ok(())
+    }
+}
+
+/// A sink writing a WAV file as blocks come, the sizes of the header being written on
+/// [`finish`](AudioSink::finish)
+///
+/// Every block must have the channel count and sample rate of the first one.
+#[derive(Debug)]
+pub struct WavSink<W: Write + Seek> {
+    writer: BufWriter<W>,
+    format: WavFormat,
+    /// The channel count and sample rate, known from the first block
+    layout: Option<(u16, u32)>,
+    frames: u64,
+}
+
+impl<W: Write + Seek> WavSink<W> {
+    pub fn new(writer: W, format: WavFormat) -> Self {
+        Self {
+            writer: BufWriter::new(writer),
+            format,
+            layout: None,
+            frames: 0,
+        }
+    }
+
+    /// The writer, once the sink is finished
+    pub fn into_inner(self) -> anyhow::Result<W> {
+        self.writer
+            .into_inner()
+            .map_err(|error| anyhow!("could not flush the file, {}", error.error()))
+    }
+}
+
+impl WavSink<File> {
+    /// A sink writing to a new file at `path`
+    pub fn create(path: impl AsRef<Path>, format: WavFormat) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let file =
+            File::create(path).with_context(|| format!("could not create {}", path.display()))?;
+
+        Ok(Self::new(file, format))
+    }
+}
+
+impl<W: Write + Seek> AudioSink for WavSink<W> {
+    fn write(&mut self, block: &AudioBlock) -> anyhow::Result<()> {
+        let layout = (block.channel_count() as u16, block.sample_rate());
+
+        match self.layout {
+            None => {
+                // The sizes are not known yet, they are written on finish
+                write_header(&mut self.writer, layout.0, layout.1, self.format, 0)?;
+                self.layout = Some(layout);
+            }
+            Some(expected) if expected != layout => {
+                return Err(anyhow!(
+                    "the block has {} channels at {} Hz, but the file has {} channels at {} Hz",
+                    layout.0,
+                    layout.1,
+                    expected.0,
+                    expected.1
+                ));
+            }
+            Some(_) => {}
+        }
+
+        self.format.write_frames(&mut self.writer, block)?;
+        self.frames += block.frames() as u64;
+
+        Ok(())
+    }
+
+    fn finish(&mut self) -> anyhow::Result<()> {
+        let (channels, sample_rate) = self.layout.ok_or(anyhow!("no block was written"))?;
+        let data_len = data_len(channels, self.frames, self.format)?;
+
+        self.writer.seek(SeekFrom::Start(0))?;
+        write_header(
+            &mut self.writer,
+            channels,
+            sample_rate,
+            self.format,
+            data_len,
+        )?;
+        self.writer.seek(SeekFrom::End(0))?;
+        self.writer.flush()?;
+
+        Ok(())
+    }
+}
+
+/// A sink writing raw interleaved samples, without any header, to pipe audio into other programs
+#[derive(Debug)]
+pub struct PcmSink<W: Write> {
+    writer: BufWriter<W>,
+    format: WavFormat,
+}
+
+impl<W: Write> PcmSink<W> {
+    /// Samples are encoded as in a WAV file of `format`, in little endian
+    pub fn new(writer: W, format: WavFormat) -> Self {
+        Self {
+            writer: BufWriter::new(writer),
+            format,
+        }
+    }
+
+    /// The writer, once the sink is finished
+    pub fn into_inner(self) -> anyhow::Result<W> {
+        self.writer
+            .into_inner()
+            .map_err(|error| anyhow!("could not flush the output, {}", error.error()))
+    }
+}
+
+impl PcmSink<Stdout> {
+    /// A sink writing to the standard output
+    pub fn stdout(format: WavFormat) -> Self {
+        Self::new(io::stdout(), format)
+    }
+}
+
+impl<W: Write> AudioSink for PcmSink<W> {
+    fn write(&mut self, block: &AudioBlock) -> anyhow::Result<()> {
+        self.format.write_frames(&mut self.writer, block)?;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> anyhow::Result<()> {
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::wav::write_wav;
+
+    fn block(frames: usize, offset: usize) -> AudioBlock {
+        AudioBlock::from_fn(2, frames, 8_000, |channel, frame| {
+            (frame + offset) as f32 / 16.0 - channel as f32 * 0.5
+        })
+    }
+
+    #[test]
+    fn wav_sink_matches_a_whole_file() {
+        let mut sink = WavSink::new(Cursor::new(Vec::new()), WavFormat::Pcm24);
+        sink.write(&block(5, 0)).unwrap();
+        sink.write(&block(3, 5)).unwrap();
+        sink.finish().unwrap();
+        let streamed = sink.into_inner().unwrap().into_inner();
+
+        let mut whole = Vec::new();
+        write_wav(&mut whole, &block(8, 0), WavFormat::Pcm24).unwrap();
+        assert_eq!(streamed, whole);
+
+        let mut sink = WavSink::new(Cursor::new(Vec::new()), WavFormat::Pcm16);
+        sink.write(&block(4, 0)).unwrap();
+        let mono = AudioBlock::new(1, 4, 8_000);
+        assert_eq!(
+            sink.write(&mono).unwrap_err().to_string(),
+            "the block has 1 channels at 8000 Hz, but the file has 2 channels at 8000 Hz"
+        );
+
+        let mut empty = WavSink::new(Cursor::new(Vec::new()), WavFormat::Pcm16);
+        assert!(empty.finish().is_err());
+    }
+
+    #[test]
+    fn pcm_and_null_sinks() {
+        let mut sink = PcmSink::new(Vec::new(), WavFormat::Pcm16);
+        sink.write(&AudioBlock::from_fn(2, 2, 8_000, |channel, frame| {
+            [[0.0, 1.0], [-1.0, 0.5]][frame][channel]
+        }))
+        .unwrap();
+        sink.finish().unwrap();
+
+        let samples: Vec<i16> = sink
+            .into_inner()
+            .unwrap()
+            .chunks(2)
+            .map(|bytes| i16::from_le_bytes([bytes[0], bytes[1]]))
+            .collect();
+        assert_eq!(samples, [0, i16::MAX, -i16::MAX, 16_384]);
+
+        let mut sink = NullSink::new();
+        sink.write(&block(5, 0)).unwrap();
+        sink.write(&block(3, 0)).unwrap();
+        assert_eq!(sink.frames(), 8);
+    }
+}
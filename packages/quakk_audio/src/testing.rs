@@ -0,0 +1,93 @@
+//! Helpers to render a single node in isolation, feeding its inputs with constant values or
+//! formulas
+
+use quakk::{
+    GraphOutInId, GraphOutOutId, Node, Quakk,
+    expression::Expression,
+    id::{InId, OutId},
+    numeric::NumericConstant,
+};
+
+use crate::{AudioBlock, Transport};
+
+/// A graph folding `out_id` of `node` to its numeric output, each input being patched to the
+/// first output of a node
+fn patch_inputs<'a>(
+    node: Box<dyn Node>,
+    out_id: &dyn OutId,
+    inputs: impl IntoIterator<Item = (&'a dyn InId, Box<dyn Node>)>,
+) -> Quakk {
+    let quakk = Quakk::new();
+    {
+        let mut graph = quakk.graph.lock().unwrap();
+        let node = graph.insert(node);
+        for (in_id, input) in inputs {
+            let input_out_id = input.out_ids().remove(0);
+            let input = graph.insert(input);
+            graph
+                .patch(
+                    input.node_out_id(&*input_out_id).unwrap(),
+                    node.node_in_id(in_id).unwrap(),
+                )
+                .unwrap();
+        }
+        let out = graph.graph_out_in_id(&GraphOutInId::Numeric).unwrap();
+        graph.patch(node.node_out_id(out_id).unwrap(), out).unwrap();
+    }
+
+    quakk
+}
+
+/// A graph folding `out_id` of `node` to its numeric output, each input being patched to a
+/// constant
+pub(crate) fn patch_node(
+    node: Box<dyn Node>,
+    out_id: &dyn OutId,
+    inputs: &[(&dyn InId, f32)],
+) -> Quakk {
+    patch_inputs(
+        node,
+        out_id,
+        inputs.iter().map(|(in_id, value)| {
+            let constant: Box<dyn Node> = Box::new(NumericConstant::new(*value));
+            (*in_id, constant)
+        }),
+    )
+}
+
+/// Same as [`patch_node`], each input being patched to an [`Expression`], which can follow `t`
+/// or `tick`
+pub(crate) fn patch_node_to_formulas(
+    node: Box<dyn Node>,
+    out_id: &dyn OutId,
+    inputs: &[(&dyn InId, &str)],
+) -> Quakk {
+    patch_inputs(
+        node,
+        out_id,
+        inputs.iter().map(|(in_id, formula)| {
+            let expression: Box<dyn Node> = Box::new(Expression::new(formula).unwrap());
+            (*in_id, expression)
+        }),
+    )
+}
+
+/// Render `frames` frames of `out_id` of `node` in blocks of `block_size`, each input being
+/// patched to a constant
+pub(crate) fn render_node(
+    node: Box<dyn Node>,
+    out_id: &dyn OutId,
+    inputs: &[(&dyn InId, f32)],
+    sample_rate: u32,
+    block_size: usize,
+    frames: usize,
+) -> AudioBlock {
+    Transport::new(sample_rate, block_size)
+        .unwrap()
+        .render(
+            &patch_node(node, out_id, inputs),
+            GraphOutOutId::Numeric,
+            frames,
+        )
+        .unwrap()
+}
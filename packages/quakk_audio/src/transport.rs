@@ -0,0 +1,212 @@
+use anyhow::anyhow;
+use quakk::{GraphOutOutId, Meta, Quakk, Quality};
+
+use crate::{AudioBlock, Signal, sink::AudioSink};
+
+/// Drive a graph one block at a time, advancing the tick after each block
+#[derive(Debug, Clone)]
+pub struct Transport {
+    meta: Meta,
+}
+
+impl Transport {
+    /// A transport at the first tick, erroring when the sample rate or block size is `0`
+    pub fn new(sample_rate: u32, block_size: usize) -> anyhow::Result<Self> {
+        if sample_rate == 0 || block_size == 0 {
+            return Err(anyhow!(
+                "the sample rate and block size should be positive, found {sample_rate} Hz and {block_size} frames"
+            ));
+        }
+
+        let meta = Meta {
+            tick: 0,
+            quality: Quality::Balanced,
+            tick_rate: 0.0,
+            sample_rate,
+            block_size,
+        };
+
+        Ok(Self {
+            meta: meta.with_audio(sample_rate, block_size),
+        })
+    }
+
+    pub fn with_quality(mut self, quality: Quality) -> Self {
+        self.meta.quality = quality;
+        self
+    }
+
+    /// The [`Meta`] of the next block
+    pub fn meta(&self) -> Meta {
+        self.meta
+    }
+
+    /// Fold the next block of an output of the graph, a number being held for the whole block
+    pub fn next_block(
+        &mut self,
+        quakk: &Quakk,
+        graph_out_out_id: GraphOutOutId,
+    ) -> anyhow::Result<AudioBlock> {
+        let data = quakk.fold_with(graph_out_out_id, self.meta)?;
+        let block = Signal::from_data(data, self.meta)?.into_block(self.meta);
+        self.meta.tick += 1;
+
+        Ok(block)
+    }
+
+    /// Fold as many blocks as needed to render `frames` frames of an output of the graph
+    pub fn render(
+        &mut self,
+        quakk: &Quakk,
+        graph_out_out_id: GraphOutOutId,
+        frames: usize,
+    ) -> anyhow::Result<AudioBlock> {
+        let mut rendered = self.next_block(quakk, graph_out_out_id.clone())?;
+
+        while rendered.frames() < frames {
+            let block = self.next_block(quakk, graph_out_out_id.clone())?;
+            rendered.append(&block)?;
+        }
+        rendered.truncate(frames);
+
+        Ok(rendered)
+    }
+
+    /// Stream `frames` frames of an output of the graph into `sink` one block at a time, then
+    /// finish the sink
+    ///
+    /// At least one block is folded, even for `0` frames, as for [`Transport::render`].
+    pub fn stream(
+        &mut self,
+        quakk: &Quakk,
+        graph_out_out_id: GraphOutOutId,
+        frames: usize,
+        sink: &mut dyn AudioSink,
+    ) -> anyhow::Result<()> {
+        let mut written = 0;
+
+        loop {
+            let mut block = self.next_block(quakk, graph_out_out_id.clone())?;
+            block.truncate(frames - written);
+            written += block.frames();
+            sink.write(&block)?;
+
+            if written >= frames {
+                break;
+            }
+        }
+
+        sink.finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use quakk::{
+        Data, GraphOutInId, LasyFold, Node,
+        id::{InId, NodeId, NodeInId, NodeOutId, OutId},
+    };
+
+    use super::*;
+
+    /// A node folding to the index of each sample
+    #[derive(Debug, Default)]
+    struct Ramp;
+
+    #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+    enum RampOutId {
+        Out,
+    }
+
+    impl OutId for RampOutId {}
+
+    impl Node for Ramp {
+        fn initialize() -> Self {
+            Self
+        }
+
+        fn title(&self) -> &str {
+            "Ramp"
+        }
+
+        fn fold(
+            &self,
+            _out_id: &dyn OutId,
+            _lasy_fold: LasyFold,
+            meta: Meta,
+        ) -> anyhow::Result<Data> {
+            Ok(Data::new(AudioBlock::from_fn(
+                1,
+                meta.block_size,
+                meta.sample_rate,
+                |_, frame| (meta.first_sample() + frame as u64) as f32,
+            )))
+        }
+
+        fn node_in_id(&self, _in_id: &dyn InId, _node_id: NodeId) -> Option<NodeInId> {
+            None
+        }
+
+        fn node_out_id(&self, out_id: &dyn OutId, node_id: NodeId) -> Option<NodeOutId> {
+            out_id
+                .as_any()
+                .downcast_ref::<RampOutId>()
+                .map(|out_id| NodeOutId::new(node_id, out_id))
+        }
+
+        fn in_ids(&self) -> Vec<Box<dyn InId>> {
+            Vec::new()
+        }
+
+        fn out_ids(&self) -> Vec<Box<dyn OutId>> {
+            vec![Box::new(RampOutId::Out)]
+        }
+    }
+
+    #[test]
+    fn renders_consecutive_blocks() {
+        let quakk = Quakk::new();
+        {
+            let mut graph = quakk.graph.lock().unwrap();
+            let ramp = graph.insert(Box::new(Ramp));
+            let out = graph.graph_out_in_id(&GraphOutInId::Numeric).unwrap();
+            graph
+                .patch(ramp.node_out_id(&RampOutId::Out).unwrap(), out)
+                .unwrap();
+        }
+
+        let mut transport = Transport::new(48_000, 4).unwrap();
+        assert_eq!(transport.meta().tick_rate, 12_000.0);
+        assert!(Transport::new(48_000, 0).is_err());
+        assert!(Transport::new(0, 4).is_err());
+
+        let block = transport
+            .render(&quakk, GraphOutOutId::Numeric, 10)
+            .unwrap();
+        let expected: Vec<f32> = (0..10).map(|sample| sample as f32).collect();
+        assert_eq!(block.channel(0).unwrap(), expected);
+        assert_eq!(transport.meta().tick, 3);
+    }
+
+    #[test]
+    fn streams_blocks_into_a_sink() {
+        let quakk = Quakk::new();
+        {
+            let mut graph = quakk.graph.lock().unwrap();
+            let ramp = graph.insert(Box::new(Ramp));
+            let out = graph.graph_out_in_id(&GraphOutInId::Numeric).unwrap();
+            graph
+                .patch(ramp.node_out_id(&RampOutId::Out).unwrap(), out)
+                .unwrap();
+        }
+
+        let mut sink = crate::sink::NullSink::new();
+        let mut transport = Transport::new(48_000, 4).unwrap();
+        transport
+            .stream(&quakk, GraphOutOutId::Numeric, 10, &mut sink)
+            .unwrap();
+
+        assert_eq!(sink.frames(), 10);
+        assert_eq!(transport.meta().tick, 3);
+    }
+}
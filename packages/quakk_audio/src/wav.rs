@@ -0,0 +1,318 @@
+//! Reading and writing [`AudioBlock`]s as WAV files
+
+use std::io::{self, Read, Write};
+
+use anyhow::{Context, anyhow};
+
+use crate::AudioBlock;
+
+/// The sample format of a WAV file
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum WavFormat {
+    /// 16 bit integers
+    #[default]
+    Pcm16,
+    /// 24 bit integers
+    Pcm24,
+    /// 32 bit floats, samples are written as is, without clipping
+    Float32,
+}
+
+impl WavFormat {
+    fn bytes_per_sample(&self) -> u16 {
+        match self {
+            WavFormat::Pcm16 => 2,
+            WavFormat::Pcm24 => 3,
+            WavFormat::Float32 => 4,
+        }
+    }
+
+    /// The `wFormatTag` of the `fmt ` chunk
+    fn format_tag(&self) -> u16 {
+        match self {
+            WavFormat::Pcm16 | WavFormat::Pcm24 => 1,
+            WavFormat::Float32 => 3,
+        }
+    }
+
+    fn write_sample(&self, writer: &mut impl Write, sample: f32) -> io::Result<()> {
+        // Integer formats clip, and round to the nearest step so the output is deterministic
+        let integer = |max: f32| (sample.clamp(-1.0, 1.0) * max).round() as i32;
+
+        match self {
+            WavFormat::Pcm16 => writer.write_all(&(integer(i16::MAX as f32) as i16).to_le_bytes()),
+            WavFormat::Pcm24 => writer.write_all(&integer(8_388_607.0).to_le_bytes()[..3]),
+            WavFormat::Float32 => writer.write_all(&sample.to_le_bytes()),
+        }
+    }
+
+    /// Write the samples of a block, its channels being interleaved
+    pub(crate) fn write_frames(
+        &self,
+        writer: &mut impl Write,
+        block: &AudioBlock,
+    ) -> io::Result<()> {
+        for frame in 0..block.frames() {
+            for channel in block.channels() {
+                self.write_sample(writer, channel[frame])?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Write a block as a complete WAV file, its channels being interleaved
+pub fn write_wav(mut writer: impl Write, block: &AudioBlock, format: WavFormat) -> io::Result<()> {
+    let channels = block.channel_count() as u16;
+    let data_len = data_len(channels, block.frames() as u64, format)?;
+
+    write_header(&mut writer, channels, block.sample_rate(), format, data_len)?;
+
+    let mut writer = io::BufWriter::new(writer);
+    format.write_frames(&mut writer, block)?;
+
+    writer.flush()
+}
+
+/// The length in bytes of `frames` frames, if it fits in a WAV file
+pub(crate) fn data_len(channels: u16, frames: u64, format: WavFormat) -> io::Result<u32> {
+    let block_align = channels as u64 * format.bytes_per_sample() as u64;
+
+    u32::try_from(frames * block_align)
+        .ok()
+        .filter(|len| *len <= u32::MAX - 36)
+        .ok_or(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "the audio is too long for a WAV file",
+        ))
+}
+
+/// Write the header of a WAV file, followed by `data_len` bytes of samples
+///
+/// Nothing is written when the sizes of a frame or of a second of audio do not fit in the header.
+pub(crate) fn write_header(
+    writer: &mut impl Write,
+    channels: u16,
+    sample_rate: u32,
+    format: WavFormat,
+    data_len: u32,
+) -> io::Result<()> {
+    let bytes_per_sample = format.bytes_per_sample();
+    let block_align = channels
+        .checked_mul(bytes_per_sample)
+        .ok_or(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "the audio has too many channels for a WAV file",
+        ))?;
+    let byte_rate = sample_rate
+        .checked_mul(block_align as u32)
+        .ok_or(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "the sample rate is too high for a WAV file",
+        ))?;
+
+    writer.write_all(b"RIFF")?;
+    writer.write_all(&(36 + data_len).to_le_bytes())?;
+    writer.write_all(b"WAVE")?;
+
+    writer.write_all(b"fmt ")?;
+    writer.write_all(&16u32.to_le_bytes())?;
+    writer.write_all(&format.format_tag().to_le_bytes())?;
+    writer.write_all(&channels.to_le_bytes())?;
+    writer.write_all(&sample_rate.to_le_bytes())?;
+    writer.write_all(&byte_rate.to_le_bytes())?;
+    writer.write_all(&block_align.to_le_bytes())?;
+    writer.write_all(&(bytes_per_sample * 8).to_le_bytes())?;
+
+    writer.write_all(b"data")?;
+    writer.write_all(&data_len.to_le_bytes())
+}
+
+/// The `wFormatTag` of `WAVE_FORMAT_EXTENSIBLE` files, the actual format being in the extension
+const FORMAT_EXTENSIBLE: u16 = 0xfffe;
+
+/// Read a whole WAV file, in 8, 16, 24 or 32 bit integers or in 32 or 64 bit floats
+pub fn read_wav(mut reader: impl Read) -> anyhow::Result<AudioBlock> {
+    let mut bytes = Vec::new();
+    reader
+        .read_to_end(&mut bytes)
+        .context("could not read the WAV file")?;
+
+    if bytes.len() < 12 || &bytes[..4] != b"RIFF" || &bytes[8..12] != b"WAVE" {
+        return Err(anyhow!("not a WAV file"));
+    }
+
+    let mut format = None;
+    let mut data = None;
+    let mut chunks = &bytes[12..];
+
+    while chunks.len() >= 8 {
+        let id = &chunks[..4];
+        let len = u32::from_le_bytes(chunks[4..8].try_into().unwrap()) as usize;
+        // A truncated last chunk is read as far as it goes
+        let chunk = &chunks[8..chunks.len().min(8 + len)];
+
+        match id {
+            b"fmt " => format = Some(chunk),
+            b"data" => data = Some(chunk),
+            _ => {}
+        }
+
+        // Chunks are aligned on two bytes
+        let next = (8 + len + len % 2).min(chunks.len());
+        chunks = &chunks[next..];
+    }
+
+    let format = format
+        .filter(|format| format.len() >= 16)
+        .ok_or(anyhow!("the WAV file has no valid `fmt ` chunk"))?;
+    let data = data.ok_or(anyhow!("the WAV file has no `data` chunk"))?;
+
+    let u16_at = |at: usize| u16::from_le_bytes([format[at], format[at + 1]]);
+    let mut tag = u16_at(0);
+    let channel_count = u16_at(2) as usize;
+    let sample_rate = u32::from_le_bytes(format[4..8].try_into().unwrap());
+    let bits = u16_at(14);
+
+    if tag == FORMAT_EXTENSIBLE {
+        // The sub format GUID starts with the actual format tag
+        tag = format
+            .get(24..26)
+            .map(|tag| u16::from_le_bytes([tag[0], tag[1]]))
+            .ok_or(anyhow!("the extensible WAV format is truncated"))?;
+    }
+
+    let decode: fn(&[u8]) -> f32 = match (tag, bits) {
+        (1, 8) => |bytes| (bytes[0] as f32 - 128.0) / 128.0,
+        (1, 16) => |bytes| i16::from_le_bytes([bytes[0], bytes[1]]) as f32 / 32_768.0,
+        (1, 24) => {
+            |bytes| i32::from_le_bytes([0, bytes[0], bytes[1], bytes[2]]) as f32 / 2_147_483_648.0
+        }
+        (1, 32) => |bytes| i32::from_le_bytes(bytes.try_into().unwrap()) as f32 / 2_147_483_648.0,
+        (3, 32) => |bytes| f32::from_le_bytes(bytes.try_into().unwrap()),
+        (3, 64) => |bytes| f64::from_le_bytes(bytes.try_into().unwrap()) as f32,
+        (tag, bits) => {
+            return Err(anyhow!(
+                "unsupported WAV format {tag} with {bits} bits per sample"
+            ));
+        }
+    };
+
+    if channel_count == 0 {
+        return Err(anyhow!("the WAV file has no channel"));
+    }
+
+    let bytes_per_sample = bits as usize / 8;
+    let mut channels = vec![Vec::new(); channel_count];
+    for frame in data.chunks_exact(bytes_per_sample * channel_count) {
+        for (channel, sample) in channels
+            .iter_mut()
+            .zip(frame.chunks_exact(bytes_per_sample))
+        {
+            channel.push(decode(sample));
+        }
+    }
+
+    AudioBlock::from_channels(channels, sample_rate)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn writes_a_pcm16_file() {
+        let block = AudioBlock::from_channels(vec![vec![0.0, 1.0], vec![-1.0, 2.0]], 8000).unwrap();
+        let mut bytes = Vec::new();
+        write_wav(&mut bytes, &block, WavFormat::Pcm16).unwrap();
+
+        assert_eq!(bytes.len(), 44 + 8);
+        assert_eq!(&bytes[..4], b"RIFF");
+        assert_eq!(&bytes[4..8], &44u32.to_le_bytes());
+        // Two channels at 8 kHz, 16 bit
+        assert_eq!(&bytes[22..24], &2u16.to_le_bytes());
+        assert_eq!(&bytes[24..28], &8000u32.to_le_bytes());
+        assert_eq!(&bytes[28..32], &32_000u32.to_le_bytes());
+        assert_eq!(&bytes[34..36], &16u16.to_le_bytes());
+        // Interleaved and clipped samples
+        assert_eq!(
+            &bytes[44..],
+            [0, 0, 0x01, 0x80, 0xff, 0x7f, 0xff, 0x7f].as_slice()
+        );
+    }
+
+    #[test]
+    fn header_sizes_must_fit() {
+        let mut bytes = Vec::new();
+        let error = write_header(&mut bytes, 2, 3_000_000_000, WavFormat::Pcm16, 0).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+
+        let error = write_header(&mut bytes, 20_000, 48_000, WavFormat::Float32, 0).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+        assert!(bytes.is_empty());
+    }
+
+    #[test]
+    fn writes_pcm24_and_float() {
+        let block = AudioBlock::from_channels(vec![vec![-1.0, 0.5]], 48_000).unwrap();
+
+        let mut bytes = Vec::new();
+        write_wav(&mut bytes, &block, WavFormat::Pcm24).unwrap();
+        assert_eq!(
+            &bytes[44..],
+            [0x01, 0x00, 0x80, 0x00, 0x00, 0x40].as_slice()
+        );
+
+        let mut bytes = Vec::new();
+        write_wav(&mut bytes, &block, WavFormat::Float32).unwrap();
+        assert_eq!(&bytes[20..22], &3u16.to_le_bytes());
+        assert_eq!(&bytes[48..], &0.5f32.to_le_bytes());
+    }
+
+    #[test]
+    fn reads_back_what_it_writes() {
+        let block = AudioBlock::from_fn(2, 100, 44_100, |channel, frame| {
+            ((frame as f32 * 0.1).sin() * 0.9) * if channel == 0 { 1.0 } else { -0.5 }
+        });
+
+        for (format, precision) in [
+            (WavFormat::Pcm16, 1e-4),
+            (WavFormat::Pcm24, 1e-6),
+            (WavFormat::Float32, 0.0),
+        ] {
+            let mut bytes = Vec::new();
+            write_wav(&mut bytes, &block, format).unwrap();
+            let read = read_wav(bytes.as_slice()).unwrap();
+
+            assert_eq!(read.channel_count(), 2);
+            assert_eq!(read.frames(), 100);
+            assert_eq!(read.sample_rate(), 44_100);
+            for (read, written) in read
+                .channels()
+                .iter()
+                .flatten()
+                .zip(block.channels().iter().flatten())
+            {
+                assert!((read - written).abs() <= precision, "{format:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn skips_unknown_chunks() {
+        let block = AudioBlock::from_channels(vec![vec![0.5, -0.5]], 8000).unwrap();
+        let mut bytes = Vec::new();
+        write_wav(&mut bytes, &block, WavFormat::Pcm16).unwrap();
+
+        // Insert an odd sized `LIST` chunk, padded to two bytes, before the `data` chunk
+        let list = [b"LIST".as_slice(), &3u32.to_le_bytes(), b"abc\0"].concat();
+        bytes.splice(36..36, list);
+
+        let read = read_wav(bytes.as_slice()).unwrap();
+        assert_eq!(read.channel(0).unwrap(), [0.5, -0.5]);
+
+        assert!(read_wav(b"RIFF\0\0\0\0WAVE".as_slice()).is_err());
+        assert!(read_wav(b"not a wav file".as_slice()).is_err());
+    }
+}
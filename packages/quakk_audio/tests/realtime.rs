@@ -0,0 +1,99 @@
+//! Folding a prepared audio output must not allocate once its first blocks are folded, checked
+//! with an instrumented global allocator
+//!
+//! The allocator counts allocations of every thread, so this file holds a single test.
+
+use std::alloc::System;
+
+use quakk::{
+    GraphOutInId, GraphOutOutId, Meta, Quakk,
+    numeric::{NumericConstant, NumericConstantOutId},
+};
+use quakk_audio::{
+    AudioBlock,
+    filter::{FilterInId, FilterKind, FilterOutId, Svf},
+    mix::{Gain, GainInId, GainOutId, GainUnit},
+    oscillator::{Oscillator, OscillatorOutId, Waveform},
+};
+use stats_alloc::{INSTRUMENTED_SYSTEM, Region, StatsAlloc};
+
+#[global_allocator]
+static GLOBAL: &StatsAlloc<System> = &INSTRUMENTED_SYSTEM;
+
+/// Run `f`, panicking if it allocated
+fn assert_no_allocation<T>(f: impl FnOnce() -> T) -> T {
+    let region = Region::new(GLOBAL);
+    let value = f();
+    let stats = region.change();
+
+    assert_eq!(
+        (stats.allocations, stats.reallocations),
+        (0, 0),
+        "the fold allocated"
+    );
+
+    value
+}
+
+/// An oscillator filtered by a low-pass, its level being halved
+fn quakk() -> Quakk {
+    let quakk = Quakk::new();
+    {
+        let mut graph = quakk.graph.lock().unwrap();
+        let oscillator = graph.insert(Box::new(Oscillator::new(Waveform::Saw)));
+        let filter = graph.insert(Box::new(Svf::new(FilterKind::LowPass)));
+        let level = graph.insert(Box::new(NumericConstant::new(0.5)));
+        let gain = graph.insert(Box::new(Gain::new(GainUnit::Linear)));
+        let out = graph.graph_out_in_id(&GraphOutInId::Numeric).unwrap();
+
+        let edges = [
+            (
+                oscillator.node_out_id(&OscillatorOutId::Out).unwrap(),
+                filter.node_in_id(&FilterInId::In).unwrap(),
+            ),
+            (
+                filter.node_out_id(&FilterOutId::Out).unwrap(),
+                gain.node_in_id(&GainInId::In).unwrap(),
+            ),
+            (
+                level.node_out_id(&NumericConstantOutId::Out).unwrap(),
+                gain.node_in_id(&GainInId::Gain).unwrap(),
+            ),
+            (gain.node_out_id(&GainOutId::Out).unwrap(), out),
+        ];
+        for (out, node_in_id) in edges {
+            graph.patch(out, node_in_id).unwrap();
+        }
+    }
+
+    quakk
+}
+
+#[test]
+fn prepared_fold_does_not_allocate() {
+    let quakk = quakk();
+    let prepared = quakk.prepare(GraphOutOutId::Numeric).unwrap();
+    let meta = |tick| Meta {
+        tick,
+        ..quakk.base_meta.with_audio(48_000, 64)
+    };
+    let peak = |tick| {
+        let data = prepared.fold(meta(tick)).unwrap();
+        let block = data.downcast_ref::<AudioBlock>().unwrap();
+        block.channels()[0]
+            .iter()
+            .fold(0.0f32, |peak, sample| peak.max(sample.abs()))
+    };
+
+    // The first blocks allocate the buffers the next ones are written in
+    peak(0);
+    let peaks = assert_no_allocation(|| {
+        let mut peaks = [0.0; 120];
+        for (tick, peak_of) in peaks.iter_mut().enumerate() {
+            *peak_of = peak(tick as u64 + 1);
+        }
+        peaks
+    });
+
+    assert!(peaks.iter().all(|peak| *peak > 0.0 && *peak <= 0.5));
+}
@@ -1,11 +1,33 @@
+use anyhow::{Context, anyhow};
 use quakk::{
     GraphOut, GraphOutInId, GraphOutOutId, LasyFold, Node, Quakk,
+    expression::{Expression, ExpressionInId, ExpressionOutId},
     id::InId,
     numeric::*,
     textual::{TextConstant, TextConstantOutId, TextSplit, TextSplitInId, TextSplitOutId},
 };
 
+mod patch;
+mod render;
+
 fn main() {
+    let mut args = std::env::args().skip(1);
+
+    let result = match args.next().as_deref() {
+        Some("render") => {
+            Some(render::RenderOptions::parse(args).and_then(|options| render::run(&options)))
+        }
+        Some("dot") => Some(dot(args)),
+        _ => None,
+    };
+    if let Some(result) = result {
+        if let Err(error) = result {
+            eprintln!("error: {error:#}");
+            std::process::exit(1);
+        }
+        return;
+    }
+
     let qk = Quakk::new();
 
     {
@@ -15,35 +37,20 @@ fn main() {
         let number_b = graph.insert(Box::new(NumericConstant::new(3.0)));
         let number_c = graph.insert(Box::new(NumericConstant::new(2.0)));
 
-        let mult = graph.insert(Box::new(Arithmetics::new(
-            ArithmeticOperation::Multiplication,
-        )));
-        let add = graph.insert(Box::new(Arithmetics::new(
-            ArithmeticOperation::Substraction,
-        )));
+        let formula = graph.insert(Box::new(Expression::new("a * b - c").unwrap()));
 
-        let _ = graph.patch(
-            number_a.node_out_id(&NumericConstantOutId::Out).unwrap(),
-            mult.node_in_id(&ArithmeticsInId::Term1).unwrap(),
-        );
-        let _ = graph.patch(
-            number_b.node_out_id(&NumericConstantOutId::Out).unwrap(),
-            mult.node_in_id(&ArithmeticsInId::Term2).unwrap(),
-        );
-        let _ = graph.patch(
-            mult.node_out_id(&ArithmeticsOutId::Out).unwrap(),
-            add.node_in_id(&ArithmeticsInId::Term1).unwrap(),
-        );
-        let _ = graph.patch(
-            number_c.node_out_id(&NumericConstantOutId::Out).unwrap(),
-            add.node_in_id(&ArithmeticsInId::Term2).unwrap(),
-        );
+        for (number, name) in [(number_a, "a"), (number_b, "b"), (number_c, "c")] {
+            let _ = graph.patch(
+                number.node_out_id(&NumericConstantOutId::Out).unwrap(),
+                formula.node_in_id(&ExpressionInId::new(name)).unwrap(),
+            );
+        }
 
         let textconst = graph.insert(Box::new(TextConstant::new("Hello World!".to_string())));
         let textsplit = graph.insert(Box::new(TextSplit::default()));
 
         let _ = graph.patch(
-            add.node_out_id(&ArithmeticsOutId::Out).unwrap(),
+            formula.node_out_id(&ExpressionOutId::Out).unwrap(),
             textsplit.node_in_id(&TextSplitInId::At).unwrap(),
         );
 
@@ -63,3 +70,27 @@ fn main() {
 
     dbg!(qk.fold_for(GraphOutOutId::Numeric).unwrap());
 }
+
+/// The `dot` subcommand, printing the graph of a patch in the Graphviz DOT format
+///
+/// ```text
+/// quakk_cli dot <patch.json>
+/// ```
+fn dot(mut args: impl Iterator<Item = String>) -> anyhow::Result<()> {
+    let path = args
+        .next()
+        .ok_or(anyhow!("missing the patch file to print"))?;
+    if let Some(arg) = args.next() {
+        return Err(anyhow!("unexpected argument `{arg}`"));
+    }
+
+    let source =
+        std::fs::read_to_string(&path).with_context(|| format!("could not read {path}"))?;
+
+    let quakk = Quakk::new();
+    let mut graph = quakk.graph.lock().unwrap();
+    patch::load(&mut graph, &source)?;
+    print!("{}", graph.to_dot());
+
+    Ok(())
+}
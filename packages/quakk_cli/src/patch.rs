@@ -0,0 +1,501 @@
+//! Loading a graph from a JSON patch file
+//!
+//! A patch names its nodes and lists its edges, each end of an edge being written as
+//! `node.Port`, ports being found by their [`name`](quakk::id::InId::name). The inputs and
+//! outputs of the graph are the ports of the reserved `in` and `out` nodes :
+//! ```json
+//! {
+//!     "nodes": {
+//!         "pitch": { "type": "NumericConstant", "value": 110 },
+//!         "osc": { "type": "Oscillator", "waveform": "Saw" },
+//!         "filter": { "type": "Svf", "kind": "LowPass" }
+//!     },
+//!     "edges": [
+//!         ["pitch.Out", "osc.Frequency"],
+//!         ["osc.Out", "filter.In"],
+//!         ["filter.Out", "out.Audio"]
+//!     ]
+//! }
+//! ```
+
+use std::collections::HashMap;
+
+use anyhow::{Context, anyhow};
+use quakk::{
+    Graph, GraphInOutId, GraphOutInId, Node, NodeHandle,
+    clock::{self, BeatDivider, Clock},
+    expression::Expression,
+    id::{NodeInId, NodeOutId},
+    numeric::*,
+    pattern::Pattern,
+    sequence::{Step, StepSequencer},
+    template::TextTemplate,
+    textual::TextConstant,
+};
+use quakk_audio::{
+    effect::{Chorus, Delay, ModulationKind, Reverb},
+    envelope::{Adsr, EnvelopeCurve},
+    filter::{Biquad, FilterKind, Svf},
+    mix::{ChannelMerge, ChannelSplit, Gain, GainUnit, Mixer, Pan},
+    oscillator::{LFO, Oscillator, Waveform},
+    sampler::Sampler,
+};
+use quakk_midi::player::NotePlayer;
+use serde_json::{Map, Value};
+
+type Parameters = Map<String, Value>;
+
+/// Insert the nodes and edges of a patch in `graph`
+pub fn load(graph: &mut Graph, source: &str) -> anyhow::Result<()> {
+    let patch: Value = serde_json::from_str(source).context("the patch is not valid JSON")?;
+
+    let mut handles = HashMap::new();
+    let nodes = patch
+        .get("nodes")
+        .and_then(Value::as_object)
+        .ok_or(anyhow!("the patch should have a `nodes` object"))?;
+
+    for (name, node) in nodes {
+        if name == "in" || name == "out" {
+            return Err(anyhow!("the node name `{name}` is reserved"));
+        }
+
+        let parameters = node
+            .as_object()
+            .ok_or(anyhow!("the node `{name}` should be an object"))?;
+        let node = build_node(parameters).with_context(|| format!("invalid node `{name}`"))?;
+        handles.insert(name.as_str(), graph.insert(node));
+    }
+
+    let edges = match patch.get("edges") {
+        Some(edges) => edges
+            .as_array()
+            .ok_or(anyhow!("the `edges` of the patch should be an array"))?
+            .as_slice(),
+        None => &[],
+    };
+
+    for edge in edges {
+        let (from, to) = edge
+            .as_array()
+            .and_then(|edge| match edge.as_slice() {
+                [from, to] => Some((from.as_str()?, to.as_str()?)),
+                _ => None,
+            })
+            .ok_or(anyhow!(
+                "the edge {edge} should be a pair of ports, like [\"node.Out\", \"node.In\"]"
+            ))?;
+
+        let out_id = resolve_out(graph, &handles, from)?;
+        let in_id = resolve_in(graph, &handles, to)?;
+        graph
+            .patch(out_id, in_id)
+            .with_context(|| format!("could not patch {from} to {to}"))?;
+    }
+
+    Ok(())
+}
+
+fn split_port(port: &str) -> anyhow::Result<(&str, &str)> {
+    port.split_once('.')
+        .ok_or(anyhow!("the port `{port}` should be written `node.Port`"))
+}
+
+fn handle<'a>(
+    handles: &'a HashMap<&str, NodeHandle>,
+    node: &str,
+) -> anyhow::Result<&'a NodeHandle> {
+    handles
+        .get(node)
+        .ok_or(anyhow!("there is no node named `{node}`"))
+}
+
+fn resolve_out(
+    graph: &Graph,
+    handles: &HashMap<&str, NodeHandle>,
+    port: &str,
+) -> anyhow::Result<NodeOutId> {
+    let (node, name) = split_port(port)?;
+
+    let out_id = if node == "in" {
+        let out_id = match name {
+            "Numeric" => GraphInOutId::Numeric,
+            name => GraphInOutId::named(name),
+        };
+        graph.graph_in_out_id(&out_id)
+    } else {
+        let handle = handle(handles, node)?;
+        let out_ids = handle.node().out_ids();
+        out_ids
+            .iter()
+            .find(|out_id| out_id.name() == name)
+            .and_then(|out_id| handle.node_out_id(&**out_id))
+    };
+
+    out_id.ok_or(anyhow!("the node `{node}` has no output `{name}`"))
+}
+
+fn resolve_in(
+    graph: &Graph,
+    handles: &HashMap<&str, NodeHandle>,
+    port: &str,
+) -> anyhow::Result<NodeInId> {
+    let (node, name) = split_port(port)?;
+
+    let in_id = if node == "out" {
+        let in_id = match name {
+            "Numeric" => GraphOutInId::Numeric,
+            name => GraphOutInId::named(name),
+        };
+        graph.graph_out_in_id(&in_id)
+    } else {
+        let handle = handle(handles, node)?;
+        let in_ids = handle.node().in_ids();
+        in_ids
+            .iter()
+            .find(|in_id| in_id.name() == name)
+            .and_then(|in_id| handle.node_in_id(&**in_id))
+    };
+
+    in_id.ok_or(anyhow!("the node `{node}` has no input `{name}`"))
+}
+
+/// Pick one of `options` by the name given in the parameter `name`, `None` when it is missing
+fn choose<T>(
+    parameters: &Parameters,
+    name: &str,
+    options: Vec<(&str, T)>,
+) -> anyhow::Result<Option<T>> {
+    let Some(value) = parameters.get(name) else {
+        return Ok(None);
+    };
+
+    let names: Vec<&str> = options.iter().map(|(name, _)| *name).collect();
+    value
+        .as_str()
+        .and_then(|value| options.into_iter().find(|(option, _)| *option == value))
+        .map(|(_, option)| Some(option))
+        .ok_or(anyhow!(
+            "the `{name}` should be one of {}, found {value}",
+            names.join(", ")
+        ))
+}
+
+fn number(parameters: &Parameters, name: &str) -> anyhow::Result<Option<f64>> {
+    parameters
+        .get(name)
+        .map(|value| {
+            value
+                .as_f64()
+                .ok_or(anyhow!("the `{name}` should be a number, found {value}"))
+        })
+        .transpose()
+}
+
+/// A count of ports, `default` when it is missing
+fn count(parameters: &Parameters, name: &str, default: usize) -> anyhow::Result<usize> {
+    match parameters.get(name) {
+        None => Ok(default),
+        Some(value) => value
+            .as_u64()
+            .filter(|count| (1..=64).contains(count))
+            .map(|count| count as usize)
+            .ok_or(anyhow!(
+                "the `{name}` should be a whole number from 1 to 64, found {value}"
+            )),
+    }
+}
+
+/// The steps of a sequencer, each being a number or an object like
+/// `{ "value": 60, "gate": true, "probability": 0.5, "ratchet": 2 }`
+fn steps(parameters: &Parameters) -> anyhow::Result<Vec<Step>> {
+    let steps = parameters
+        .get("steps")
+        .and_then(Value::as_array)
+        .ok_or(anyhow!("the `steps` should be an array"))?;
+
+    steps
+        .iter()
+        .enumerate()
+        .map(|(index, step)| {
+            let invalid = || format!("invalid step {index}");
+            if let Some(value) = step.as_f64() {
+                return Ok(Step::new(value));
+            }
+
+            let step = step
+                .as_object()
+                .ok_or(anyhow!(
+                    "a step should be a number or an object, found {step}"
+                ))
+                .with_context(invalid)?;
+            let gate = match step.get("gate") {
+                None => true,
+                Some(gate) => gate
+                    .as_bool()
+                    .ok_or(anyhow!("the `gate` should be a boolean, found {gate}"))
+                    .with_context(invalid)?,
+            };
+
+            Ok(Step {
+                value: number(step, "value").with_context(invalid)?.unwrap_or(0.0),
+                gate,
+                probability: number(step, "probability")
+                    .with_context(invalid)?
+                    .unwrap_or(1.0),
+                ratchet: count(step, "ratchet", 1).with_context(invalid)? as u32,
+            })
+        })
+        .collect()
+}
+
+fn text<'a>(parameters: &'a Parameters, name: &str) -> anyhow::Result<&'a str> {
+    parameters
+        .get(name)
+        .and_then(Value::as_str)
+        .ok_or(anyhow!("the `{name}` should be a text"))
+}
+
+/// The optional `division` of a clock, like `"1/8T"`, a beat when not given
+fn division(parameters: &Parameters) -> anyhow::Result<clock::Division> {
+    match parameters.get("division") {
+        None => Ok(clock::Division::default()),
+        Some(_) => text(parameters, "division")?
+            .parse()
+            .map_err(|error| anyhow!("invalid division, {error}")),
+    }
+}
+
+fn waveform(parameters: &Parameters) -> anyhow::Result<Waveform> {
+    use Waveform::*;
+    let waveform = choose(
+        parameters,
+        "waveform",
+        vec![
+            ("Sine", Sine),
+            ("Saw", Saw),
+            ("Pulse", Pulse),
+            ("Triangle", Triangle),
+        ],
+    )?;
+
+    Ok(waveform.unwrap_or_default())
+}
+
+fn build_node(parameters: &Parameters) -> anyhow::Result<Box<dyn Node>> {
+    let kind = text(parameters, "type")?;
+
+    let node: Box<dyn Node> = match kind {
+        "NumericConstant" => Box::new(NumericConstant::new(
+            number(parameters, "value")?.unwrap_or(0.0) as f32,
+        )),
+        "TextConstant" => Box::new(TextConstant::new(text(parameters, "value")?.to_string())),
+        "Arithmetics" => {
+            use ArithmeticOperation::*;
+            let operation = choose(
+                parameters,
+                "operation",
+                vec![
+                    ("Addition", Addition),
+                    ("Substraction", Substraction),
+                    ("Multiplication", Multiplication),
+                    ("Division", Division),
+                    ("Modulo", Modulo),
+                    ("Power", Power),
+                    ("Minimum", Minimum),
+                    ("Maximum", Maximum),
+                ],
+            )?;
+            Box::new(Arithmetics::new(operation.unwrap_or_default()))
+        }
+        "Math" => {
+            use MathFunction::*;
+            let function = choose(
+                parameters,
+                "function",
+                vec![
+                    ("Absolute", Absolute),
+                    ("Sign", Sign),
+                    ("SquareRoot", SquareRoot),
+                    ("Floor", Floor),
+                    ("Ceil", Ceil),
+                    ("Round", Round),
+                    ("Fract", Fract),
+                    ("Negate", Negate),
+                    ("Sine", Sine),
+                    ("Cosine", Cosine),
+                    ("Tangent", Tangent),
+                    ("ArcSine", ArcSine),
+                    ("ArcCosine", ArcCosine),
+                    ("ArcTangent", ArcTangent),
+                ],
+            )?;
+            Box::new(Math::new(function.unwrap_or_default()))
+        }
+        "Clamp" => Box::new(Clamp),
+        "Lerp" => Box::new(Lerp),
+        "Expression" => Box::new(
+            Expression::new(text(parameters, "formula")?)
+                .map_err(|error| anyhow!("invalid formula, {error}"))?,
+        ),
+        "TextTemplate" => Box::new(
+            TextTemplate::new(text(parameters, "template")?)
+                .map_err(|error| anyhow!("invalid template, {error}"))?,
+        ),
+        "StepSequencer" => Box::new(StepSequencer::new(
+            steps(parameters)?,
+            number(parameters, "seed")?.unwrap_or(0.0) as u64,
+        )),
+        "Pattern" => Box::new(
+            Pattern::new(
+                text(parameters, "pattern")?,
+                number(parameters, "seed")?.unwrap_or(0.0) as u64,
+            )
+            .map_err(|error| anyhow!("invalid pattern, {error}"))?,
+        ),
+        "Clock" => Box::new(Clock::new(division(parameters)?)),
+        "BeatDivider" => Box::new(BeatDivider::new(division(parameters)?)),
+        "Oscillator" => Box::new(Oscillator::new(waveform(parameters)?)),
+        "LFO" => Box::new(LFO::new(waveform(parameters)?)),
+        "Adsr" => {
+            let curve = choose(
+                parameters,
+                "curve",
+                vec![
+                    ("Linear", EnvelopeCurve::Linear),
+                    ("Exponential", EnvelopeCurve::Exponential),
+                ],
+            )?;
+            Box::new(Adsr::new(curve.unwrap_or_default()))
+        }
+        "Svf" | "Biquad" => {
+            use FilterKind::*;
+            let filter_kind = choose(
+                parameters,
+                "kind",
+                vec![
+                    ("LowPass", LowPass),
+                    ("HighPass", HighPass),
+                    ("BandPass", BandPass),
+                    ("Notch", Notch),
+                    ("LowShelf", LowShelf),
+                    ("HighShelf", HighShelf),
+                    ("Peak", Peak),
+                ],
+            )?
+            .unwrap_or_default();
+
+            if kind == "Svf" {
+                Box::new(Svf::new(filter_kind))
+            } else {
+                Box::new(Biquad::new(filter_kind))
+            }
+        }
+        "Gain" => {
+            let unit = choose(
+                parameters,
+                "unit",
+                vec![
+                    ("Linear", GainUnit::Linear),
+                    ("Decibels", GainUnit::Decibels),
+                ],
+            )?;
+            Box::new(Gain::new(unit.unwrap_or_default()))
+        }
+        "Pan" => Box::new(Pan::new()),
+        "Mixer" => Box::new(Mixer::new(count(parameters, "inputs", 2)?)),
+        "ChannelSplit" => Box::new(ChannelSplit::new(count(parameters, "channels", 2)?)),
+        "ChannelMerge" => Box::new(ChannelMerge::new(count(parameters, "channels", 2)?)),
+        "Delay" => Box::new(Delay::default()),
+        "Chorus" => {
+            let kind = choose(
+                parameters,
+                "kind",
+                vec![
+                    ("Chorus", ModulationKind::Chorus),
+                    ("Flanger", ModulationKind::Flanger),
+                ],
+            )?;
+            Box::new(Chorus::new(kind.unwrap_or_default()))
+        }
+        "Reverb" => Box::new(Reverb::default()),
+        "NotePlayer" => Box::new(NotePlayer::load(text(parameters, "path")?)?),
+        "Sampler" => Box::new(Sampler::load(text(parameters, "path")?)?),
+        kind => return Err(anyhow!("unknown node type `{kind}`")),
+    };
+
+    Ok(node)
+}
+
+#[cfg(test)]
+mod tests {
+    use quakk::{GraphOutOutId, Quakk};
+
+    use super::*;
+
+    #[test]
+    fn loads_nodes_and_edges() {
+        let quakk = Quakk::new();
+        load(
+            &mut quakk.graph.lock().unwrap(),
+            r#"{
+                "nodes": {
+                    "a": { "type": "NumericConstant", "value": 2 },
+                    "twice": { "type": "Expression", "formula": "a * 2" }
+                },
+                "edges": [["a.Out", "twice.a"], ["twice.Out", "out.Numeric"]]
+            }"#,
+        )
+        .unwrap();
+
+        let value = quakk.fold_for(GraphOutOutId::Numeric).unwrap();
+        assert_eq!(value.as_f32().unwrap(), 4.0);
+    }
+
+    #[test]
+    fn loads_sequencer_steps() {
+        let parameters = serde_json::json!({
+            "steps": [60, { "value": 62, "gate": false }, { "ratchet": 3, "probability": 0.5 }]
+        });
+        let loaded = steps(parameters.as_object().unwrap()).unwrap();
+
+        assert_eq!(loaded[0], Step::new(60.0));
+        assert_eq!(loaded[1], Step::rest(62.0));
+        assert_eq!(loaded[2].ratchet, 3);
+        assert_eq!(loaded[2].probability, 0.5);
+
+        let parameters = serde_json::json!({ "steps": [60, "C4"] });
+        let error = steps(parameters.as_object().unwrap()).unwrap_err();
+        assert_eq!(error.to_string(), "invalid step 1");
+    }
+
+    #[test]
+    fn reports_unknown_ports_and_nodes() {
+        let error = |source: &str| load(&mut Graph::new(), source).unwrap_err().to_string();
+
+        assert_eq!(
+            error(r#"{ "nodes": { "a": { "type": "Foo" } } }"#),
+            "invalid node `a`"
+        );
+        assert_eq!(
+            error(
+                r#"{ "nodes": { "a": { "type": "NumericConstant" } }, "edges": [["a.Nope", "out.x"]] }"#
+            ),
+            "the node `a` has no output `Nope`"
+        );
+        assert_eq!(
+            error(r#"{ "nodes": {}, "edges": [["b.Out", "out.x"]] }"#),
+            "there is no node named `b`"
+        );
+        assert_eq!(
+            error(
+                r#"{ "nodes": { "a": { "type": "Mixer", "inputs": 2 } }, "edges": [["a.Out", "a.In3"]] }"#
+            ),
+            "the node `a` has no input `In3`"
+        );
+        assert_eq!(
+            error(r#"{ "nodes": { "out": { "type": "Clamp" } } }"#),
+            "the node name `out` is reserved"
+        );
+    }
+}
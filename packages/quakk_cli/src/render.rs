@@ -0,0 +1,247 @@
+//! The `render` subcommand, rendering an output of a patch to a WAV file
+//!
+//! ```text
+//! quakk_cli render <patch.json> --output <file.wav> [--duration <seconds>]
+//!     [--sample-rate <hz>] [--block-size <frames>] [--format pcm16|pcm24|float]
+//!     [--port <name>] [--sink wav|pcm|null]
+//! ```
+//!
+//! Rendering runs as fast as possible and is deterministic, the same patch always renders to the
+//! same file. The `pcm` sink writes raw interleaved samples to the standard output instead, to
+//! pipe them into other programs, and the `null` sink drops them, to measure the rendering time.
+
+use std::{path::PathBuf, time::Instant};
+
+use anyhow::{Context, anyhow};
+use quakk::{GraphOutOutId, Quakk, Quality};
+use quakk_audio::{
+    Transport,
+    sink::{AudioSink, NullSink, PcmSink, WavSink},
+    wav::WavFormat,
+};
+
+use crate::patch;
+
+/// The highest sample rate to render at, the highest audio interfaces run at
+const MAX_SAMPLE_RATE: u32 = 768_000;
+
+/// Where the rendered audio goes
+#[derive(Debug, PartialEq)]
+pub enum Output {
+    Wav(PathBuf),
+    /// Raw samples on the standard output
+    Pcm,
+    Null,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct RenderOptions {
+    pub patch: PathBuf,
+    pub output: Output,
+    pub duration: f64,
+    pub sample_rate: u32,
+    pub block_size: usize,
+    pub format: WavFormat,
+    /// The name of the output of the graph to render
+    pub port: String,
+}
+
+impl RenderOptions {
+    /// Parse the arguments following `render`
+    pub fn parse(args: impl IntoIterator<Item = String>) -> anyhow::Result<Self> {
+        let mut patch = None;
+        let mut output = None;
+        let mut sink = "wav".to_string();
+        let mut options = Self {
+            patch: PathBuf::new(),
+            output: Output::Null,
+            duration: 10.0,
+            sample_rate: 48_000,
+            block_size: 512,
+            format: WavFormat::Pcm16,
+            port: "Audio".to_string(),
+        };
+
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            if !arg.starts_with('-') {
+                if patch.replace(PathBuf::from(&arg)).is_some() {
+                    return Err(anyhow!("unexpected argument `{arg}`"));
+                }
+                continue;
+            }
+
+            let value = args
+                .next()
+                .ok_or(anyhow!("the option `{arg}` needs a value"))?;
+            let invalid = || format!("invalid value `{value}` for `{arg}`");
+
+            match arg.as_str() {
+                "--output" | "-o" => output = Some(PathBuf::from(&value)),
+                "--duration" => options.duration = value.parse().with_context(invalid)?,
+                "--sample-rate" => options.sample_rate = value.parse().with_context(invalid)?,
+                "--block-size" => options.block_size = value.parse().with_context(invalid)?,
+                "--format" => {
+                    options.format = match value.as_str() {
+                        "pcm16" => WavFormat::Pcm16,
+                        "pcm24" => WavFormat::Pcm24,
+                        "float" => WavFormat::Float32,
+                        _ => return Err(anyhow!("{}, expected pcm16, pcm24 or float", invalid())),
+                    }
+                }
+                "--port" => options.port = value,
+                "--sink" => sink = value,
+                _ => return Err(anyhow!("unknown option `{arg}`")),
+            }
+        }
+
+        if !(options.duration.is_finite() && options.duration >= 0.0) {
+            return Err(anyhow!(
+                "the duration should be a positive number of seconds"
+            ));
+        }
+        if options.sample_rate == 0 || options.block_size == 0 {
+            return Err(anyhow!("the sample rate and block size should not be 0"));
+        }
+        if options.sample_rate > MAX_SAMPLE_RATE {
+            return Err(anyhow!(
+                "the sample rate should be at most {MAX_SAMPLE_RATE} Hz"
+            ));
+        }
+
+        options.patch = patch.ok_or(anyhow!("missing the patch file to render"))?;
+        options.output = match sink.as_str() {
+            "wav" => Output::Wav(output.ok_or(anyhow!("missing the `--output` file"))?),
+            "pcm" | "null" if output.is_some() => {
+                return Err(anyhow!("the `{sink}` sink does not write to a file"));
+            }
+            "pcm" => Output::Pcm,
+            "null" => Output::Null,
+            _ => {
+                return Err(anyhow!(
+                    "invalid value `{sink}` for `--sink`, expected wav, pcm or null"
+                ));
+            }
+        };
+
+        Ok(options)
+    }
+}
+
+pub fn run(options: &RenderOptions) -> anyhow::Result<()> {
+    let source = std::fs::read_to_string(&options.patch)
+        .with_context(|| format!("could not read {}", options.patch.display()))?;
+
+    let quakk = Quakk::new();
+    patch::load(&mut quakk.graph.lock().unwrap(), &source)?;
+
+    let (mut sink, destination): (Box<dyn AudioSink>, String) = match &options.output {
+        Output::Wav(path) => (
+            Box::new(WavSink::create(path, options.format)?),
+            path.display().to_string(),
+        ),
+        Output::Pcm => (
+            Box::new(PcmSink::stdout(options.format)),
+            "the standard output".to_string(),
+        ),
+        Output::Null => (Box::new(NullSink::new()), "nowhere".to_string()),
+    };
+
+    let started = Instant::now();
+    let frames = (options.duration * options.sample_rate as f64).round() as usize;
+    Transport::new(options.sample_rate, options.block_size)?
+        .with_quality(Quality::Highest)
+        .stream(
+            &quakk,
+            GraphOutOutId::from_name(&options.port),
+            frames,
+            sink.as_mut(),
+        )
+        .with_context(|| format!("could not render the output `{}`", options.port))?;
+
+    eprintln!(
+        "rendered {:.2}s of audio in {:.2}s to {destination}",
+        options.duration,
+        started.elapsed().as_secs_f64(),
+    );
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &str) -> anyhow::Result<RenderOptions> {
+        RenderOptions::parse(args.split_whitespace().map(str::to_string))
+    }
+
+    #[test]
+    fn parses_options() {
+        let options =
+            parse("patch.json -o out.wav --duration 2.5 --format float --port Left").unwrap();
+
+        assert_eq!(options.patch, PathBuf::from("patch.json"));
+        assert_eq!(options.output, Output::Wav(PathBuf::from("out.wav")));
+        assert_eq!(options.duration, 2.5);
+        assert_eq!(options.sample_rate, 48_000);
+        assert_eq!(options.format, WavFormat::Float32);
+        assert_eq!(options.port, "Left");
+
+        assert!(parse("-o out.wav").is_err());
+        assert!(parse("patch.json").is_err());
+        assert!(parse("patch.json -o out.wav --format mp3").is_err());
+        assert!(parse("patch.json -o out.wav --duration").is_err());
+        assert!(parse("patch.json -o out.wav --block-size 0").is_err());
+        assert!(parse("patch.json -o out.wav --sample-rate 3000000000").is_err());
+        assert!(parse("patch.json -o out.wav --sample-rate 768000").is_ok());
+
+        assert_eq!(parse("patch.json --sink pcm").unwrap().output, Output::Pcm);
+        assert_eq!(
+            parse("patch.json --sink null").unwrap().output,
+            Output::Null
+        );
+        assert!(parse("patch.json -o out.wav --sink null").is_err());
+        assert!(parse("patch.json --sink alsa").is_err());
+    }
+
+    #[test]
+    fn renders_deterministically() {
+        let directory = std::env::temp_dir().join(format!("quakk-render-{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+
+        let patch = directory.join("patch.json");
+        std::fs::write(
+            &patch,
+            r#"{
+                "nodes": {
+                    "osc": { "type": "Oscillator", "waveform": "Saw" },
+                    "filter": { "type": "Svf" }
+                },
+                "edges": [["osc.Out", "filter.In"], ["filter.Out", "out.Audio"]]
+            }"#,
+        )
+        .unwrap();
+
+        let render = |name: &str| {
+            let output = directory.join(name);
+            let options = RenderOptions::parse([
+                patch.display().to_string(),
+                "-o".to_string(),
+                output.display().to_string(),
+                "--duration".to_string(),
+                "0.1".to_string(),
+            ])
+            .unwrap();
+
+            run(&options).unwrap();
+            std::fs::read(output).unwrap()
+        };
+
+        let first = render("first.wav");
+        assert_eq!(first.len(), 44 + 4800 * 2);
+        assert_eq!(first, render("second.wav"));
+
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+}
@@ -0,0 +1,10 @@
+//! MIDI for quakk
+//!
+//! Arrangements are imported from Standard MIDI Files as a list of [`Note`]s, timed in seconds,
+//! and played back by a [`NotePlayer`](player::NotePlayer) following [`Meta::time`](quakk::Meta::time).
+
+mod note;
+pub use note::Note;
+
+pub mod player;
+pub mod smf;
@@ -0,0 +1,31 @@
+/// A note of an arrangement, timed in seconds
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Note {
+    /// The MIDI pitch, `60` being the middle C and `69` the A at 440 Hz
+    pub pitch: u8,
+    /// From `1` to `127`
+    pub velocity: u8,
+    /// The MIDI channel, from `0` to `15`
+    pub channel: u8,
+    /// When the note starts, in seconds
+    pub start: f64,
+    /// How long the note is held, in seconds
+    pub duration: f64,
+}
+
+impl Note {
+    /// When the note is released, in seconds
+    pub fn end(&self) -> f64 {
+        self.start + self.duration
+    }
+
+    /// Whether the note is held at `time`, in seconds
+    pub fn is_active_at(&self, time: f64) -> bool {
+        self.start <= time && time < self.end()
+    }
+
+    /// The frequency of the pitch in Hz, in equal temperament
+    pub fn frequency(&self) -> f64 {
+        440.0 * 2f64.powf((self.pitch as f64 - 69.0) / 12.0)
+    }
+}
@@ -0,0 +1,284 @@
+//! Playing notes back, following the time of the graph
+
+use std::{fmt, fs::File, io::BufReader, path::Path, sync::Arc};
+
+use anyhow::{Context, anyhow};
+use quakk::{
+    Data, DataList, LasyFold, Meta, Node,
+    id::{InId, NodeId, NodeInId, NodeOutId, OutId},
+};
+
+use crate::{Note, smf::read_smf};
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum NotePlayerInId {
+    /// Only play the notes of this channel, from `0` to `15`, optional
+    Channel,
+}
+
+impl InId for NotePlayerInId {}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum NotePlayerOutId {
+    /// The [`Note`]s held at the current time, as a list
+    Notes,
+    /// The frequency of the last note started, in Hz, held after its release
+    Frequency,
+    /// The velocity of the last note started, between `0` and `1`
+    Velocity,
+    /// `1` while a note is held, `0` otherwise
+    Gate,
+}
+
+impl OutId for NotePlayerOutId {}
+
+/// Play a list of [`Note`]s at [`Meta::time`]
+///
+/// The `Notes` output carries every note held, for polyphonic uses, while the other outputs
+/// follow the last note started, to drive a monophonic voice.
+pub struct NotePlayer {
+    notes: Arc<[Note]>,
+}
+
+impl NotePlayer {
+    pub fn new(mut notes: Vec<Note>) -> Self {
+        notes.sort_by(|a, b| a.start.total_cmp(&b.start));
+
+        Self {
+            notes: notes.into(),
+        }
+    }
+
+    /// A player of the notes of a Standard MIDI File
+    pub fn load(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let file =
+            File::open(path).with_context(|| format!("could not open {}", path.display()))?;
+        let notes = read_smf(BufReader::new(file))
+            .with_context(|| format!("invalid {}", path.display()))?;
+
+        Ok(Self::new(notes))
+    }
+
+    /// The notes played, sorted by start
+    pub fn notes(&self) -> &[Note] {
+        &self.notes
+    }
+
+    /// The notes started at `time`, of `channel` when given
+    fn started(&self, time: f64, channel: Option<u8>) -> impl DoubleEndedIterator<Item = &Note> {
+        let started = self.notes.partition_point(|note| note.start <= time);
+
+        self.notes[..started]
+            .iter()
+            .filter(move |note| channel.is_none_or(|channel| note.channel == channel))
+    }
+}
+
+impl Default for NotePlayer {
+    fn default() -> Self {
+        Self::new(Vec::new())
+    }
+}
+
+impl fmt::Debug for NotePlayer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("NotePlayer")
+            .field("notes", &self.notes.len())
+            .finish()
+    }
+}
+
+impl Node for NotePlayer {
+    fn initialize() -> Self {
+        Self::default()
+    }
+
+    fn title(&self) -> &str {
+        "Note player"
+    }
+
+    fn fold(&self, out_id: &dyn OutId, lasy_fold: LasyFold, meta: Meta) -> anyhow::Result<Data> {
+        let Some(out_id) = out_id.as_any().downcast_ref::<NotePlayerOutId>() else {
+            return Err(anyhow!("not a valid out_id"));
+        };
+
+        let channel = if lasy_fold.is_patched(&NotePlayerInId::Channel) {
+            let channel = lasy_fold
+                .get_in(&NotePlayerInId::Channel, meta)?
+                .to_i64()
+                .context("invalid Channel")?;
+            let channel = u8::try_from(channel)
+                .ok()
+                .filter(|channel| *channel < 16)
+                .ok_or(anyhow!(
+                    "the channel should be from 0 to 15, found {channel}"
+                ))?;
+            Some(channel)
+        } else {
+            None
+        };
+
+        let time = meta.time();
+        let mut held = self
+            .started(time, channel)
+            .filter(|note| note.is_active_at(time));
+        let last = self.started(time, channel).next_back();
+
+        let data = match out_id {
+            NotePlayerOutId::Notes => {
+                Data::new(held.map(|note| Data::new(*note)).collect::<DataList>())
+            }
+            NotePlayerOutId::Frequency => {
+                Data::new(last.map(|note| note.frequency() as f32).unwrap_or(0.0))
+            }
+            NotePlayerOutId::Velocity => {
+                Data::new(last.map(|note| note.velocity as f32 / 127.0).unwrap_or(0.0))
+            }
+            NotePlayerOutId::Gate => Data::new(if held.next().is_some() { 1.0f32 } else { 0.0 }),
+        };
+
+        Ok(data)
+    }
+
+    fn node_in_id(&self, in_id: &dyn InId, node_id: NodeId) -> Option<NodeInId> {
+        in_id
+            .as_any()
+            .downcast_ref::<NotePlayerInId>()
+            .map(|in_id| NodeInId::new(node_id, in_id))
+    }
+
+    fn node_out_id(&self, out_id: &dyn OutId, node_id: NodeId) -> Option<NodeOutId> {
+        out_id
+            .as_any()
+            .downcast_ref::<NotePlayerOutId>()
+            .map(|out_id| NodeOutId::new(node_id, out_id))
+    }
+
+    fn in_ids(&self) -> Vec<Box<dyn InId>> {
+        vec![Box::new(NotePlayerInId::Channel)]
+    }
+
+    fn out_ids(&self) -> Vec<Box<dyn OutId>> {
+        vec![
+            Box::new(NotePlayerOutId::Notes),
+            Box::new(NotePlayerOutId::Frequency),
+            Box::new(NotePlayerOutId::Velocity),
+            Box::new(NotePlayerOutId::Gate),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use quakk::{GraphOutInId, GraphOutOutId, Quakk, Quality, numeric::*};
+
+    use super::*;
+
+    fn note(pitch: u8, channel: u8, start: f64, duration: f64) -> Note {
+        Note {
+            pitch,
+            velocity: 127,
+            channel,
+            start,
+            duration,
+        }
+    }
+
+    /// Fold an output of a player at a time in seconds, at 10 ticks per second
+    fn fold(
+        player: NotePlayer,
+        out_id: NotePlayerOutId,
+        channel: Option<f32>,
+        times: &[f64],
+    ) -> Vec<Data> {
+        let quakk = Quakk::new();
+        {
+            let mut graph = quakk.graph.lock().unwrap();
+            let player = graph.insert(Box::new(player));
+            if let Some(channel) = channel {
+                let channel = graph.insert(Box::new(NumericConstant::new(channel)));
+                graph
+                    .patch(
+                        channel.node_out_id(&NumericConstantOutId::Out).unwrap(),
+                        player.node_in_id(&NotePlayerInId::Channel).unwrap(),
+                    )
+                    .unwrap();
+            }
+            let out = graph.graph_out_in_id(&GraphOutInId::Numeric).unwrap();
+            graph
+                .patch(player.node_out_id(&out_id).unwrap(), out)
+                .unwrap();
+        }
+
+        times
+            .iter()
+            .map(|time| {
+                let meta = Meta {
+                    tick: (time * 10.0).round() as u64,
+                    quality: Quality::Balanced,
+                    tick_rate: 10.0,
+                    sample_rate: 48_000,
+                    block_size: 4_800,
+                };
+                quakk.fold_with(GraphOutOutId::Numeric, meta).unwrap()
+            })
+            .collect()
+    }
+
+    fn arrangement() -> NotePlayer {
+        NotePlayer::new(vec![
+            note(72, 1, 1.0, 1.0),
+            note(69, 0, 0.0, 1.5),
+            note(60, 0, 2.0, 0.5),
+        ])
+    }
+
+    #[test]
+    fn notes_held_at_the_time() {
+        let notes = fold(
+            arrangement(),
+            NotePlayerOutId::Notes,
+            None,
+            &[0.5, 1.2, 1.5],
+        );
+        let pitches = |data: &Data| -> Vec<u8> {
+            data.as_list()
+                .unwrap()
+                .iter()
+                .map(|note| note.downcast_ref::<Note>().unwrap().pitch)
+                .collect()
+        };
+
+        assert_eq!(pitches(&notes[0]), [69]);
+        assert_eq!(pitches(&notes[1]), [69, 72]);
+        // The end of a note is not held anymore
+        assert_eq!(pitches(&notes[2]), [72]);
+    }
+
+    #[test]
+    fn monophonic_outputs_follow_the_last_note() {
+        let frequencies = fold(
+            arrangement(),
+            NotePlayerOutId::Frequency,
+            Some(0.0),
+            &[0.5, 1.2, 1.8, 2.2],
+        );
+        let frequencies: Vec<f32> = frequencies
+            .iter()
+            .map(|data| data.as_f32().unwrap())
+            .collect();
+        // The note of channel 1 is not played, and the frequency holds after a release
+        assert_eq!(frequencies[..3], [440.0, 440.0, 440.0]);
+        assert!((frequencies[3] - 261.6256).abs() < 1e-3);
+
+        let gates = fold(
+            arrangement(),
+            NotePlayerOutId::Gate,
+            Some(0.0),
+            &[0.5, 1.8, 2.2, 3.0],
+        );
+        let gates: Vec<f32> = gates.iter().map(|data| data.as_f32().unwrap()).collect();
+        assert_eq!(gates, [1.0, 0.0, 1.0, 0.0]);
+    }
+}
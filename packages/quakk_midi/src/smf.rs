@@ -0,0 +1,491 @@
+//! Reading and writing Standard MIDI Files
+//!
+//! Only notes are kept : their times are converted to seconds following the tempo changes of the
+//! file, and every other event is skipped.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    io::{self, Read, Write},
+};
+
+use anyhow::{Context, anyhow};
+
+use crate::Note;
+
+/// The resolution of written files, in ticks per quarter note
+const WRITE_DIVISION: u16 = 480;
+/// The tempo of written files, 120 BPM, in microseconds per quarter note
+const WRITE_TEMPO: u32 = 500_000;
+/// The tempo of a file until its first tempo change
+const DEFAULT_TEMPO: u32 = 500_000;
+
+/// A cursor over the bytes of a chunk
+struct Bytes<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Bytes<'a> {
+    fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    fn take(&mut self, count: usize) -> anyhow::Result<&'a [u8]> {
+        if self.bytes.len() < count {
+            return Err(anyhow!("the MIDI file is truncated"));
+        }
+
+        let (taken, rest) = self.bytes.split_at(count);
+        self.bytes = rest;
+        Ok(taken)
+    }
+
+    fn byte(&mut self) -> anyhow::Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.bytes.first().copied()
+    }
+
+    fn u16(&mut self) -> anyhow::Result<u16> {
+        Ok(u16::from_be_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    fn u32(&mut self) -> anyhow::Result<u32> {
+        Ok(u32::from_be_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    /// A variable length quantity, 7 bits per byte, of at most 4 bytes
+    fn variable(&mut self) -> anyhow::Result<u32> {
+        let mut value = 0;
+        for _ in 0..4 {
+            let byte = self.byte()?;
+            value = (value << 7) | (byte & 0x7f) as u32;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+
+        Err(anyhow!("invalid variable length quantity"))
+    }
+
+    /// The id and the content of the next chunk
+    fn chunk(&mut self) -> anyhow::Result<(&'a [u8], Bytes<'a>)> {
+        let id = self.take(4)?;
+        let len = self.u32()? as usize;
+        // A truncated last chunk is read as far as it goes
+        let content = self.take(len.min(self.bytes.len()))?;
+
+        Ok((id, Bytes { bytes: content }))
+    }
+}
+
+/// How ticks are converted to seconds
+enum Timing {
+    /// Ticks per quarter note, with the tempo changes of the file as `(tick, microseconds per
+    /// quarter note)`, sorted by tick
+    Metrical {
+        division: u16,
+        tempos: Vec<(u64, u32)>,
+    },
+    /// Seconds per tick, whatever the tempo
+    Timecode(f64),
+}
+
+impl Timing {
+    fn seconds(&self, tick: u64) -> f64 {
+        match self {
+            Timing::Timecode(seconds_per_tick) => tick as f64 * seconds_per_tick,
+            Timing::Metrical { division, tempos } => {
+                let mut seconds = 0.0;
+                let mut last_tick = 0;
+                let mut tempo = DEFAULT_TEMPO;
+
+                for &(change, next_tempo) in tempos.iter().take_while(|(change, _)| *change < tick)
+                {
+                    seconds += (change - last_tick) as f64 * tempo as f64;
+                    last_tick = change;
+                    tempo = next_tempo;
+                }
+                seconds += (tick - last_tick) as f64 * tempo as f64;
+
+                seconds / (*division as f64 * 1_000_000.0)
+            }
+        }
+    }
+}
+
+/// A note of a track, timed in ticks
+struct TrackNote {
+    pitch: u8,
+    velocity: u8,
+    channel: u8,
+    start: u64,
+    end: u64,
+}
+
+/// Read the notes and tempo changes of a track, tempo changes being pushed to `tempos`
+fn read_track(mut bytes: Bytes, tempos: &mut Vec<(u64, u32)>) -> anyhow::Result<Vec<TrackNote>> {
+    let mut notes = Vec::new();
+    // The notes being held, by channel and pitch, with their start and velocity
+    let mut held: HashMap<(u8, u8), VecDeque<(u64, u8)>> = HashMap::new();
+    let mut tick = 0u64;
+    let mut running_status = None;
+
+    while !bytes.is_empty() {
+        tick += bytes.variable()? as u64;
+
+        let status = match bytes.peek() {
+            Some(byte) if byte & 0x80 != 0 => bytes.byte()?,
+            _ => running_status.ok_or(anyhow!("a data byte has no running status"))?,
+        };
+
+        match status {
+            0x80..=0xef => {
+                running_status = Some(status);
+                let channel = status & 0x0f;
+
+                match status & 0xf0 {
+                    0x80 | 0x90 => {
+                        let pitch = bytes.byte()? & 0x7f;
+                        let velocity = bytes.byte()? & 0x7f;
+
+                        if status & 0xf0 == 0x90 && velocity > 0 {
+                            held.entry((channel, pitch))
+                                .or_default()
+                                .push_back((tick, velocity));
+                        } else if let Some((start, velocity)) = held
+                            .get_mut(&(channel, pitch))
+                            .and_then(VecDeque::pop_front)
+                        {
+                            notes.push(TrackNote {
+                                pitch,
+                                velocity,
+                                channel,
+                                start,
+                                end: tick,
+                            });
+                        }
+                    }
+                    // Program change and channel pressure have a single data byte
+                    0xc0 | 0xd0 => {
+                        bytes.take(1)?;
+                    }
+                    _ => {
+                        bytes.take(2)?;
+                    }
+                }
+            }
+            0xff => {
+                running_status = None;
+                let kind = bytes.byte()?;
+                let len = bytes.variable()? as usize;
+                let data = bytes.take(len)?;
+
+                match (kind, data) {
+                    (0x51, [a, b, c]) => {
+                        tempos.push((tick, u32::from_be_bytes([0, *a, *b, *c])));
+                    }
+                    (0x2f, _) => break,
+                    _ => {}
+                }
+            }
+            0xf0 | 0xf7 => {
+                running_status = None;
+                let len = bytes.variable()? as usize;
+                bytes.take(len)?;
+            }
+            status => return Err(anyhow!("invalid status byte {status:#04x}")),
+        }
+    }
+
+    // Notes never released end with the track
+    for ((channel, pitch), starts) in held {
+        notes.extend(starts.into_iter().map(|(start, velocity)| TrackNote {
+            pitch,
+            velocity,
+            channel,
+            start,
+            end: tick,
+        }));
+    }
+
+    Ok(notes)
+}
+
+/// Read the notes of a whole Standard MIDI File, of any format, sorted by start then by pitch
+pub fn read_smf(mut reader: impl Read) -> anyhow::Result<Vec<Note>> {
+    let mut content = Vec::new();
+    reader
+        .read_to_end(&mut content)
+        .context("could not read the MIDI file")?;
+    let mut bytes = Bytes { bytes: &content };
+
+    let (id, mut header) = bytes.chunk().context("not a MIDI file")?;
+    if id != b"MThd" {
+        return Err(anyhow!("not a MIDI file"));
+    }
+    let _format = header.u16()?;
+    let track_count = header.u16()?;
+    let division = header.u16()?;
+
+    let mut tempos = Vec::new();
+    let mut notes = Vec::new();
+    let mut tracks = 0;
+
+    while tracks < track_count && !bytes.is_empty() {
+        let (id, track) = bytes.chunk()?;
+        // Unknown chunks should be skipped
+        if id == b"MTrk" {
+            tracks += 1;
+            notes.extend(
+                read_track(track, &mut tempos)
+                    .with_context(|| format!("invalid track {tracks}"))?,
+            );
+        }
+    }
+
+    let timing = if division & 0x8000 == 0 {
+        if division == 0 {
+            return Err(anyhow!("the MIDI file has a division of 0"));
+        }
+
+        tempos.sort_by_key(|(tick, _)| *tick);
+        Timing::Metrical { division, tempos }
+    } else {
+        // The negated frame rate, in i16 as negating -128 overflows an i8
+        let frames_per_second = match -i16::from((division >> 8) as u8 as i8) {
+            29 => 29.97,
+            fps @ (24 | 25 | 30) => fps as f64,
+            _ => 0.0,
+        };
+        let ticks_per_frame = (division & 0xff) as f64;
+        if frames_per_second == 0.0 || ticks_per_frame == 0.0 {
+            return Err(anyhow!("the MIDI file has an invalid timecode division"));
+        }
+
+        Timing::Timecode(1.0 / (frames_per_second * ticks_per_frame))
+    };
+
+    let mut notes: Vec<Note> = notes
+        .into_iter()
+        .map(|note| {
+            let start = timing.seconds(note.start);
+            Note {
+                pitch: note.pitch,
+                velocity: note.velocity,
+                channel: note.channel,
+                start,
+                duration: timing.seconds(note.end) - start,
+            }
+        })
+        .collect();
+    notes.sort_by(|a, b| a.start.total_cmp(&b.start).then(a.pitch.cmp(&b.pitch)));
+
+    Ok(notes)
+}
+
+fn write_variable(bytes: &mut Vec<u8>, mut value: u32) {
+    let mut groups = vec![(value & 0x7f) as u8];
+    value >>= 7;
+    while value > 0 {
+        groups.push((value & 0x7f) as u8 | 0x80);
+        value >>= 7;
+    }
+
+    bytes.extend(groups.iter().rev());
+}
+
+/// Write notes as a single track Standard MIDI File, at 120 BPM
+///
+/// Times are rounded to the closest tick, a 960th of a second. Pitches and velocities are clamped
+/// to their MIDI range, a velocity of at least `1` keeping a note from reading as a release.
+pub fn write_smf(mut writer: impl Write, notes: &[Note]) -> io::Result<()> {
+    let ticks_per_second = WRITE_DIVISION as f64 * 1_000_000.0 / WRITE_TEMPO as f64;
+    let tick = |seconds: f64| (seconds.max(0.0) * ticks_per_second).round() as u64;
+
+    // Releases come before the starts at the same tick, so a repeated note is not cut at once
+    let mut events = Vec::new();
+    for note in notes {
+        let channel = note.channel & 0x0f;
+        let pitch = note.pitch.min(127);
+        let start = tick(note.start);
+        let end = tick(note.end()).max(start);
+
+        events.push((
+            start,
+            1,
+            [0x90 | channel, pitch, note.velocity.clamp(1, 127)],
+        ));
+        events.push((end, 0, [0x80 | channel, pitch, 0]));
+    }
+    events.sort_by_key(|(tick, order, _)| (*tick, *order));
+
+    let mut track = Vec::new();
+    write_variable(&mut track, 0);
+    track.extend([0xff, 0x51, 0x03]);
+    track.extend(&WRITE_TEMPO.to_be_bytes()[1..]);
+
+    let mut last_tick = 0;
+    for (tick, _, event) in events {
+        // Variable length quantities hold at most 28 bits
+        let delta = u32::try_from(tick - last_tick)
+            .ok()
+            .filter(|delta| *delta <= 0x0fff_ffff)
+            .ok_or(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "the notes are too far apart for a MIDI file",
+            ))?;
+        write_variable(&mut track, delta);
+        track.extend(event);
+        last_tick = tick;
+    }
+
+    write_variable(&mut track, 0);
+    track.extend([0xff, 0x2f, 0x00]);
+
+    let track_len = u32::try_from(track.len()).map_err(|_| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "the notes are too many for a MIDI file",
+        )
+    })?;
+
+    writer.write_all(b"MThd")?;
+    writer.write_all(&6u32.to_be_bytes())?;
+    // Format 0, a single track
+    writer.write_all(&0u16.to_be_bytes())?;
+    writer.write_all(&1u16.to_be_bytes())?;
+    writer.write_all(&WRITE_DIVISION.to_be_bytes())?;
+
+    writer.write_all(b"MTrk")?;
+    writer.write_all(&track_len.to_be_bytes())?;
+    writer.write_all(&track)?;
+
+    writer.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chunk(id: &[u8], content: &[u8]) -> Vec<u8> {
+        [id, &(content.len() as u32).to_be_bytes(), content].concat()
+    }
+
+    #[test]
+    fn variable_length_quantities() {
+        for (value, encoded) in [
+            (0, vec![0x00]),
+            (0x7f, vec![0x7f]),
+            (0x80, vec![0x81, 0x00]),
+            (0x0fff_ffff, vec![0xff, 0xff, 0xff, 0x7f]),
+        ] {
+            let mut bytes = Vec::new();
+            write_variable(&mut bytes, value);
+            assert_eq!(bytes, encoded);
+            assert_eq!(Bytes { bytes: &encoded }.variable().unwrap(), value);
+        }
+    }
+
+    #[test]
+    fn reads_a_format_1_file() {
+        let header = chunk(b"MThd", &[0, 1, 0, 2, 0, 96]);
+        // The tempo goes from 120 BPM to 60 BPM after one quarter note
+        let tempo_track = chunk(
+            b"MTrk",
+            &[
+                0x00, 0xff, 0x51, 0x03, 0x07, 0xa1, 0x20, // 500 000 µs
+                0x60, 0xff, 0x51, 0x03, 0x0f, 0x42, 0x40, // 1 000 000 µs
+                0x00, 0xff, 0x2f, 0x00,
+            ],
+        );
+        let note_track = chunk(
+            b"MTrk",
+            &[
+                0x00, 0x91, 60, 100, // A note on channel 1
+                0x30, 64, 80, // Running status, a second note
+                0x30, 60, 0, // Velocity 0 releases the first note
+                0x00, 0xc1, 5, // A program change is skipped
+                0x60, 0x81, 64, 0, // The second note is released after the tempo change
+                0x00, 0xff, 0x2f, 0x00,
+            ],
+        );
+        let file = [header, chunk(b"XFOO", b"skipped"), tempo_track, note_track].concat();
+
+        let notes = read_smf(file.as_slice()).unwrap();
+        assert_eq!(
+            notes,
+            [
+                Note {
+                    pitch: 60,
+                    velocity: 100,
+                    channel: 1,
+                    start: 0.0,
+                    duration: 0.5
+                },
+                Note {
+                    pitch: 64,
+                    velocity: 80,
+                    channel: 1,
+                    start: 0.25,
+                    duration: 1.25
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn reads_back_what_it_writes() {
+        let notes = vec![
+            Note {
+                pitch: 57,
+                velocity: 90,
+                channel: 0,
+                start: 0.0,
+                duration: 0.5,
+            },
+            Note {
+                pitch: 57,
+                velocity: 64,
+                channel: 0,
+                start: 0.5,
+                duration: 0.25,
+            },
+            Note {
+                pitch: 81,
+                velocity: 127,
+                channel: 9,
+                start: 0.125,
+                duration: 2.0,
+            },
+        ];
+
+        let mut bytes = Vec::new();
+        write_smf(&mut bytes, &notes).unwrap();
+        let mut read = read_smf(bytes.as_slice()).unwrap();
+
+        let mut expected = notes.clone();
+        expected.sort_by(|a, b| a.start.total_cmp(&b.start));
+        read.sort_by(|a, b| a.start.total_cmp(&b.start));
+        assert_eq!(read, expected);
+    }
+
+    #[test]
+    fn rejects_invalid_files() {
+        assert!(read_smf(b"RIFF".as_slice()).is_err());
+
+        let header = chunk(b"MThd", &[0, 0, 0, 1, 0, 96]);
+        let track = chunk(b"MTrk", &[0x00, 60, 100]);
+        let error = read_smf([header, track].concat().as_slice()).unwrap_err();
+        assert_eq!(error.to_string(), "invalid track 1");
+
+        let header = chunk(b"MThd", &[0, 0, 0, 1, 0x80, 0x04]);
+        let track = chunk(b"MTrk", &[0x00, 0xff, 0x2f, 0x00]);
+        let error = read_smf([header, track].concat().as_slice()).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "the MIDI file has an invalid timecode division"
+        );
+    }
+}